@@ -0,0 +1,119 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use std::env;
+use std::process::ExitCode;
+
+use iks::{DiffOptions, Document, ParseError, ParseErrorKind, ReadError};
+
+fn print_version() {
+    println!("iksdiff (iksemel) v{}", iks::VERSION);
+}
+
+fn print_usage() {
+    println!(concat!(
+        "Usage: iksdiff [OPTIONS] <OLD.xml> <NEW.xml>\n",
+        "This tool prints the structural changes between two XML documents.\n",
+        "Exit status is 0 if the documents are equal, 1 if different, 2 if in trouble.\n",
+        "Options:\n",
+        "  -o, --attribute-order  Report the attribute order changes\n",
+        "  -w, --ignore-space     Ignore whitespace-only text nodes\n",
+        "  -n, --ignore-prefixes  Compare names by namespace URI and local name\n",
+        "  -h, --help             Display this help message and exit\n",
+        "  -v, --version          Display the version and exit\n",
+        "Report issues at https://github.com/meduketto/iksemel-rust/issues"
+    ));
+}
+
+fn load_xml_file(file_name: &str) -> Result<Document, ExitCode> {
+    match Document::from_file(file_name) {
+        Ok(document) => Ok(document),
+        Err(ReadError::Io(err)) => {
+            eprintln!("Error: io error in file '{}': {}", file_name, err);
+            Err(ExitCode::from(2))
+        }
+        Err(ReadError::Parse(ParseError::BadXml { kind, location })) => {
+            match location {
+                Some(location) => eprintln!(
+                    "Error: syntax error in file '{}' at {}: {}",
+                    file_name, location, kind
+                ),
+                None => eprintln!("Error: syntax error in file '{}': {}", file_name, kind),
+            }
+            if let ParseErrorKind::TagMismatch(mismatch) = kind
+                && let Some(open_location) = mismatch.open_location
+            {
+                eprintln!(
+                    "Error: start tag '{}' is at {}",
+                    mismatch.open_name, open_location
+                );
+            }
+            Err(ExitCode::from(2))
+        }
+        Err(ReadError::Parse(err)) => {
+            eprintln!("Error: {} in file '{}'", err, file_name);
+            Err(ExitCode::from(2))
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let mut files: Vec<String> = Vec::new();
+    let mut options = DiffOptions::default();
+
+    // Skip the first argument (program name)
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "-o" | "--attribute-order" => {
+                options.ignore_attribute_order = false;
+            }
+            "-w" | "--ignore-space" => {
+                options.ignore_whitespace = true;
+            }
+            "-n" | "--ignore-prefixes" => {
+                options.ignore_prefixes = true;
+            }
+            "-h" | "--help" => {
+                print_usage();
+                return ExitCode::SUCCESS;
+            }
+            "-v" | "--version" => {
+                print_version();
+                return ExitCode::SUCCESS;
+            }
+            _ => {
+                files.push(arg);
+            }
+        }
+    }
+    if files.len() != 2 {
+        eprintln!("Error: two files are expected, see --help for usage");
+        return ExitCode::from(2);
+    }
+
+    let old = match load_xml_file(&files[0]) {
+        Ok(document) => document,
+        Err(code) => return code,
+    };
+    let new = match load_xml_file(&files[1]) {
+        Ok(document) => document,
+        Err(code) => return code,
+    };
+
+    let edits = old.diff(&new, &options);
+    for edit in edits.iter() {
+        println!("{}", edit);
+    }
+    if edits.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
@@ -9,7 +9,6 @@
 */
 
 use std::collections::HashMap;
-use std::collections::HashSet;
 use std::env;
 use std::fs::File;
 use std::io::Read;
@@ -18,9 +17,11 @@ use std::process::ExitCode;
 use std::vec::Vec;
 
 use iks::ParseError;
+use iks::ParseErrorKind;
+use iks::ReadError;
 use iks::SaxElement;
-use iks::SaxElements;
 use iks::SaxParser;
+use iks::SaxReader;
 
 const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
 
@@ -37,10 +38,11 @@ fn print_usage() {
             "  -s, --stat           Overall statistics\n",
             "  -c, --count          Tag counts\n",
             "  -t, --tokenize       Print SAX tokens\n",
+            "  -r, --recover        Report all errors instead of stopping at the first one\n",
             "  -b, --buffer <SIZE>  File read buffer size in bytes (default: {})\n",
             "  -h, --help           Display this help message and exit\n",
             "  -v, --version        Display the version and exit\n",
-            "Boolean single-character options can be combined (e.g. -sct)\n",
+            "Boolean single-character options can be combined (e.g. -sctr)\n",
             "Input is read from stdin if no files or '-' are specified.\n",
             "Report issues at https://github.com/meduketto/iksemel-rust/issues"
         ),
@@ -57,10 +59,7 @@ struct Handler {
     nr_tags: usize,
     nr_empty_tags: usize,
     nr_cdata_size: usize,
-    tag_stack: Vec<String>,
     tag_map: HashMap<String, usize>,
-    attribute_map: HashSet<String>,
-    error: Option<String>,
 }
 
 impl Handler {
@@ -74,14 +73,11 @@ impl Handler {
             nr_tags: 0,
             nr_empty_tags: 0,
             nr_cdata_size: 0,
-            tag_stack: Vec::new(),
             tag_map: HashMap::new(),
-            attribute_map: HashSet::new(),
-            error: None,
         }
     }
 
-    fn process_element(&mut self, element: &SaxElement) -> Result<(), ParseError> {
+    fn process_element(&mut self, element: &SaxElement) {
         if self.do_tokenize {
             println!("{:?}", element);
         }
@@ -93,37 +89,20 @@ impl Handler {
                 if self.do_tag_count {
                     *self.tag_map.entry(name.to_string()).or_insert(0) += 1;
                 }
-                self.tag_stack.push(name.to_string());
-                self.attribute_map.clear();
             }
-            SaxElement::Attribute(name, _value) => {
-                if self.attribute_map.contains(*name) {
-                    self.error = Some(format!("duplicate attribute: '{}'", name));
-                    return Err(ParseError::BadXml("duplicate attribute"));
-                }
-                self.attribute_map.insert(name.to_string());
-            }
-            SaxElement::StartTagContent => {}
+            SaxElement::Attribute(..) | SaxElement::StartTagContent => {}
             SaxElement::StartTagEmpty => {
                 self.nr_empty_tags += 1;
-                self.tag_stack.pop();
             }
-            SaxElement::CData(cdata) => {
+            SaxElement::CData(cdata)
+            | SaxElement::CDataSection(cdata)
+            | SaxElement::Reference(cdata) => {
                 self.nr_cdata_size += cdata.len();
             }
-            SaxElement::EndTag(name) => {
+            SaxElement::EndTag(_) => {
                 self.level -= 1;
-                let start_name = self.tag_stack.pop().unwrap();
-                if &start_name != name {
-                    self.error = Some(format!(
-                        "end tag mismatch: expected '{}', got '{}'",
-                        start_name, name
-                    ));
-                    return Err(ParseError::BadXml("end tag mismatch"));
-                }
             }
         }
-        Ok(())
     }
 
     fn report(&mut self) {
@@ -148,26 +127,7 @@ impl Handler {
         self.nr_tags = 0;
         self.nr_empty_tags = 0;
         self.nr_cdata_size = 0;
-        self.tag_stack.clear();
         self.tag_map.clear();
-        self.attribute_map.clear();
-    }
-}
-
-enum LinterError {
-    IoError(std::io::Error),
-    ParseError(ParseError),
-}
-
-impl From<std::io::Error> for LinterError {
-    fn from(err: std::io::Error) -> Self {
-        LinterError::IoError(err)
-    }
-}
-
-impl From<ParseError> for LinterError {
-    fn from(err: ParseError) -> Self {
-        LinterError::ParseError(err)
     }
 }
 
@@ -178,68 +138,93 @@ struct Linter {
 }
 
 impl Linter {
-    fn new(do_stats: bool, do_tag_count: bool, do_tokenize: bool, buffer_size: usize) -> Self {
+    fn new(
+        do_stats: bool,
+        do_tag_count: bool,
+        do_tokenize: bool,
+        do_recover: bool,
+        buffer_size: usize,
+    ) -> Self {
+        let mut parser = SaxParser::new();
+        parser.set_strict(true);
+        parser.set_recovery(do_recover);
         Linter {
             handler: Handler::new(do_stats, do_tag_count, do_tokenize),
-            parser: SaxParser::new(),
+            parser,
             buffer_size,
         }
     }
 
-    fn parse_file(&mut self, file: &str, is_stream: bool) -> Result<(), LinterError> {
-        let mut f: Box<dyn Read> = if is_stream {
+    fn parse_file(&mut self, file: &str, is_stream: bool) -> Result<(), ReadError> {
+        let f: Box<dyn Read> = if is_stream {
             Box::new(stdin())
         } else {
             Box::new(File::open(file)?)
         };
-        let mut buffer = vec![0u8; self.buffer_size];
-        loop {
-            let bytes_read = f.read(&mut buffer)?;
-            if bytes_read == 0 {
-                break;
-            }
-            let mut elements = SaxElements::new(&mut self.parser, &buffer[..bytes_read]);
-            loop {
-                match elements.next() {
-                    Some(Ok(element)) => {
-                        self.handler.process_element(&element)?;
-                    }
-                    Some(Err(err)) => return Err(err.into()),
-                    None => {
-                        break;
-                    }
-                }
-            }
+        let parser = std::mem::take(&mut self.parser);
+        let mut reader = SaxReader::with_buffer_size(f, parser, self.buffer_size);
+        let result = self.parse_elements(&mut reader);
+        (_, self.parser) = reader.into_inner();
+        result
+    }
+
+    fn parse_elements(&mut self, reader: &mut SaxReader<Box<dyn Read>>) -> Result<(), ReadError> {
+        while let Some(result) = reader.next() {
+            self.handler.process_element(&result?);
         }
-        Ok(self.parser.parse_finish()?)
+        Ok(())
     }
 
-    fn lint_file(&mut self, file: &str, is_stream: bool) -> bool {
-        self.parser.reset();
-        match self.parse_file(file, is_stream) {
-            Ok(()) => {
-                self.handler.report();
-                true
-            }
-            Err(LinterError::IoError(e)) => {
-                eprintln!("Error reading file '{}': {}", file, e);
-                false
-            }
-            Err(LinterError::ParseError(ParseError::NoMemory)) => {
+    fn print_error(&self, file: &str, err: ParseError) {
+        match err {
+            ParseError::NoMemory => {
                 eprintln!("Memory allocation failed while parsing '{}'", file);
-                false
             }
-            Err(LinterError::ParseError(ParseError::BadXml(msg))) => {
+            ParseError::BadXml { kind, location } => {
                 eprintln!(
                     "Syntax error in file '{}' at {}: {}",
                     file,
+                    location.unwrap_or(self.parser.location()),
+                    kind
+                );
+                if let ParseErrorKind::TagMismatch(mismatch) = kind
+                    && let Some(open_location) = mismatch.open_location
+                {
+                    eprintln!("Start tag '{}' is at {}", mismatch.open_name, open_location);
+                }
+            }
+            err => {
+                eprintln!(
+                    "Error in file '{}' at {}: {}",
+                    file,
                     self.parser.location(),
-                    msg
+                    err
                 );
-                false
             }
         }
     }
+
+    fn lint_file(&mut self, file: &str, is_stream: bool) -> bool {
+        self.parser.reset();
+        let result = self.parse_file(file, is_stream);
+        let mut errors = self.parser.take_diagnostics();
+        match result {
+            Ok(()) => (),
+            Err(ReadError::Io(e)) => {
+                eprintln!("Error reading file '{}': {}", file, e);
+                return false;
+            }
+            Err(ReadError::Parse(err)) => errors.push(err),
+        }
+        if errors.is_empty() {
+            self.handler.report();
+            return true;
+        }
+        for err in errors {
+            self.print_error(file, err);
+        }
+        false
+    }
 }
 
 fn main() -> ExitCode {
@@ -249,6 +234,7 @@ fn main() -> ExitCode {
     let mut do_stats = false;
     let mut do_tag_count = false;
     let mut do_tokenize = false;
+    let mut do_recover = false;
     let mut stdin_only = false;
     let mut buffer_size = DEFAULT_BUFFER_SIZE;
 
@@ -265,6 +251,9 @@ fn main() -> ExitCode {
             "-t" | "--tokenize" => {
                 do_tokenize = true;
             }
+            "-r" | "--recover" => {
+                do_recover = true;
+            }
             "-b" | "--buffer" => {
                 if let Some(size) = args.next() {
                     if let Ok(size) = size.parse::<usize>() {
@@ -306,6 +295,7 @@ fn main() -> ExitCode {
                             's' => do_stats = true,
                             'c' => do_tag_count = true,
                             't' => do_tokenize = true,
+                            'r' => do_recover = true,
                             _ => {
                                 eprintln!("Unknown combined option {} in {}", char, arg);
                                 return ExitCode::FAILURE;
@@ -324,7 +314,7 @@ fn main() -> ExitCode {
         return ExitCode::FAILURE;
     }
 
-    let mut linter = Linter::new(do_stats, do_tag_count, do_tokenize, buffer_size);
+    let mut linter = Linter::new(do_stats, do_tag_count, do_tokenize, do_recover, buffer_size);
     if files.is_empty() {
         if !linter.lint_file("stdin", true) {
             return ExitCode::FAILURE;
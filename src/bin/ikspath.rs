@@ -11,11 +11,13 @@
 use std::env;
 use std::fs::File;
 use std::fs::metadata;
-use std::io::Read;
 use std::io::stdin;
 use std::process::ExitCode;
 
-use iks::{Document, DocumentParser, ParseError, XPath};
+use iks::{
+    Document, DocumentParser, Location, ParseError, ParseErrorKind, ReadError, SerializeOptions,
+    XPath, XPathValue,
+};
 
 fn print_version() {
     println!("ikspath (iksemel) v{}", iks::VERSION);
@@ -28,6 +30,7 @@ fn print_usage() {
         "Options:\n",
         "  -f, --file <FILE.xml>  Specify the XML file to process\n",
         "  -m, --memory           Display document memory usage\n",
+        "  -p, --pretty           Indent the result elements for readability\n",
         "  -h, --help             Display this help message and exit\n",
         "  -v, --version          Display the version and exit\n",
         "Report issues at https://github.com/meduketto/iksemel-rust/issues"
@@ -37,7 +40,8 @@ fn print_usage() {
 enum IkspathError {
     IoError(std::io::Error),
     NoMemory,
-    BadXml(&'static str),
+    BadXml(ParseErrorKind, Option<Location>),
+    LimitExceeded(ParseError),
 }
 
 impl From<std::io::Error> for IkspathError {
@@ -50,7 +54,17 @@ impl From<ParseError> for IkspathError {
     fn from(err: ParseError) -> Self {
         match err {
             ParseError::NoMemory => IkspathError::NoMemory,
-            ParseError::BadXml(msg) => IkspathError::BadXml(msg),
+            ParseError::BadXml { kind, location } => IkspathError::BadXml(kind, location),
+            _ => IkspathError::LimitExceeded(err),
+        }
+    }
+}
+
+impl From<ReadError> for IkspathError {
+    fn from(err: ReadError) -> Self {
+        match err {
+            ReadError::Io(err) => err.into(),
+            ReadError::Parse(err) => err.into(),
         }
     }
 }
@@ -59,17 +73,9 @@ fn load_xml_file(
     parser: &mut DocumentParser,
     file: Option<String>,
 ) -> Result<Document, IkspathError> {
-    let mut f: Box<dyn Read> = match file {
-        None => Box::new(stdin()),
-        Some(file_name) => Box::new(File::open(file_name)?),
-    };
-    let mut buffer = vec![0u8; 256 * 1024];
-    loop {
-        let bytes_read = f.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
-        }
-        parser.parse_bytes(&buffer[..bytes_read])?;
+    match file {
+        None => parser.parse_reader(stdin())?,
+        Some(file_name) => parser.parse_reader(File::open(file_name)?)?,
     }
     Ok(parser.take_document()?)
 }
@@ -80,6 +86,7 @@ fn main() -> ExitCode {
     let mut file: Option<String> = None;
     let mut expression: Option<XPath> = None;
     let mut memory_usage = false;
+    let mut pretty = false;
 
     // Skip the first argument (program name)
     args.next();
@@ -96,6 +103,9 @@ fn main() -> ExitCode {
             "-m" | "--memory" => {
                 memory_usage = true;
             }
+            "-p" | "--pretty" => {
+                pretty = true;
+            }
             "-h" | "--help" => {
                 print_usage();
                 return ExitCode::SUCCESS;
@@ -148,13 +158,25 @@ fn main() -> ExitCode {
             eprintln!("Error: not enough memory");
             return ExitCode::FAILURE;
         }
-        Err(IkspathError::BadXml(err)) => {
+        Err(IkspathError::BadXml(kind, location)) => {
             eprintln!(
                 "Error: syntax error in {} at {}: {}",
                 file_desc,
-                parser.location(),
-                err
+                location.unwrap_or(parser.location()),
+                kind
             );
+            if let ParseErrorKind::TagMismatch(mismatch) = kind
+                && let Some(open_location) = mismatch.open_location
+            {
+                eprintln!(
+                    "Error: start tag '{}' is at {}",
+                    mismatch.open_name, open_location
+                );
+            }
+            return ExitCode::FAILURE;
+        }
+        Err(IkspathError::LimitExceeded(err)) => {
+            eprintln!("Error: {} in {}", err, file_desc);
             return ExitCode::FAILURE;
         }
     };
@@ -165,7 +187,16 @@ fn main() -> ExitCode {
 
     if let Some(xpath) = expression {
         let sequence = xpath.apply(&document).unwrap();
-        println!("{}", sequence);
+        if pretty {
+            let options = SerializeOptions::pretty();
+            for value in sequence.items.iter() {
+                match value {
+                    XPathValue::Node(node) => println!("{}", node.to_string_with(&options)),
+                }
+            }
+        } else {
+            println!("{}", sequence);
+        }
     }
 
     ExitCode::SUCCESS
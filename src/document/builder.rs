@@ -11,17 +11,19 @@
 use std::ptr::null_mut;
 
 use crate::ParseError;
+use crate::ParseErrorKind;
 use crate::SaxElement;
+use crate::TagMismatch;
 
 use super::Cursor;
 use super::Document;
 use super::Node;
-use super::error::description;
 
 pub struct DocumentBuilder {
     doc: Option<Document>,
     node: *mut Node,
     size_hint: Option<usize>,
+    recovery: bool,
 }
 
 impl DocumentBuilder {
@@ -30,6 +32,7 @@ impl DocumentBuilder {
             doc: None,
             node: null_mut(),
             size_hint: None,
+            recovery: false,
         }
     }
 
@@ -38,9 +41,21 @@ impl DocumentBuilder {
             doc: None,
             node: null_mut(),
             size_hint: Some(size_hint),
+            recovery: false,
         }
     }
 
+    /// Enables or disables auto-closing of the mismatched tags.
+    ///
+    /// When enabled, an end tag closes all the open tags up to the
+    /// nearest one with the same name, or it is ignored if there is no
+    /// such tag. The mismatch error is still returned for reporting,
+    /// but the builder can continue with the next element. This is
+    /// useful with the recovery mode of the [SaxParser](crate::SaxParser).
+    pub fn set_recovery(&mut self, recovery: bool) {
+        self.recovery = recovery;
+    }
+
     pub fn append_element(&mut self, element: &SaxElement) -> Result<(), ParseError> {
         match &self.doc {
             None => match element {
@@ -52,34 +67,67 @@ impl DocumentBuilder {
                     self.node = doc.root().get_node_ptr();
                     self.doc = Some(doc);
                 }
-                _ => return Err(ParseError::BadXml(description::NO_START_TAG)),
+                _ => return Err(ParseErrorKind::NoStartTag.into()),
             },
             Some(doc) => match element {
                 SaxElement::StartTag(name) => {
-                    let new_tag = Cursor::new(self.node, &doc.arena).insert_tag(name)?;
+                    let new_tag = Cursor::new(self.node, doc).insert_tag(name)?;
                     self.node = new_tag.get_node_ptr();
                 }
                 SaxElement::Attribute(name, value) => {
-                    Cursor::new(self.node, &doc.arena).insert_attribute(name, value)?;
+                    Cursor::new(self.node, doc).insert_attribute(name, value)?;
                 }
                 SaxElement::StartTagContent => {}
                 SaxElement::StartTagEmpty => {
-                    self.node = Cursor::new(self.node, &doc.arena).parent().get_node_ptr();
+                    self.node = Cursor::new(self.node, doc).parent().get_node_ptr();
                 }
-                SaxElement::CData(cdata) => {
-                    Cursor::new(self.node, &doc.arena).insert_cdata(cdata)?;
+                SaxElement::CData(cdata)
+                | SaxElement::CDataSection(cdata)
+                | SaxElement::Reference(cdata) => {
+                    Cursor::new(self.node, doc).insert_cdata(cdata)?;
                 }
                 SaxElement::EndTag(name) => {
-                    if name != &Cursor::new(self.node, &doc.arena).name() {
-                        return Err(ParseError::BadXml(description::TAG_MISMATCH));
+                    let open_tag = Cursor::new(self.node, doc);
+                    let open_name = open_tag.name();
+                    if name != &open_name {
+                        if self.recovery {
+                            let mut tag = open_tag.clone();
+                            while !tag.is_null() && tag.name() != *name {
+                                tag = tag.parent();
+                            }
+                            if !tag.is_null() {
+                                self.node = tag.parent().get_node_ptr();
+                            }
+                        }
+                        return Err(ParseErrorKind::TagMismatch(Box::new(TagMismatch {
+                            open_name: open_name.to_string(),
+                            open_location: None,
+                            close_name: name.to_string(),
+                            close_location: None,
+                        }))
+                        .into());
                     }
-                    self.node = Cursor::new(self.node, &doc.arena).parent().get_node_ptr();
+                    self.node = Cursor::new(self.node, doc).parent().get_node_ptr();
                 }
             },
         }
         Ok(())
     }
 
+    // Returns the number of the open tags.
+    pub(super) fn depth(&self) -> usize {
+        let Some(doc) = &self.doc else {
+            return 0;
+        };
+        let mut depth = 0;
+        let mut tag = Cursor::new(self.node, doc);
+        while !tag.is_null() {
+            depth += 1;
+            tag = tag.parent();
+        }
+        depth
+    }
+
     pub fn peek(&self) -> Option<&Document> {
         self.doc.as_ref()
     }
@@ -98,3 +146,6 @@ impl Default for DocumentBuilder {
         Self::new()
     }
 }
+
+// The node pointer always points into the arena of the owned document.
+unsafe impl Send for DocumentBuilder {}
@@ -0,0 +1,346 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use std::fmt::Write;
+
+use crate::XML_NS;
+use crate::parser::declared_prefix;
+
+use super::Cursor;
+use super::Document;
+use super::Tag;
+use super::VisitorStep;
+use super::serialize::IoWriter;
+
+/// Canonicalization algorithm of the canonical XML output.
+///
+/// Canonical forms are used for signing and hashing documents. Both
+/// algorithms sort the attributes, write the namespace declarations
+/// in a fixed order, use a fixed escaping set, and never use the
+/// self-closing tags.
+///
+/// Comments and processing instructions are not kept in a
+/// [Document](crate::Document), so the output is always the "without
+/// comments" form of the element tree.
+///
+/// # Examples
+///
+/// ```
+/// use std::str::FromStr;
+/// use iks::{Canonicalization, Document, ParseError};
+/// # fn main() -> Result<(), ParseError> {
+///
+/// let doc = Document::from_str("<a xmlns:n='urn:x' y='2' x='1'><n:b/></a>")?;
+///
+/// assert_eq!(
+///     doc.root().to_canonical_string(&Canonicalization::Inclusive),
+///     "<a xmlns:n=\"urn:x\" x=\"1\" y=\"2\"><n:b></n:b></a>"
+/// );
+/// assert_eq!(
+///     doc.find_tag("n:b").to_canonical_string(&Canonicalization::Exclusive(Vec::new())),
+///     "<n:b xmlns:n=\"urn:x\"></n:b>"
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Canonicalization {
+    /// [Canonical XML 1.0](https://www.w3.org/TR/xml-c14n/).
+    ///
+    /// All namespace declarations and `xml:` attributes in scope of
+    /// a subtree are copied to its top element.
+    Inclusive,
+
+    /// [Exclusive XML Canonicalization 1.0](https://www.w3.org/TR/xml-exc-c14n/).
+    ///
+    /// Namespace declarations are only written on the elements which
+    /// use them. The value is the InclusiveNamespaces PrefixList, the
+    /// prefixes in it are handled as in the inclusive algorithm. The
+    /// default namespace is given as "#default".
+    Exclusive(Vec<String>),
+}
+
+fn escape_text<W: Write>(s: &str, out: &mut W) -> std::fmt::Result {
+    for c in s.chars() {
+        match c {
+            '&' => out.write_str("&amp;")?,
+            '<' => out.write_str("&lt;")?,
+            '>' => out.write_str("&gt;")?,
+            '\r' => out.write_str("&#xD;")?,
+            _ => out.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+fn escape_attribute<W: Write>(s: &str, out: &mut W) -> std::fmt::Result {
+    for c in s.chars() {
+        match c {
+            '&' => out.write_str("&amp;")?,
+            '<' => out.write_str("&lt;")?,
+            '"' => out.write_str("&quot;")?,
+            '\t' => out.write_str("&#x9;")?,
+            '\n' => out.write_str("&#xA;")?,
+            '\r' => out.write_str("&#xD;")?,
+            _ => out.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+fn name_prefix(name: &str) -> Option<&str> {
+    name.split_once(':').map(|(prefix, _)| prefix)
+}
+
+// Namespace declarations in scope, and the ones written to the output
+struct Scopes {
+    bindings: Vec<(String, String)>,
+    rendered: Vec<(String, String)>,
+    frames: Vec<(usize, usize)>,
+}
+
+fn lookup<'b>(list: &'b [(String, String)], prefix: &str) -> Option<&'b str> {
+    list.iter()
+        .rev()
+        .find(|(name, _)| name == prefix)
+        .map(|(_, uri)| uri.as_str())
+}
+
+impl Scopes {
+    fn new() -> Self {
+        Scopes {
+            bindings: Vec::new(),
+            rendered: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    fn push_frame(&mut self) {
+        self.frames.push((self.bindings.len(), self.rendered.len()));
+    }
+
+    fn pop_frame(&mut self) {
+        if let Some((bindings, rendered)) = self.frames.pop() {
+            self.bindings.truncate(bindings);
+            self.rendered.truncate(rendered);
+        }
+    }
+
+    fn namespace_uri(&self, prefix: &str) -> Option<&str> {
+        if prefix == "xml" {
+            return Some(XML_NS);
+        }
+        lookup(&self.bindings, prefix)
+    }
+
+    // Checks if the namespace node needs to be written
+    fn render(&self, prefix: &str, uri: &str, output: &mut Vec<(String, String)>) {
+        if prefix == "xml" || output.iter().any(|(name, _)| name == prefix) {
+            return;
+        }
+        let current = lookup(&self.rendered, prefix);
+        let needed = if prefix.is_empty() && uri.is_empty() {
+            current.is_some_and(|value| !value.is_empty())
+        } else {
+            current != Some(uri)
+        };
+        if needed {
+            output.push((prefix.to_string(), uri.to_string()));
+        }
+    }
+}
+
+impl Canonicalization {
+    fn is_inclusive_prefix(&self, prefix: &str) -> bool {
+        match self {
+            Canonicalization::Inclusive => true,
+            Canonicalization::Exclusive(prefixes) => prefixes
+                .iter()
+                .any(|name| name == prefix || (prefix.is_empty() && name == "#default")),
+        }
+    }
+
+    fn write_start_tag<W: Write>(
+        &self,
+        tag: &Tag,
+        inherited: &[(String, String)],
+        scopes: &mut Scopes,
+        out: &mut W,
+    ) -> std::fmt::Result {
+        let mut attributes: Vec<(&str, &str)> = Vec::new();
+        let mut attr = tag.attributes;
+        scopes.push_frame();
+        while !attr.is_null() {
+            unsafe {
+                let name = (*attr).name_as_str();
+                let value = (*attr).value_as_str();
+                match declared_prefix(name) {
+                    Some(prefix) => scopes
+                        .bindings
+                        .push((prefix.to_string(), value.to_string())),
+                    None => attributes.push((name, value)),
+                }
+                attr = (*attr).next;
+            }
+        }
+        for (name, value) in inherited {
+            if !attributes.iter().any(|(attr_name, _)| attr_name == name) {
+                attributes.push((name, value));
+            }
+        }
+
+        let mut namespaces: Vec<(String, String)> = Vec::new();
+        let mut prefixes: Vec<&str> = Vec::new();
+        if *self != Canonicalization::Inclusive {
+            // Visibly utilized prefixes
+            prefixes.push(name_prefix(tag.as_str()).unwrap_or(""));
+            for (name, _) in attributes.iter() {
+                if let Some(prefix) = name_prefix(name) {
+                    prefixes.push(prefix);
+                }
+            }
+        }
+        for (prefix, _) in scopes.bindings.iter() {
+            if self.is_inclusive_prefix(prefix) {
+                prefixes.push(prefix);
+            }
+        }
+        for prefix in prefixes {
+            match scopes.namespace_uri(prefix) {
+                Some(uri) => scopes.render(prefix, uri, &mut namespaces),
+                None if prefix.is_empty() => scopes.render("", "", &mut namespaces),
+                None => (),
+            }
+        }
+        namespaces.sort();
+
+        let mut sorted: Vec<(&str, &str, &str, &str)> = attributes
+            .iter()
+            .map(|(name, value)| match name.split_once(':') {
+                Some((prefix, local)) => (
+                    scopes.namespace_uri(prefix).unwrap_or(""),
+                    local,
+                    *name,
+                    *value,
+                ),
+                None => ("", *name, *name, *value),
+            })
+            .collect();
+        sorted.sort();
+
+        out.write_char('<')?;
+        out.write_str(tag.as_str())?;
+        for (prefix, uri) in namespaces.iter() {
+            if prefix.is_empty() {
+                out.write_str(" xmlns=\"")?;
+            } else {
+                write!(out, " xmlns:{prefix}=\"")?;
+            }
+            escape_attribute(uri, out)?;
+            out.write_char('"')?;
+        }
+        for (_, _, name, value) in sorted {
+            write!(out, " {name}=\"")?;
+            escape_attribute(value, out)?;
+            out.write_char('"')?;
+        }
+        out.write_char('>')?;
+        scopes.rendered.extend(namespaces);
+        Ok(())
+    }
+
+    fn write_cursor<W: Write>(&self, cursor: &Cursor, out: &mut W) -> std::fmt::Result {
+        if cursor.is_null() {
+            return Ok(());
+        }
+        let mut scopes = Scopes::new();
+
+        // Context of the subtree from the ancestors
+        let mut inherited: Vec<(String, String)> = Vec::new();
+        for ancestor in cursor.clone().ancestor() {
+            for (name, value) in ancestor.attributes() {
+                if let Some(prefix) = declared_prefix(name) {
+                    if lookup(&scopes.bindings, prefix).is_none() {
+                        scopes
+                            .bindings
+                            .push((prefix.to_string(), value.to_string()));
+                    }
+                } else if *self == Canonicalization::Inclusive
+                    && name.starts_with("xml:")
+                    && lookup(&inherited, name).is_none()
+                {
+                    inherited.push((name.to_string(), value.to_string()));
+                }
+            }
+        }
+
+        let mut visitor = cursor.visitor();
+        let mut first = true;
+        while let Some(step) = visitor.next() {
+            match step {
+                VisitorStep::StartTag(tag) => {
+                    if first {
+                        self.write_start_tag(tag, &inherited, &mut scopes, out)?;
+                    } else {
+                        self.write_start_tag(tag, &[], &mut scopes, out)?;
+                    }
+                    if tag.children.is_null() {
+                        write!(out, "</{}>", tag.as_str())?;
+                        scopes.pop_frame();
+                    }
+                }
+                VisitorStep::EndTag(tag) => {
+                    write!(out, "</{}>", tag.as_str())?;
+                    scopes.pop_frame();
+                }
+                VisitorStep::CData(cdata) => {
+                    escape_text(cdata.as_str(), out)?;
+                }
+            }
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+impl Cursor<'_> {
+    /// Returns the canonical XML text of the element and its subtree.
+    pub fn to_canonical_string(&self, method: &Canonicalization) -> String {
+        let mut buf = String::with_capacity(self.str_size());
+        // Writing into a String cannot fail
+        let _ = method.write_cursor(self, &mut buf);
+        buf
+    }
+
+    /// Writes the canonical XML text of the element and its subtree.
+    pub fn write_canonical<W: std::io::Write>(
+        &self,
+        writer: W,
+        method: &Canonicalization,
+    ) -> std::io::Result<()> {
+        IoWriter::write_with(writer, |out| method.write_cursor(self, out))
+    }
+}
+
+impl Document {
+    /// Returns the canonical XML text of the document.
+    pub fn to_canonical_string(&self, method: &Canonicalization) -> String {
+        self.root().to_canonical_string(method)
+    }
+
+    /// Writes the canonical XML text of the document.
+    pub fn write_canonical<W: std::io::Write>(
+        &self,
+        writer: W,
+        method: &Canonicalization,
+    ) -> std::io::Result<()> {
+        self.root().write_canonical(writer, method)
+    }
+}
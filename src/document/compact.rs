@@ -0,0 +1,323 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use std::mem::size_of;
+use std::ptr::null_mut;
+
+use crate::Arena;
+use crate::NoMemory;
+use crate::ParseError;
+
+use super::ArenaExt;
+use super::Attribute;
+use super::CData;
+use super::Document;
+use super::Node;
+use super::NodePayload;
+use super::Tag;
+use super::lossless::NodeSource;
+
+// Arena space used by the live elements of a document.
+#[derive(Default)]
+struct LiveSize {
+    struct_bytes: usize,
+    cdata_bytes: usize,
+}
+
+impl LiveSize {
+    fn total(&self) -> usize {
+        self.struct_bytes + self.cdata_bytes
+    }
+
+    unsafe fn add_node(&mut self, node: *mut Node) {
+        unsafe {
+            self.struct_bytes += size_of::<Node>();
+            if !(*node).source.is_null() {
+                self.struct_bytes += size_of::<NodeSource>();
+            }
+            match (*node).payload {
+                NodePayload::Tag(tag) => {
+                    self.struct_bytes += size_of::<Tag>();
+                    self.cdata_bytes += (*tag).name_size;
+                    let mut attr = (*tag).attributes;
+                    while !attr.is_null() {
+                        self.struct_bytes += size_of::<Attribute>();
+                        self.cdata_bytes += (*attr).name_size + (*attr).value_size;
+                        attr = (*attr).next;
+                    }
+                }
+                NodePayload::CData(cdata) => {
+                    self.struct_bytes += size_of::<CData>();
+                    self.cdata_bytes += (*cdata).value_size;
+                }
+            }
+        }
+    }
+}
+
+// Returns the next node in the document order, without leaving the subtree of the top.
+unsafe fn next_node(node: *mut Node, top: *mut Node) -> *mut Node {
+    unsafe {
+        if let NodePayload::Tag(tag) = (*node).payload
+            && !(*tag).children.is_null()
+        {
+            return (*tag).children;
+        }
+        let mut current = node;
+        while current != top {
+            if !(*current).next.is_null() {
+                return (*current).next;
+            }
+            current = (*current).parent;
+        }
+        null_mut()
+    }
+}
+
+// Returns the arena bytes of the node, without its children.
+pub(super) unsafe fn node_bytes(node: *mut Node) -> usize {
+    let mut size = LiveSize::default();
+    unsafe { size.add_node(node) };
+    size.total()
+}
+
+// Returns the arena bytes of the node and its subtree.
+pub(super) unsafe fn subtree_bytes(top: *mut Node) -> usize {
+    let mut size = LiveSize::default();
+    let mut node = top;
+    while !node.is_null() {
+        unsafe {
+            size.add_node(node);
+            node = next_node(node, top);
+        }
+    }
+    size.total()
+}
+
+// Copies the node without its links into the arena.
+unsafe fn copy_node(arena: &Arena, old: *mut Node) -> Result<*mut Node, NoMemory> {
+    unsafe {
+        let payload = match (*old).payload {
+            NodePayload::Tag(old_tag) => {
+                let tag = arena.alloc_tag((*old_tag).as_str())?.as_ptr();
+                let mut old_attr = (*old_tag).attributes;
+                while !old_attr.is_null() {
+                    let attr = arena
+                        .alloc_attribute((*old_attr).name_as_str(), (*old_attr).value_as_str())?
+                        .as_ptr();
+                    if (*tag).attributes.is_null() {
+                        (*tag).attributes = attr;
+                    } else {
+                        (*(*tag).last_attribute).next = attr;
+                        (*attr).previous = (*tag).last_attribute;
+                    }
+                    (*tag).last_attribute = attr;
+                    old_attr = (*old_attr).next;
+                }
+                NodePayload::Tag(tag)
+            }
+            NodePayload::CData(old_cdata) => {
+                NodePayload::CData(arena.alloc_cdata((*old_cdata).as_str())?.as_ptr())
+            }
+        };
+        let node = arena.alloc_node(payload)?.as_ptr();
+        if !(*old).source.is_null() {
+            let source = arena.alloc_struct::<NodeSource>()?.as_ptr();
+            source.write(*(*old).source);
+            (*node).source = source;
+        }
+        Ok(node)
+    }
+}
+
+// Appends the node to the children of the parent without touching the sources.
+unsafe fn append_child(parent: *mut Node, node: *mut Node) {
+    unsafe {
+        if let NodePayload::Tag(tag) = (*parent).payload {
+            (*node).parent = parent;
+            if (*tag).children.is_null() {
+                (*tag).children = node;
+            } else {
+                (*(*tag).last_child).next = node;
+                (*node).previous = (*tag).last_child;
+            }
+            (*tag).last_child = node;
+        }
+    }
+}
+
+impl Document {
+    fn live_size(&self) -> LiveSize {
+        let mut size = LiveSize {
+            cdata_bytes: self.source_size,
+            ..LiveSize::default()
+        };
+        let mut node = self.root_node;
+        while !node.is_null() {
+            unsafe {
+                size.add_node(node);
+                node = next_node(node, self.root_node);
+            }
+        }
+        size
+    }
+
+    // Checks if the node is in the tree of the root element.
+    pub(super) unsafe fn is_live(&self, node: *mut Node) -> bool {
+        let mut current = node;
+        unsafe {
+            while !(*current).parent.is_null() {
+                current = (*current).parent;
+            }
+        }
+        current == self.root_node
+    }
+
+    // Counts the bytes which are no longer reachable after an edit of
+    // the node. Edits of the already removed elements are not counted
+    // again.
+    pub(super) unsafe fn abandon(&self, node: *mut Node, bytes: usize) {
+        if bytes > 0 && unsafe { self.is_live(node) } {
+            self.wasted.set(self.wasted.get() + bytes);
+        }
+    }
+
+    // Counts the old bytes of a string replaced in the tree, or the new
+    // bytes if the node is already removed.
+    pub(super) unsafe fn replace(&self, node: *mut Node, old_bytes: usize, new_bytes: usize) {
+        let bytes = if unsafe { self.is_live(node) } {
+            old_bytes
+        } else {
+            new_bytes
+        };
+        self.wasted.set(self.wasted.get() + bytes);
+    }
+
+    // Updates the count after a subtree is moved between the tree and
+    // a removed subtree.
+    pub(super) unsafe fn relocate(&self, node: *mut Node, was_live: bool) {
+        let is_live = unsafe { self.is_live(node) };
+        if is_live != was_live {
+            let bytes = unsafe { subtree_bytes(node) };
+            if was_live {
+                self.wasted.set(self.wasted.get() + bytes);
+            } else {
+                self.wasted.set(self.wasted.get().saturating_sub(bytes));
+            }
+        }
+    }
+
+    /// Returns the arena bytes used by the removed elements.
+    ///
+    /// Removed elements, and the old names, values and text replaced
+    /// by the edit methods stay in the arena until the document is
+    /// dropped. The edit methods keep a running count of these bytes,
+    /// so this method is cheap enough to be called after every edit.
+    /// Elements added under an already removed element are not counted.
+    pub fn wasted_bytes(&self) -> usize {
+        self.wasted.get()
+    }
+
+    /// Copies the live elements into a new right-sized arena and frees the old one.
+    ///
+    /// Long-lived documents which are edited a lot can use this method
+    /// to reclaim the memory of the removed elements. The
+    /// [wasted_bytes](Document::wasted_bytes) method can be used to
+    /// decide when to compact. The lossless source text of the
+    /// document is kept.
+    ///
+    /// Since the method takes a mutable reference, the compiler makes
+    /// sure that there are no [Cursor](crate::Cursor)s left pointing to
+    /// the old arena. If there is not enough memory for the new arena,
+    /// the document is left unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use iks::{Document, ParseError};
+    /// # fn main() -> Result<(), ParseError> {
+    ///
+    /// let mut doc = Document::from_str("<a><b>some text</b><c/></a>")?;
+    /// doc.find_tag("b").remove();
+    /// for i in 0..100 {
+    ///     doc.find_tag("c").set_attribute("counter", Some(&i.to_string()))?;
+    /// }
+    /// assert!(doc.wasted_bytes() > 0);
+    ///
+    /// doc.compact()?;
+    /// assert_eq!(doc.wasted_bytes(), 0);
+    /// assert_eq!(doc.to_string(), "<a><c counter=\"99\"/></a>");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn compact(&mut self) -> Result<(), ParseError> {
+        self.compact_with(null_mut())?;
+        Ok(())
+    }
+
+    // Compacts the document, and returns the new location of the given
+    // node, or null if the node is not in the tree.
+    pub(super) fn compact_with(&mut self, node: *mut Node) -> Result<*mut Node, ParseError> {
+        let size = self.live_size();
+        let arena = Arena::with_chunk_sizes(
+            size.struct_bytes.div_ceil(size_of::<usize>()),
+            size.cdata_bytes,
+        )?;
+
+        let mut source_text: *const u8 = std::ptr::null();
+        if !self.source_text.is_null() {
+            unsafe {
+                let slice = std::slice::from_raw_parts(self.source_text, self.source_size);
+                source_text = arena
+                    .push_str(std::str::from_utf8_unchecked(slice))?
+                    .as_ptr();
+            }
+        }
+
+        let mut new_node = null_mut();
+        let root_node;
+        unsafe {
+            root_node = copy_node(&arena, self.root_node)?;
+            if node == self.root_node {
+                new_node = root_node;
+            }
+            let mut old = self.root_node;
+            let mut new = root_node;
+            loop {
+                let next = next_node(old, self.root_node);
+                if next.is_null() {
+                    break;
+                }
+                // Find the parent of the next node in the new tree
+                let mut parent = new;
+                if (*next).parent != old {
+                    let mut current = old;
+                    while current != (*next).parent {
+                        current = (*current).parent;
+                        parent = (*parent).parent;
+                    }
+                }
+                new = copy_node(&arena, next)?;
+                append_child(parent, new);
+                if next == node {
+                    new_node = new;
+                }
+                old = next;
+            }
+        }
+
+        self.arena = arena;
+        self.root_node = root_node;
+        self.source_text = source_text;
+        self.wasted.set(0);
+        Ok(new_node)
+    }
+}
@@ -0,0 +1,1111 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hash;
+use std::hash::Hasher;
+
+use super::super::entities::escape;
+use super::Cursor;
+use super::Document;
+use super::Node;
+
+/// Options of the structural comparison of element trees.
+///
+/// # Examples
+///
+/// ```
+/// use std::str::FromStr;
+/// use iks::{DiffEdit, DiffOptions, Document, ParseError};
+/// # fn main() -> Result<(), ParseError> {
+///
+/// let old = Document::from_str("<roster><item jid='a@b'/><item jid='c@d'/></roster>")?;
+/// let new = Document::from_str("<roster><item jid='c@d' name='C'/></roster>")?;
+///
+/// assert_eq!(
+///     old.diff(&new, &DiffOptions::default()),
+///     vec![
+///         DiffEdit::Remove {
+///             path: "/roster/item[1]".to_string(),
+///             xml: "<item jid=\"a@b\"/>".to_string(),
+///         },
+///         DiffEdit::Attribute {
+///             path: "/roster/item".to_string(),
+///             name: "name".to_string(),
+///             old: None,
+///             new: Some("C".to_string()),
+///         },
+///     ]
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DiffOptions {
+    /// Attributes in a different order are equal.
+    pub ignore_attribute_order: bool,
+
+    /// Text nodes with only whitespace characters are skipped.
+    pub ignore_whitespace: bool,
+
+    /// Tag and attribute names are compared by their namespace URIs
+    /// and local names, and the namespace declarations are skipped.
+    pub ignore_prefixes: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            ignore_attribute_order: true,
+            ignore_whitespace: false,
+            ignore_prefixes: false,
+        }
+    }
+}
+
+/// A change between the old and the new element trees.
+///
+/// Paths of the old nodes are in the old tree, paths of the new and
+/// changed nodes are in the new tree. Paths are XPath expressions
+/// with the positions among the siblings with the same name, like
+/// `/a/b[2]` and `/a/text()[1]`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DiffEdit {
+    /// A node which is only in the new tree.
+    Insert { path: String, xml: String },
+
+    /// A node which is only in the old tree.
+    Remove { path: String, xml: String },
+
+    /// An unchanged node in a different place.
+    Move { from: String, to: String },
+
+    /// An added, removed, or changed attribute value.
+    Attribute {
+        path: String,
+        name: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+
+    /// Same attributes in a different order.
+    AttributeOrder { path: String },
+
+    /// A changed text node.
+    Text {
+        path: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl std::fmt::Display for DiffEdit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiffEdit::Insert { path, xml } => write!(f, "+ {path}: {xml}"),
+            DiffEdit::Remove { path, xml } => write!(f, "- {path}: {xml}"),
+            DiffEdit::Move { from, to } => write!(f, "> {from} -> {to}"),
+            DiffEdit::Attribute {
+                path,
+                name,
+                old,
+                new,
+            } => {
+                write!(f, "@ {path} {name}: ")?;
+                match old {
+                    Some(value) => write!(f, "{value:?}")?,
+                    None => f.write_str("(none)")?,
+                }
+                f.write_str(" -> ")?;
+                match new {
+                    Some(value) => write!(f, "{value:?}"),
+                    None => f.write_str("(none)"),
+                }
+            }
+            DiffEdit::AttributeOrder { path } => write!(f, "@ {path}: attribute order"),
+            DiffEdit::Text { path, old, new } => write!(f, "~ {path}: {old:?} -> {new:?}"),
+        }
+    }
+}
+
+// Gaps without unique items larger than this are matched in order
+// instead of with the longest common subsequence table.
+const MAX_LCS_CELLS: usize = 1 << 20;
+
+// Hashes of the subtrees of the tags, equal for the equal subtrees
+type Signatures = HashMap<*mut Node, u64>;
+
+fn text_signature(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write_u8(b't');
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn run_signature(signatures: &Signatures, cursor: &Cursor, text: &Option<String>) -> u64 {
+    match text {
+        Some(text) => text_signature(text),
+        None => signatures
+            .get(&cursor.get_node_ptr())
+            .copied()
+            .unwrap_or_default(),
+    }
+}
+
+fn feature_hash<T: Hash>(kind: u8, value: T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write_u8(kind);
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+// A child node, or a run of adjacent text nodes
+#[derive(Clone)]
+struct Item<'a> {
+    cursor: Cursor<'a>,
+    text: Option<String>,
+    path: String,
+    signature: u64,
+    // Equal for the structurally equal items of the same parent pair
+    class: usize,
+}
+
+impl Item<'_> {
+    fn xml(&self) -> String {
+        match &self.text {
+            Some(text) => {
+                let mut buf = String::new();
+                escape(text, &mut buf);
+                buf
+            }
+            None => self.cursor.to_string(),
+        }
+    }
+}
+
+// Attributes and child signatures of a tag for finding the similar tags
+struct Features<'a> {
+    attributes: Vec<(String, &'a str)>,
+    children: Vec<u64>,
+}
+
+impl<'a> Features<'a> {
+    fn value(&self, key: &str) -> Option<&'a str> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute == key)
+            .map(|(_, value)| *value)
+    }
+
+    // Hashes of the attributes and children, children can repeat
+    fn hashes(&self) -> impl Iterator<Item = u64> {
+        self.attributes
+            .iter()
+            .map(|(key, value)| feature_hash(b'a', (key, value)))
+            .chain(
+                self.children
+                    .iter()
+                    .map(|signature| feature_hash(b'c', signature)),
+            )
+    }
+}
+
+// Pending comparison of two matched tags
+struct Pair<'a, 'b> {
+    old: Cursor<'a>,
+    new: Cursor<'b>,
+    old_path: String,
+    new_path: String,
+}
+
+impl DiffOptions {
+    fn is_skipped(&self, cursor: &Cursor) -> bool {
+        self.ignore_whitespace
+            && !cursor.is_tag()
+            && cursor.cdata().chars().all(|c| c.is_ascii_whitespace())
+    }
+
+    fn name_key(&self, cursor: &Cursor, name: &str) -> String {
+        if self.ignore_prefixes {
+            let (prefix, local) = name.split_once(':').unwrap_or(("", name));
+            let uri = cursor.lookup_namespace(prefix).unwrap_or("");
+            format!("{{{uri}}}{local}")
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn attribute_key(&self, cursor: &Cursor, name: &str) -> String {
+        if self.ignore_prefixes && !name.contains(':') {
+            // Unprefixed attributes are not in the default namespace
+            return format!("{{}}{name}");
+        }
+        self.name_key(cursor, name)
+    }
+
+    fn is_declaration(&self, name: &str) -> bool {
+        self.ignore_prefixes && (name == "xmlns" || name.starts_with("xmlns:"))
+    }
+
+    // Compared attributes as (key, name, value)
+    fn attributes<'a>(&self, cursor: &Cursor<'a>) -> Vec<(String, &'a str, &'a str)> {
+        cursor
+            .clone()
+            .attributes()
+            .filter(|(name, _)| !self.is_declaration(name))
+            .map(|(name, value)| (self.attribute_key(cursor, name), name, value))
+            .collect()
+    }
+
+    fn first_child<'a>(&self, cursor: &Cursor<'a>) -> Cursor<'a> {
+        let mut child = cursor.clone().first_child();
+        while !child.is_null() && self.is_skipped(&child) {
+            child = child.next();
+        }
+        child
+    }
+
+    fn next_sibling<'a>(&self, cursor: &Cursor<'a>) -> Cursor<'a> {
+        let mut next = cursor.clone().next();
+        while !next.is_null() && self.is_skipped(&next) {
+            next = next.next();
+        }
+        next
+    }
+
+    // Compared children with the adjacent text nodes joined
+    fn child_runs<'a>(&self, cursor: &Cursor<'a>) -> Vec<(Cursor<'a>, Option<String>)> {
+        let mut runs: Vec<(Cursor<'a>, Option<String>)> = Vec::new();
+        let mut child = self.first_child(cursor);
+        while !child.is_null() {
+            if child.is_tag() {
+                runs.push((child.clone(), None));
+            } else if let Some((_, Some(text))) = runs.last_mut() {
+                text.push_str(child.cdata());
+            } else {
+                runs.push((child.clone(), Some(child.cdata().to_string())));
+            }
+            child = self.next_sibling(&child);
+        }
+        runs
+    }
+
+    // Hashes the tag with the already calculated signatures of its children
+    fn tag_signature(&self, cursor: &Cursor, signatures: &Signatures) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u8(b'<');
+        self.name_key(cursor, cursor.name()).hash(&mut hasher);
+        let mut attributes = self.attributes(cursor);
+        if self.ignore_attribute_order {
+            attributes.sort();
+        }
+        hasher.write_usize(attributes.len());
+        for (key, _, value) in attributes {
+            key.hash(&mut hasher);
+            value.hash(&mut hasher);
+        }
+        let runs = self.child_runs(cursor);
+        hasher.write_usize(runs.len());
+        for (child, text) in &runs {
+            hasher.write_u64(run_signature(signatures, child, text));
+        }
+        hasher.finish()
+    }
+
+    // Calculates the signatures of all tags in the tree once, the
+    // children before their parents.
+    fn signatures(&self, top: &Cursor) -> Signatures {
+        let mut tags: Vec<Cursor> = Vec::new();
+        let mut stack = vec![top.clone()];
+        while let Some(node) = stack.pop() {
+            let mut child = self.first_child(&node);
+            while !child.is_null() {
+                if child.is_tag() {
+                    stack.push(child.clone());
+                }
+                child = self.next_sibling(&child);
+            }
+            tags.push(node);
+        }
+        let mut signatures = Signatures::with_capacity(tags.len());
+        for tag in tags.iter().rev() {
+            let signature = self.tag_signature(tag, &signatures);
+            signatures.insert(tag.get_node_ptr(), signature);
+        }
+        signatures
+    }
+
+    // Returns a hash which is equal for the equal subtrees
+    fn signature(&self, top: &Cursor) -> u64 {
+        if top.is_tag() {
+            run_signature(&self.signatures(top), top, &None)
+        } else {
+            text_signature(top.cdata())
+        }
+    }
+
+    // Compares two subtrees under the options, the signatures can be
+    // equal for the different subtrees.
+    fn same_subtree(&self, old: &Cursor, new: &Cursor) -> bool {
+        if !old.is_tag() || !new.is_tag() {
+            return !old.is_tag() && !new.is_tag() && old.cdata() == new.cdata();
+        }
+        let mut stack = vec![(old.clone(), new.clone())];
+        while let Some((old, new)) = stack.pop() {
+            if self.name_key(&old, old.name()) != self.name_key(&new, new.name()) {
+                return false;
+            }
+            let mut old_attributes = self.attributes(&old);
+            let mut new_attributes = self.attributes(&new);
+            if self.ignore_attribute_order {
+                old_attributes.sort();
+                new_attributes.sort();
+            }
+            if old_attributes.len() != new_attributes.len()
+                || old_attributes
+                    .iter()
+                    .zip(&new_attributes)
+                    .any(|(a, b)| a.0 != b.0 || a.2 != b.2)
+            {
+                return false;
+            }
+            let old_runs = self.child_runs(&old);
+            let new_runs = self.child_runs(&new);
+            if old_runs.len() != new_runs.len() {
+                return false;
+            }
+            for ((old_child, old_text), (new_child, new_text)) in old_runs.into_iter().zip(new_runs)
+            {
+                match (old_text, new_text) {
+                    (Some(old_text), Some(new_text)) if old_text == new_text => (),
+                    (None, None) => stack.push((old_child, new_child)),
+                    _ => return false,
+                }
+            }
+        }
+        true
+    }
+
+    fn same_item(&self, old: &Item, new: &Item) -> bool {
+        if old.signature != new.signature {
+            return false;
+        }
+        match (&old.text, &new.text) {
+            (Some(old_text), Some(new_text)) => old_text == new_text,
+            (None, None) => self.same_subtree(&old.cursor, &new.cursor),
+            _ => false,
+        }
+    }
+
+    // Numbers the items of both lists, the structurally equal items get
+    // the same class. Signatures only select the items to compare.
+    fn classify<'i>(&self, old: &[Item<'i>], new: &[Item<'i>]) -> (Vec<usize>, Vec<usize>) {
+        let mut representatives: Vec<&Item> = Vec::new();
+        let mut candidates: HashMap<u64, Vec<usize>> = HashMap::new();
+        let mut classes = Vec::with_capacity(old.len() + new.len());
+        for item in old.iter().chain(new) {
+            let same = candidates.entry(item.signature).or_default();
+            let class = match same
+                .iter()
+                .find(|class| self.same_item(representatives[**class], item))
+            {
+                Some(class) => *class,
+                None => {
+                    same.push(representatives.len());
+                    representatives.push(item);
+                    representatives.len() - 1
+                }
+            };
+            classes.push(class);
+        }
+        let new_classes = classes.split_off(old.len());
+        (classes, new_classes)
+    }
+
+    fn children<'a>(
+        &self,
+        cursor: &Cursor<'a>,
+        path: &str,
+        signatures: &Signatures,
+    ) -> Vec<Item<'a>> {
+        let runs = self.child_runs(cursor);
+        let mut totals: HashMap<String, usize> = HashMap::new();
+        for (child, text) in &runs {
+            if text.is_none() {
+                *totals.entry(child.name().to_string()).or_default() += 1;
+            }
+        }
+        let mut indices: HashMap<String, usize> = HashMap::new();
+        let mut texts = 0;
+        let mut items: Vec<Item<'a>> = Vec::with_capacity(runs.len());
+        for (child, text) in runs {
+            let signature = run_signature(signatures, &child, &text);
+            let child_path = if text.is_some() {
+                texts += 1;
+                format!("{path}/text()[{texts}]")
+            } else {
+                let name = child.name();
+                let index = indices.entry(name.to_string()).or_default();
+                *index += 1;
+                if totals.get(name) == Some(&1) {
+                    format!("{path}/{name}")
+                } else {
+                    format!("{path}/{name}[{index}]")
+                }
+            };
+            items.push(Item {
+                cursor: child,
+                text,
+                path: child_path,
+                signature,
+                class: 0,
+            });
+        }
+        items
+    }
+
+    fn features<'a>(&self, item: &Item<'a>, signatures: &Signatures) -> Features<'a> {
+        Features {
+            attributes: self
+                .attributes(&item.cursor)
+                .into_iter()
+                .map(|(key, _, value)| (key, value))
+                .collect(),
+            children: self
+                .child_runs(&item.cursor)
+                .iter()
+                .map(|(child, text)| run_signature(signatures, child, text))
+                .collect(),
+        }
+    }
+
+    fn diff_attributes(&self, pair: &Pair, edits: &mut Vec<DiffEdit>) {
+        let old = self.attributes(&pair.old);
+        let new = self.attributes(&pair.new);
+        for (key, name, value) in old.iter() {
+            match new.iter().find(|(new_key, _, _)| new_key == key) {
+                Some((_, _, new_value)) if new_value == value => (),
+                Some((_, new_name, new_value)) => edits.push(DiffEdit::Attribute {
+                    path: pair.new_path.clone(),
+                    name: new_name.to_string(),
+                    old: Some(value.to_string()),
+                    new: Some(new_value.to_string()),
+                }),
+                None => edits.push(DiffEdit::Attribute {
+                    path: pair.new_path.clone(),
+                    name: name.to_string(),
+                    old: Some(value.to_string()),
+                    new: None,
+                }),
+            }
+        }
+        for (key, name, value) in new.iter() {
+            if !old.iter().any(|(old_key, _, _)| old_key == key) {
+                edits.push(DiffEdit::Attribute {
+                    path: pair.new_path.clone(),
+                    name: name.to_string(),
+                    old: None,
+                    new: Some(value.to_string()),
+                });
+            }
+        }
+        if !self.ignore_attribute_order {
+            let old_order: Vec<&String> = old
+                .iter()
+                .map(|(key, _, _)| key)
+                .filter(|key| new.iter().any(|(new_key, _, _)| new_key == *key))
+                .collect();
+            let new_order: Vec<&String> = new
+                .iter()
+                .map(|(key, _, _)| key)
+                .filter(|key| old.iter().any(|(old_key, _, _)| old_key == *key))
+                .collect();
+            if old_order != new_order {
+                edits.push(DiffEdit::AttributeOrder {
+                    path: pair.new_path.clone(),
+                });
+            }
+        }
+    }
+
+    // Pairs the unmatched children of the same kind. Identical nodes
+    // are paired first, and the text nodes in their order. Tags with
+    // the same name are paired by an identity attribute, then with the
+    // most similar tag by the number of equal attributes and children.
+    // Tags without any common attributes or children are only paired
+    // if both have no attributes. Returns the index of the paired new
+    // node for each old node.
+    fn pair_changed(
+        &self,
+        old: &[&Item],
+        new: &[&Item],
+        signatures: (&Signatures, &Signatures),
+    ) -> Vec<Option<usize>> {
+        let mut paired: Vec<Option<usize>> = vec![None; old.len()];
+        let mut used = vec![false; new.len()];
+
+        let mut identical: HashMap<usize, VecDeque<usize>> = HashMap::new();
+        for (new_index, new_item) in new.iter().enumerate() {
+            identical
+                .entry(new_item.class)
+                .or_default()
+                .push_back(new_index);
+        }
+        for (old_index, old_item) in old.iter().enumerate() {
+            if let Some(new_index) = identical
+                .get_mut(&old_item.class)
+                .and_then(VecDeque::pop_front)
+            {
+                paired[old_index] = Some(new_index);
+                used[new_index] = true;
+            }
+        }
+
+        let mut new_texts =
+            (0..new.len()).filter(|index| !used[*index] && new[*index].text.is_some());
+        let mut text_pairs = Vec::new();
+        for (old_index, old_item) in old.iter().enumerate() {
+            if paired[old_index].is_none() && old_item.text.is_some() {
+                match new_texts.next() {
+                    Some(new_index) => text_pairs.push((old_index, new_index)),
+                    None => break,
+                }
+            }
+        }
+        for (old_index, new_index) in text_pairs {
+            paired[old_index] = Some(new_index);
+            used[new_index] = true;
+        }
+
+        // Tags are only compared with the tags of the same name
+        let mut groups: HashMap<String, (Vec<usize>, Vec<usize>)> = HashMap::new();
+        for (old_index, old_item) in old.iter().enumerate() {
+            if paired[old_index].is_none() && old_item.text.is_none() {
+                let key = self.name_key(&old_item.cursor, old_item.cursor.name());
+                groups.entry(key).or_default().0.push(old_index);
+            }
+        }
+        for (new_index, new_item) in new.iter().enumerate() {
+            if !used[new_index] && new_item.text.is_none() {
+                let key = self.name_key(&new_item.cursor, new_item.cursor.name());
+                if let Some((_, new_group)) = groups.get_mut(&key) {
+                    new_group.push(new_index);
+                }
+            }
+        }
+        for (old_group, new_group) in groups.into_values() {
+            if new_group.is_empty() {
+                continue;
+            }
+            let old_features: Vec<Features> = old_group
+                .iter()
+                .map(|index| self.features(old[*index], signatures.0))
+                .collect();
+            let new_features: Vec<Features> = new_group
+                .iter()
+                .map(|index| self.features(new[*index], signatures.1))
+                .collect();
+            let mut group_paired: Vec<Option<usize>> = vec![None; old_group.len()];
+            let mut group_used = vec![false; new_group.len()];
+            pair_by_identity(
+                &old_features,
+                &new_features,
+                &mut group_paired,
+                &mut group_used,
+            );
+            pair_by_similarity(
+                &old_features,
+                &new_features,
+                &mut group_paired,
+                &mut group_used,
+            );
+            for (old_position, new_position) in group_paired.into_iter().enumerate() {
+                if let Some(new_position) = new_position {
+                    paired[old_group[old_position]] = Some(new_group[new_position]);
+                }
+            }
+        }
+        paired
+    }
+
+    fn diff_trees<'a, 'b>(&self, old: &Cursor<'a>, new: &Cursor<'b>) -> Vec<DiffEdit> {
+        let mut edits: Vec<DiffEdit> = Vec::new();
+        // Removed nodes and the inserted nodes by their signatures for finding the moves
+        let mut removed: Vec<(usize, Item<'a>)> = Vec::new();
+        let mut inserted: HashMap<u64, VecDeque<(usize, Item<'b>)>> = HashMap::new();
+
+        if old.is_null() || new.is_null() {
+            return edits;
+        }
+        let old_path = format!("/{}", old.name());
+        let new_path = format!("/{}", new.name());
+        if !old.is_tag()
+            || !new.is_tag()
+            || self.name_key(old, old.name()) != self.name_key(new, new.name())
+        {
+            edits.push(DiffEdit::Remove {
+                path: old_path,
+                xml: old.to_string(),
+            });
+            edits.push(DiffEdit::Insert {
+                path: new_path,
+                xml: new.to_string(),
+            });
+            return edits;
+        }
+
+        let old_signatures = self.signatures(old);
+        let new_signatures = self.signatures(new);
+        let mut pairs: Vec<Pair<'a, 'b>> = vec![Pair {
+            old: old.clone(),
+            new: new.clone(),
+            old_path,
+            new_path,
+        }];
+        while let Some(pair) = pairs.pop() {
+            self.diff_attributes(&pair, &mut edits);
+            let mut old_items = self.children(&pair.old, &pair.old_path, &old_signatures);
+            let mut new_items = self.children(&pair.new, &pair.new_path, &new_signatures);
+            let (old_classes, new_classes) = self.classify(&old_items, &new_items);
+            for (item, class) in old_items.iter_mut().zip(old_classes) {
+                item.class = class;
+            }
+            for (item, class) in new_items.iter_mut().zip(new_classes) {
+                item.class = class;
+            }
+            let mut old_matched = vec![false; old_items.len()];
+            let mut new_matched = vec![false; new_items.len()];
+            for (old_index, new_index) in common_items(&old_items, &new_items) {
+                old_matched[old_index] = true;
+                new_matched[new_index] = true;
+            }
+
+            let old_changed: Vec<&Item> = old_items
+                .iter()
+                .zip(old_matched)
+                .filter(|(_, matched)| !matched)
+                .map(|(item, _)| item)
+                .collect();
+            let new_changed: Vec<&Item> = new_items
+                .iter()
+                .zip(new_matched)
+                .filter(|(_, matched)| !matched)
+                .map(|(item, _)| item)
+                .collect();
+            let paired = self.pair_changed(
+                &old_changed,
+                &new_changed,
+                (&old_signatures, &new_signatures),
+            );
+
+            let mut nested: Vec<Pair<'a, 'b>> = Vec::new();
+            let mut new_paired = vec![false; new_changed.len()];
+            for (old_item, new_index) in old_changed.iter().zip(paired.iter()) {
+                let Some(new_index) = *new_index else {
+                    removed.push((edits.len(), (*old_item).clone()));
+                    edits.push(DiffEdit::Remove {
+                        path: old_item.path.clone(),
+                        xml: old_item.xml(),
+                    });
+                    continue;
+                };
+                new_paired[new_index] = true;
+                let new_item = new_changed[new_index];
+                if old_item.class == new_item.class {
+                    edits.push(DiffEdit::Move {
+                        from: old_item.path.clone(),
+                        to: new_item.path.clone(),
+                    });
+                    continue;
+                }
+                match (&old_item.text, &new_item.text) {
+                    (Some(old_text), Some(new_text)) => edits.push(DiffEdit::Text {
+                        path: new_item.path.clone(),
+                        old: old_text.clone(),
+                        new: new_text.clone(),
+                    }),
+                    _ => nested.push(Pair {
+                        old: old_item.cursor.clone(),
+                        new: new_item.cursor.clone(),
+                        old_path: old_item.path.clone(),
+                        new_path: new_item.path.clone(),
+                    }),
+                }
+            }
+            for (new_item, paired) in new_changed.iter().zip(new_paired) {
+                if !paired {
+                    inserted
+                        .entry(new_item.signature)
+                        .or_default()
+                        .push_back((edits.len(), (*new_item).clone()));
+                    edits.push(DiffEdit::Insert {
+                        path: new_item.path.clone(),
+                        xml: new_item.xml(),
+                    });
+                }
+            }
+            // Visit the children in the document order
+            pairs.extend(nested.into_iter().rev());
+        }
+
+        // Removed and inserted identical nodes are moves
+        let mut dropped = vec![false; edits.len()];
+        for (remove_index, old_item) in removed {
+            let Some(queue) = inserted.get_mut(&old_item.signature) else {
+                continue;
+            };
+            let Some(position) = queue
+                .iter()
+                .position(|(_, new_item)| self.same_item(&old_item, new_item))
+            else {
+                continue;
+            };
+            if let Some((insert_index, _)) = queue.remove(position) {
+                if let (DiffEdit::Remove { path: from, .. }, DiffEdit::Insert { path: to, .. }) =
+                    (&edits[remove_index], &edits[insert_index])
+                {
+                    edits[remove_index] = DiffEdit::Move {
+                        from: from.clone(),
+                        to: to.clone(),
+                    };
+                }
+                dropped[insert_index] = true;
+            }
+        }
+        let mut dropped = dropped.into_iter();
+        edits.retain(|_| !dropped.next().unwrap_or(false));
+        edits
+    }
+}
+
+// Positions of the tags by the values of an identity attribute
+type Identities<'a> = HashMap<&'a str, usize>;
+
+// Returns the values of the attribute if all tags have it with a different value
+fn identity_values<'a>(features: &[Features<'a>], key: &str) -> Option<Identities<'a>> {
+    let mut values = Identities::with_capacity(features.len());
+    for (position, feature) in features.iter().enumerate() {
+        let value = feature.value(key)?;
+        if values.insert(value, position).is_some() {
+            return None;
+        }
+    }
+    Some(values)
+}
+
+// Pairs the tags with the same value of the identity attribute, like
+// the jid of the roster items. The attribute which pairs the most tags
+// is used.
+fn pair_by_identity(
+    old: &[Features],
+    new: &[Features],
+    paired: &mut [Option<usize>],
+    used: &mut [bool],
+) {
+    let Some(first) = old.first() else {
+        return;
+    };
+    let mut best: Option<(usize, Identities, Identities)> = None;
+    for (key, _) in &first.attributes {
+        let (Some(old_values), Some(new_values)) =
+            (identity_values(old, key), identity_values(new, key))
+        else {
+            continue;
+        };
+        let count = old_values
+            .keys()
+            .filter(|value| new_values.contains_key(*value))
+            .count();
+        if count > 0
+            && best
+                .as_ref()
+                .is_none_or(|(best_count, _, _)| count > *best_count)
+        {
+            best = Some((count, old_values, new_values));
+        }
+    }
+    if let Some((_, old_values, new_values)) = best {
+        for (value, old_position) in old_values {
+            if let Some(new_position) = new_values.get(value) {
+                paired[old_position] = Some(*new_position);
+                used[*new_position] = true;
+            }
+        }
+    }
+}
+
+// Pairs each remaining tag with the most similar unpaired tag. Only the
+// tags with a common attribute or child are scored, through an index
+// of the attribute and child hashes of the new tags.
+fn pair_by_similarity(
+    old: &[Features],
+    new: &[Features],
+    paired: &mut [Option<usize>],
+    used: &mut [bool],
+) {
+    let mut index: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (new_position, features) in new.iter().enumerate() {
+        if used[new_position] {
+            continue;
+        }
+        let mut hashes: Vec<u64> = features.hashes().collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        for hash in hashes {
+            index.entry(hash).or_default().push(new_position);
+        }
+    }
+    let mut scores = vec![0usize; new.len()];
+    let mut scored: Vec<usize> = Vec::new();
+    for (old_position, features) in old.iter().enumerate() {
+        if paired[old_position].is_some() {
+            continue;
+        }
+        for hash in features.hashes() {
+            for new_position in index.get(&hash).into_iter().flatten() {
+                if !used[*new_position] {
+                    if scores[*new_position] == 0 {
+                        scored.push(*new_position);
+                    }
+                    scores[*new_position] += 1;
+                }
+            }
+        }
+        // Highest score, and the first tag for the equal scores
+        let best = scored
+            .iter()
+            .copied()
+            .max_by(|a, b| scores[*a].cmp(&scores[*b]).then(b.cmp(a)));
+        if let Some(new_position) = best {
+            paired[old_position] = Some(new_position);
+            used[new_position] = true;
+        }
+        for new_position in scored.drain(..) {
+            scores[new_position] = 0;
+        }
+    }
+
+    let mut next_new = 0;
+    for (old_position, features) in old.iter().enumerate() {
+        if paired[old_position].is_some() || !features.attributes.is_empty() {
+            continue;
+        }
+        while next_new < new.len() && (used[next_new] || !new[next_new].attributes.is_empty()) {
+            next_new += 1;
+        }
+        if next_new == new.len() {
+            break;
+        }
+        paired[old_position] = Some(next_new);
+        used[next_new] = true;
+    }
+}
+
+// Returns the index pairs of the identical items which keep their
+// order. Common prefix and suffix are matched first, then the items
+// which occur once in both lists are used as anchors, and only the
+// gaps between them are searched for the longest common subsequence.
+fn common_items(old: &[Item], new: &[Item]) -> Vec<(usize, usize)> {
+    let mut matches = Vec::new();
+    match_items(old, new, (0, 0), &mut matches);
+    matches
+}
+
+fn match_items(
+    old: &[Item],
+    new: &[Item],
+    offsets: (usize, usize),
+    matches: &mut Vec<(usize, usize)>,
+) {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old_item, new_item)| old_item.class == new_item.class)
+        .count();
+    matches.extend((0..prefix).map(|index| (offsets.0 + index, offsets.1 + index)));
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(old_item, new_item)| old_item.class == new_item.class)
+        .count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+    let offsets = (offsets.0 + prefix, offsets.1 + prefix);
+
+    if !old.is_empty() && !new.is_empty() {
+        let anchors = unique_anchors(old, new);
+        if anchors.is_empty() {
+            match_gap(old, new, offsets, matches);
+        } else {
+            let (mut old_start, mut new_start) = (0, 0);
+            for (old_index, new_index) in anchors {
+                match_items(
+                    &old[old_start..old_index],
+                    &new[new_start..new_index],
+                    (offsets.0 + old_start, offsets.1 + new_start),
+                    matches,
+                );
+                matches.push((offsets.0 + old_index, offsets.1 + new_index));
+                old_start = old_index + 1;
+                new_start = new_index + 1;
+            }
+            match_items(
+                &old[old_start..],
+                &new[new_start..],
+                (offsets.0 + old_start, offsets.1 + new_start),
+                matches,
+            );
+        }
+    }
+
+    let ends = (offsets.0 + old.len(), offsets.1 + new.len());
+    matches.extend((0..suffix).map(|index| (ends.0 + index, ends.1 + index)));
+}
+
+// Returns the items which occur once in both lists, and keep their order
+fn unique_anchors(old: &[Item], new: &[Item]) -> Vec<(usize, usize)> {
+    // Counts and the positions of the classes in both lists
+    let mut occurrences: HashMap<usize, (usize, usize, usize, usize)> = HashMap::new();
+    for (index, item) in old.iter().enumerate() {
+        let entry = occurrences.entry(item.class).or_default();
+        entry.0 += 1;
+        entry.2 = index;
+    }
+    for (index, item) in new.iter().enumerate() {
+        if let Some(entry) = occurrences.get_mut(&item.class) {
+            entry.1 += 1;
+            entry.3 = index;
+        }
+    }
+    let mut anchors: Vec<(usize, usize)> = occurrences
+        .into_values()
+        .filter(|(old_count, new_count, _, _)| *old_count == 1 && *new_count == 1)
+        .map(|(_, _, old_index, new_index)| (old_index, new_index))
+        .collect();
+    anchors.sort_unstable();
+    longest_increasing(&anchors)
+}
+
+// Returns the longest subsequence of the pairs with increasing second
+// values, with the patience sorting.
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // Last pair of each pile, and the previous pair of each pair
+    let mut piles: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; pairs.len()];
+    for (index, (_, value)) in pairs.iter().enumerate() {
+        let pile = piles.partition_point(|top| pairs[*top].1 < *value);
+        if pile > 0 {
+            previous[index] = Some(piles[pile - 1]);
+        }
+        if pile == piles.len() {
+            piles.push(index);
+        } else {
+            piles[pile] = index;
+        }
+    }
+    let mut result = Vec::new();
+    let mut current = piles.last().copied();
+    while let Some(index) = current {
+        result.push(pairs[index]);
+        current = previous[index];
+    }
+    result.reverse();
+    result
+}
+
+// Matches the items of a gap without any unique items
+fn match_gap(
+    old: &[Item],
+    new: &[Item],
+    offsets: (usize, usize),
+    matches: &mut Vec<(usize, usize)>,
+) {
+    let width = new.len() + 1;
+    if (old.len() + 1).saturating_mul(width) > MAX_LCS_CELLS {
+        // Each item is matched with the next identical item
+        let mut positions: HashMap<usize, VecDeque<usize>> = HashMap::new();
+        for (index, item) in new.iter().enumerate() {
+            positions.entry(item.class).or_default().push_back(index);
+        }
+        let mut next = 0;
+        for (old_index, item) in old.iter().enumerate() {
+            if let Some(queue) = positions.get_mut(&item.class) {
+                while queue.front().is_some_and(|index| *index < next) {
+                    queue.pop_front();
+                }
+                if let Some(new_index) = queue.pop_front() {
+                    matches.push((offsets.0 + old_index, offsets.1 + new_index));
+                    next = new_index + 1;
+                }
+            }
+        }
+        return;
+    }
+
+    let mut lengths = vec![0usize; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = if old[i].class == new[j].class {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i].class == new[j].class {
+            matches.push((offsets.0 + i, offsets.1 + j));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+}
+
+impl Cursor<'_> {
+    /// Returns the changes from this element tree to the other one.
+    ///
+    /// An empty edit script means the trees are equal under the
+    /// given options.
+    pub fn diff(&self, other: &Cursor, options: &DiffOptions) -> Vec<DiffEdit> {
+        options.diff_trees(self, other)
+    }
+
+    /// Checks if the element trees are equal, ignoring the attribute
+    /// order, whitespace-only text nodes, and namespace prefixes.
+    pub fn semantic_eq(&self, other: &Cursor) -> bool {
+        let options = DiffOptions {
+            ignore_attribute_order: true,
+            ignore_whitespace: true,
+            ignore_prefixes: true,
+        };
+        options.signature(self) == options.signature(other) && options.same_subtree(self, other)
+    }
+}
+
+impl Document {
+    /// Returns the changes from this document to the other one.
+    pub fn diff(&self, other: &Document, options: &DiffOptions) -> Vec<DiffEdit> {
+        self.root().diff(&other.root(), options)
+    }
+
+    /// Checks if the documents are equal, ignoring the attribute
+    /// order, whitespace-only text nodes, and namespace prefixes.
+    pub fn semantic_eq(&self, other: &Document) -> bool {
+        self.root().semantic_eq(&other.root())
+    }
+}
@@ -8,6 +8,9 @@
 ** the License, or (at your option) any later version.
 */
 
+use std::error::Error;
+use std::fmt::Display;
+
 use crate::{NoMemory, ParseError};
 
 impl From<NoMemory> for ParseError {
@@ -16,17 +19,37 @@ impl From<NoMemory> for ParseError {
     }
 }
 
-pub(super) mod description {
-    pub(in super::super) const NO_DOCUMENT: &str = "no document parsed yet";
-    pub(in super::super) const NO_START_TAG: &str = "document must start with a StartTag element";
-    pub(in super::super) const TAG_MISMATCH: &str = "start and end tags have different names";
-    pub(in super::super) const DUPLICATE_ATTRIBUTE: &str =
-        "attribute name already used in this tag";
-    pub(in super::super) const CDATA_ATTRIBUTE: &str = "attributes cannot be set on CDATA elements";
-    pub(in super::super) const CDATA_CHILDREN: &str =
-        "child elements cannot be added on CDATA elements";
-    pub(in super::super) const NULL_CURSOR_EDIT: &str = "null cursor cannot edit the document";
-    pub(in super::super) const ROOT_SIBLING: &str = "root element cannot have siblings";
-    pub(in super::super) const CDATA_TO_DOCUMENT: &str =
-        "cannot make a document out of a CDATA element";
+/// The error type for the typed value accessors of a [Cursor](crate::Cursor).
+///
+/// Names the tag and the attribute whose value could not be converted,
+/// so the error can be reported without any further context.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ValueError {
+    /// Name of the tag.
+    pub tag: String,
+    /// Name of the attribute, or `None` if the text of the tag is converted.
+    pub attribute: Option<String>,
+    /// The value which could not be converted.
+    pub value: String,
+    /// Description of the conversion error.
+    pub reason: String,
 }
+
+impl Display for ValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.attribute {
+            Some(attribute) => write!(
+                f,
+                "invalid value '{}' of attribute '{}' in tag '{}': {}",
+                self.value, attribute, self.tag, self.reason
+            ),
+            None => write!(
+                f,
+                "invalid text '{}' in tag '{}': {}",
+                self.value, self.tag, self.reason
+            ),
+        }
+    }
+}
+
+impl Error for ValueError {}
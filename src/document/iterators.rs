@@ -212,3 +212,30 @@ impl<'a> Iterator for PrecedingSibling<'a> {
         Some(result)
     }
 }
+
+pub struct TextChunks<'a> {
+    descendants: DescendantOrSelf<'a>,
+}
+
+impl<'a> TextChunks<'a> {
+    pub fn new(cursor: Cursor<'a>) -> Self {
+        TextChunks {
+            descendants: DescendantOrSelf::new(cursor),
+        }
+    }
+}
+
+impl<'a> Iterator for TextChunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for node in self.descendants.by_ref() {
+            unsafe {
+                if let NodePayload::CData(cdata) = (*node.get_node_ptr()).payload {
+                    return Some((*cdata).as_str());
+                }
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,396 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use crate::ElementSpan;
+use crate::ParseError;
+use crate::ParseErrorKind;
+use crate::SaxElement;
+
+use super::super::entities::escape;
+use super::Cursor;
+use super::Document;
+use super::Node;
+use super::NodePayload;
+use super::Tag;
+
+// Byte offsets of a node in the original text.
+//
+// Text between the end of the previous sibling (or the start tag of
+// the parent) and the start of the node is kept as the leading part of
+// the node, so the comments and whitespace are not lost. Text nodes
+// span all the way to the next tag, including the character data
+// section markup, references, and comments between the text chunks.
+#[derive(Clone, Copy, Default)]
+pub(super) struct NodeSource {
+    lead_start: usize,
+    start: usize,
+    content_start: usize,
+    tail_start: usize,
+    content_end: usize,
+    end: usize,
+    // Own markup of the node is edited
+    modified: bool,
+    // Children list or a descendant is edited
+    dirty: bool,
+}
+
+impl NodeSource {
+    fn is_empty_tag(&self) -> bool {
+        self.content_start == self.end
+    }
+}
+
+// Records an edit for the lossless serialization.
+//
+// Own edits are the attribute and text value changes of the node,
+// other edits are the changes in the children list of the node.
+pub(super) fn touch(node: *mut Node, own: bool) {
+    unsafe {
+        let source = (*node).source;
+        if source.is_null() {
+            // Not parsed in the lossless mode, or a new node which is
+            // already regenerated as a whole
+            return;
+        }
+        let mut current = node;
+        if own {
+            (*source).modified = true;
+            current = (*node).parent;
+        }
+        while !current.is_null() {
+            let source = (*current).source;
+            if source.is_null() || (*source).dirty {
+                break;
+            }
+            (*source).dirty = true;
+            current = (*current).parent;
+        }
+    }
+}
+
+// Collects the source text and node ranges while a document is parsed.
+pub(super) struct SourceRecorder {
+    text: Vec<u8>,
+    sources: Vec<NodeSource>,
+    open_tags: Vec<usize>,
+    text_node: Option<usize>,
+    pending_end: Option<usize>,
+    boundary: usize,
+}
+
+impl SourceRecorder {
+    pub(super) fn new() -> Self {
+        SourceRecorder {
+            text: Vec::new(),
+            sources: Vec::new(),
+            open_tags: Vec::new(),
+            text_node: None,
+            pending_end: None,
+            boundary: 0,
+        }
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.text.clear();
+        self.sources.clear();
+        self.open_tags.clear();
+        self.text_node = None;
+        self.pending_end = None;
+        self.boundary = 0;
+    }
+
+    pub(super) fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), ParseError> {
+        if self.text.try_reserve(bytes.len()).is_err() {
+            return Err(ParseError::NoMemory);
+        }
+        self.text.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn push_source(&mut self, source: NodeSource) -> Result<usize, ParseError> {
+        if self.sources.try_reserve(1).is_err() || self.open_tags.try_reserve(1).is_err() {
+            return Err(ParseError::NoMemory);
+        }
+        self.sources.push(source);
+        Ok(self.sources.len() - 1)
+    }
+
+    // End tags are yielded after the name, so the closing '>' is
+    // looked up when the next element is parsed.
+    fn resolve_end(&mut self) {
+        if let Some(index) = self.pending_end.take() {
+            let source = &mut self.sources[index];
+            let mut end = source.end;
+            while end < self.text.len() && self.text[end] != b'>' {
+                end += 1;
+            }
+            source.end = end + 1;
+            self.boundary = source.end;
+        }
+    }
+
+    fn end_text(&mut self, end: usize) {
+        if let Some(index) = self.text_node.take() {
+            self.sources[index].end = end;
+            self.boundary = end;
+        }
+    }
+
+    pub(super) fn record(
+        &mut self,
+        element: &SaxElement,
+        span: &ElementSpan,
+    ) -> Result<(), ParseError> {
+        self.resolve_end();
+        let start = span.span.start.bytes;
+        let end = span.span.end.bytes;
+        match element {
+            SaxElement::StartTag(_) => {
+                self.end_text(start);
+                let index = self.push_source(NodeSource {
+                    lead_start: self.boundary,
+                    start,
+                    ..NodeSource::default()
+                })?;
+                self.open_tags.push(index);
+            }
+            SaxElement::Attribute(..) => {}
+            SaxElement::StartTagContent => {
+                if let Some(index) = self.open_tags.last() {
+                    self.sources[*index].content_start = end;
+                    self.boundary = end;
+                }
+            }
+            SaxElement::StartTagEmpty => {
+                if let Some(index) = self.open_tags.pop() {
+                    // The '>' immediately follows the '/'
+                    let source = &mut self.sources[index];
+                    source.content_start = end + 1;
+                    source.tail_start = end + 1;
+                    source.content_end = end + 1;
+                    source.end = end + 1;
+                    self.boundary = end + 1;
+                }
+            }
+            SaxElement::EndTag(_) => {
+                self.end_text(start);
+                if let Some(index) = self.open_tags.pop() {
+                    let source = &mut self.sources[index];
+                    source.tail_start = self.boundary;
+                    source.content_end = start;
+                    source.end = end;
+                    self.pending_end = Some(index);
+                }
+            }
+            SaxElement::CData(_) | SaxElement::CDataSection(_) | SaxElement::Reference(_) => {
+                if self.text_node.is_none() {
+                    let index = self.push_source(NodeSource {
+                        lead_start: self.boundary,
+                        start: self.boundary,
+                        ..NodeSource::default()
+                    })?;
+                    self.text_node = Some(index);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Attaches the recorded sources to the nodes of the parsed document.
+    pub(super) fn attach(&mut self, doc: &mut Document) -> Result<(), ParseError> {
+        let result = self.attach_sources(doc);
+        self.clear();
+        result
+    }
+
+    fn attach_sources(&mut self, doc: &mut Document) -> Result<(), ParseError> {
+        self.resolve_end();
+        let Ok(text) = std::str::from_utf8(&self.text) else {
+            return Err(ParseErrorKind::Utf8InvalidContByte.into());
+        };
+        let text = doc.arena.push_str(text)?;
+        doc.source_text = text.as_ptr();
+        doc.source_size = text.len();
+
+        // Nodes are created in the same order as the sources
+        let mut sources = self.sources.iter();
+        let mut node = doc.root_node;
+        unsafe {
+            while !node.is_null() {
+                let Some(source) = sources.next() else {
+                    break;
+                };
+                let ptr = doc.arena.alloc_struct::<NodeSource>()?.as_ptr();
+                ptr.write(*source);
+                (*node).source = ptr;
+
+                if let NodePayload::Tag(tag) = (*node).payload
+                    && !(*tag).children.is_null()
+                {
+                    node = (*tag).children;
+                    continue;
+                }
+                while !node.is_null() && (*node).next.is_null() {
+                    node = (*node).parent;
+                }
+                if !node.is_null() {
+                    node = (*node).next;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn push_start_tag(tag: &Tag, empty: bool, buf: &mut String) {
+    buf.push('<');
+    buf.push_str(tag.as_str());
+    let mut attr = tag.attributes;
+    while !attr.is_null() {
+        buf.push(' ');
+        unsafe {
+            buf.push_str((*attr).name_as_str());
+            buf.push_str("=\"");
+            escape((*attr).value_as_str(), buf);
+            buf.push('"');
+            attr = (*attr).next;
+        }
+    }
+    if empty {
+        buf.push_str("/>");
+    } else {
+        buf.push('>');
+    }
+}
+
+impl Document {
+    fn source_text(&self) -> Option<&str> {
+        if self.source_text.is_null() {
+            return None;
+        }
+        unsafe {
+            let slice = std::slice::from_raw_parts(self.source_text, self.source_size);
+            Some(std::str::from_utf8_unchecked(slice))
+        }
+    }
+
+    /// Returns the XML text of the document, keeping the original text of the unedited parts.
+    ///
+    /// For the documents parsed in the [lossless](crate::DocumentParser::set_lossless)
+    /// mode, the unedited elements, the whitespace inside the tags, the
+    /// attribute quotes, comments, and the prolog are written byte for
+    /// byte as they are in the original text. Only the edited tags and
+    /// text are regenerated. Other documents are serialized the same
+    /// as the [to_string](Document::to_string) method.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use iks::{DocumentParser, ParseError};
+    /// # fn main() -> Result<(), ParseError> {
+    ///
+    /// let xml_text = "<?xml version='1.0'?>\n<a>\n  <!-- port -->\n  <b port = '80' />\n</a>\n";
+    ///
+    /// let mut parser = DocumentParser::new();
+    /// parser.set_lossless(true);
+    /// parser.parse_bytes(xml_text.as_bytes())?;
+    /// let document = parser.into_document()?;
+    /// document.find_tag("b").set_attribute("port", Some("8080"))?;
+    ///
+    /// assert_eq!(
+    ///     document.to_lossless_string(),
+    ///     "<?xml version='1.0'?>\n<a>\n  <!-- port -->\n  <b port=\"8080\"/>\n</a>\n"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_lossless_string(&self) -> String {
+        let Some(text) = self.source_text() else {
+            return self.to_string();
+        };
+        let root = self.root_node;
+        let mut buf = String::with_capacity(text.len());
+        let mut node = root;
+        let mut going_down = true;
+        unsafe {
+            if (*root).source.is_null() {
+                return self.to_string();
+            }
+            loop {
+                let source = (*node).source;
+                if going_down {
+                    if source.is_null() {
+                        // New nodes are generated as a whole
+                        buf.push_str(&Cursor::new(node, self).to_string());
+                    } else {
+                        let source = &*source;
+                        buf.push_str(&text[source.lead_start..source.start]);
+                        if !source.modified && !source.dirty {
+                            buf.push_str(&text[source.start..source.end]);
+                        } else {
+                            match (*node).payload {
+                                NodePayload::CData(cdata) => escape((*cdata).as_str(), &mut buf),
+                                NodePayload::Tag(tag) => {
+                                    let tag = &*tag;
+                                    let has_children = !tag.children.is_null();
+                                    if source.modified || (source.is_empty_tag() && has_children) {
+                                        let empty = source.is_empty_tag() && !has_children;
+                                        push_start_tag(tag, empty, &mut buf);
+                                    } else {
+                                        buf.push_str(&text[source.start..source.content_start]);
+                                    }
+                                    if has_children {
+                                        node = tag.children;
+                                        continue;
+                                    }
+                                    going_down = false;
+                                    continue;
+                                }
+                            }
+                        }
+                    }
+                } else {
+                    // Leaving a tag whose children are written separately
+                    let source = &*source;
+                    if let NodePayload::Tag(tag) = (*node).payload {
+                        if !source.is_empty_tag() {
+                            if source.modified {
+                                // Tag might be renamed
+                                buf.push_str(&text[source.tail_start..source.content_end]);
+                                buf.push_str("</");
+                                buf.push_str((*tag).as_str());
+                                buf.push('>');
+                            } else {
+                                buf.push_str(&text[source.tail_start..source.end]);
+                            }
+                        } else if !(*tag).children.is_null() {
+                            buf.push_str("</");
+                            buf.push_str((*tag).as_str());
+                            buf.push('>');
+                        }
+                    }
+                }
+                if node == root {
+                    break;
+                }
+                let next = (*node).next;
+                if next.is_null() {
+                    node = (*node).parent;
+                    going_down = false;
+                } else {
+                    node = next;
+                    going_down = true;
+                }
+            }
+            buf.push_str(&text[(*(*root).source).end..]);
+        }
+        buf
+    }
+}
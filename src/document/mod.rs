@@ -9,15 +9,28 @@
 */
 
 mod builder;
+mod canonical;
+mod compact;
+mod diff;
 mod error;
 mod iterators;
+mod lossless;
+mod namespaces;
 mod parser;
+mod restructure;
+mod serialize;
 mod sync_cursor;
 mod sync_iterators;
+mod text;
+mod values;
 
+use std::cell::Cell;
 use std::fmt::Debug;
+use std::fs::File;
+use std::io::Read;
 use std::marker::PhantomPinned;
 use std::marker::Send;
+use std::path::Path;
 use std::ptr::NonNull;
 use std::ptr::null_mut;
 use std::str::FromStr;
@@ -26,20 +39,34 @@ use crate::Arena;
 use crate::ArenaStats;
 use crate::NoMemory;
 pub use crate::ParseError;
+use crate::ParseErrorKind;
+use crate::ReadError;
 
 use super::entities::escape;
-use super::entities::escape_fmt;
 use super::entities::escaped_size;
 
+use compact::subtree_bytes;
+use lossless::NodeSource;
+use lossless::touch;
+use restructure::unlink;
+
 pub use builder::DocumentBuilder;
-use error::description;
+pub use canonical::Canonicalization;
+pub use diff::DiffEdit;
+pub use diff::DiffOptions;
+pub use error::ValueError;
 pub use iterators::Ancestor;
 pub use iterators::Attributes;
 pub use iterators::Children;
 pub use iterators::DescendantOrSelf;
 pub use iterators::FollowingSibling;
 pub use iterators::PrecedingSibling;
+pub use iterators::TextChunks;
 pub use parser::DocumentParser;
+pub use serialize::Indent;
+pub use serialize::Newline;
+pub use serialize::Quote;
+pub use serialize::SerializeOptions;
 pub use sync_cursor::SyncCursor;
 
 enum NodePayload {
@@ -52,6 +79,7 @@ struct Node {
     previous: *mut Node,
     parent: *mut Node,
     payload: NodePayload,
+    source: *mut NodeSource,
 
     _pin: PhantomPinned,
 }
@@ -134,6 +162,7 @@ impl ArenaExt for Arena {
             (*node).previous = null_mut();
             (*node).parent = null_mut();
             (*node).payload = payload;
+            (*node).source = null_mut();
         }
 
         Ok(NonNull::new(node).unwrap())
@@ -277,6 +306,10 @@ impl Visitor {
 pub struct Document {
     arena: Arena,
     root_node: *mut Node,
+    source_text: *const u8,
+    source_size: usize,
+    // Arena bytes of the removed elements and replaced strings
+    wasted: Cell<usize>,
 }
 
 impl Document {
@@ -289,6 +322,9 @@ impl Document {
         Ok(Document {
             arena,
             root_node: node,
+            source_text: std::ptr::null(),
+            source_size: 0,
+            wasted: Cell::new(0),
         })
     }
 
@@ -308,12 +344,33 @@ impl Document {
         Ok(Document {
             arena,
             root_node: node,
+            source_text: std::ptr::null(),
+            source_size: 0,
+            wasted: Cell::new(0),
         })
     }
 
+    /// Parses a document from the given reader.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Document, ReadError> {
+        let mut parser = DocumentParser::new();
+        parser.parse_reader(reader)?;
+        Ok(parser.into_document()?)
+    }
+
+    /// Parses a document from the given file.
+    ///
+    /// File size is used as the size hint of the document.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Document, ReadError> {
+        let file = File::open(path)?;
+        let size_hint = file.metadata()?.len();
+        let mut parser = DocumentParser::with_size_hint(size_hint as usize);
+        parser.parse_reader(file)?;
+        Ok(parser.into_document()?)
+    }
+
     /// Returns a cursor to the root element of the document.
     pub fn root<'a>(&'a self) -> Cursor<'a> {
-        Cursor::new(self.root_node, &self.arena)
+        Cursor::new(self.root_node, self)
     }
 
     pub fn arena_stats(&self) -> ArenaStats {
@@ -396,7 +453,7 @@ unsafe impl Send for Document {}
 
 macro_rules! null_cursor {
     ($x:expr) => {
-        Cursor::new(null_mut() as *mut Node, $x.arena)
+        Cursor::new(null_mut() as *mut Node, $x.document)
     };
 }
 
@@ -412,7 +469,7 @@ macro_rules! cursor_edit_guards {
     ($self:ident) => {{
         let node = $self.get_node_ptr();
         if node.is_null() {
-            return Err(ParseError::BadXml(description::NULL_CURSOR_EDIT));
+            return Err(ParseErrorKind::NullCursorEdit.into());
         }
         node
     }};
@@ -421,12 +478,12 @@ macro_rules! cursor_edit_guards {
 /// Reference to an element in a document.
 pub struct Cursor<'a> {
     node: *mut Node,
-    arena: &'a Arena,
+    document: &'a Document,
 }
 
 impl<'a> Cursor<'a> {
-    fn new(node: *mut Node, arena: &'a Arena) -> Cursor<'a> {
-        Cursor { node, arena }
+    fn new(node: *mut Node, document: &'a Document) -> Cursor<'a> {
+        Cursor { node, document }
     }
 
     fn get_node_ptr(&self) -> *mut Node {
@@ -452,11 +509,16 @@ impl<'a> Cursor<'a> {
             match (*node).payload {
                 NodePayload::CData(_) => {
                     // Cannot insert a tag into a cdata element
-                    Err(ParseError::BadXml(description::CDATA_CHILDREN))
+                    Err(ParseErrorKind::CDataChildren.into())
                 }
                 NodePayload::Tag(tag) => {
-                    let new_tag = self.arena.alloc_tag(tag_name)?.as_ptr();
-                    let new_node = self.arena.alloc_node(NodePayload::Tag(new_tag))?.as_ptr();
+                    let new_tag = self.document.arena.alloc_tag(tag_name)?.as_ptr();
+                    let new_node = self
+                        .document
+                        .arena
+                        .alloc_node(NodePayload::Tag(new_tag))?
+                        .as_ptr();
+                    touch(node, false);
 
                     (*new_node).parent = node;
                     if (*tag).children.is_null() {
@@ -468,7 +530,7 @@ impl<'a> Cursor<'a> {
                     }
                     (*tag).last_child = new_node;
 
-                    Ok(Cursor::new(new_node, self.arena))
+                    Ok(Cursor::new(new_node, self.document))
                 }
             }
         }
@@ -479,14 +541,19 @@ impl<'a> Cursor<'a> {
 
         unsafe {
             if (*node).parent.is_null() {
-                return Err(ParseError::BadXml(description::ROOT_SIBLING));
+                return Err(ParseErrorKind::RootSibling.into());
             }
 
-            let new_tag = self.arena.alloc_tag(tag_name)?.as_ptr();
-            let new_node = self.arena.alloc_node(NodePayload::Tag(new_tag))?.as_ptr();
+            let new_tag = self.document.arena.alloc_tag(tag_name)?.as_ptr();
+            let new_node = self
+                .document
+                .arena
+                .alloc_node(NodePayload::Tag(new_tag))?
+                .as_ptr();
 
             let parent = (*node).parent;
             (*new_node).parent = parent;
+            touch(parent, false);
 
             let next = (*node).next;
             (*new_node).next = next;
@@ -506,7 +573,7 @@ impl<'a> Cursor<'a> {
             (*new_node).previous = node;
             (*node).next = new_node;
 
-            Ok(Cursor::new(new_node, self.arena))
+            Ok(Cursor::new(new_node, self.document))
         }
     }
 
@@ -515,14 +582,19 @@ impl<'a> Cursor<'a> {
 
         unsafe {
             if (*node).parent.is_null() {
-                return Err(ParseError::BadXml(description::ROOT_SIBLING));
+                return Err(ParseErrorKind::RootSibling.into());
             }
 
-            let new_tag = self.arena.alloc_tag(tag_name)?.as_ptr();
-            let new_node = self.arena.alloc_node(NodePayload::Tag(new_tag))?.as_ptr();
+            let new_tag = self.document.arena.alloc_tag(tag_name)?.as_ptr();
+            let new_node = self
+                .document
+                .arena
+                .alloc_node(NodePayload::Tag(new_tag))?
+                .as_ptr();
 
             let parent = (*node).parent;
             (*new_node).parent = parent;
+            touch(parent, false);
 
             let previous = (*node).previous;
             (*new_node).previous = previous;
@@ -542,7 +614,7 @@ impl<'a> Cursor<'a> {
             (*new_node).next = node;
             (*node).previous = new_node;
 
-            Ok(Cursor::new(new_node, self.arena))
+            Ok(Cursor::new(new_node, self.document))
         }
     }
 
@@ -555,18 +627,19 @@ impl<'a> Cursor<'a> {
 
         unsafe {
             match (*node).payload {
-                NodePayload::CData(_) => Err(ParseError::BadXml(description::CDATA_ATTRIBUTE)),
+                NodePayload::CData(_) => Err(ParseErrorKind::CDataAttribute.into()),
                 NodePayload::Tag(tag) => {
                     let mut attr = (*tag).attributes;
                     while !attr.is_null() {
                         if name == (*attr).name_as_str() {
                             // Two attributes with the same name
-                            return Err(ParseError::BadXml(description::DUPLICATE_ATTRIBUTE));
+                            return Err(ParseErrorKind::DuplicateAttribute.into());
                         }
                         attr = (*attr).next;
                     }
                     // Add the new attribute
-                    let attribute = self.arena.alloc_attribute(name, value)?.as_ptr();
+                    let attribute = self.document.arena.alloc_attribute(name, value)?.as_ptr();
+                    touch(node, true);
                     if (*tag).attributes.is_null() {
                         (*tag).attributes = attribute;
                     }
@@ -576,7 +649,7 @@ impl<'a> Cursor<'a> {
                     }
                     (*tag).last_attribute = attribute;
 
-                    Ok(Cursor::new(node, self.arena))
+                    Ok(Cursor::new(node, self.document))
                 }
             }
         }
@@ -591,14 +664,21 @@ impl<'a> Cursor<'a> {
 
         unsafe {
             match (*node).payload {
-                NodePayload::CData(_) => Err(ParseError::BadXml(description::CDATA_ATTRIBUTE)),
+                NodePayload::CData(_) => Err(ParseErrorKind::CDataAttribute.into()),
                 NodePayload::Tag(tag) => {
                     let mut attr = (*tag).attributes;
                     while !attr.is_null() {
                         if name == (*attr).name_as_str() {
                             // Existing attribute, change the value
+                            touch(node, true);
                             match value {
                                 None => {
+                                    self.document.abandon(
+                                        node,
+                                        size_of::<Attribute>()
+                                            + (*attr).name_size
+                                            + (*attr).value_size,
+                                    );
                                     if !(*attr).next.is_null() {
                                         (*(*attr).next).previous = (*attr).previous;
                                     }
@@ -613,10 +693,11 @@ impl<'a> Cursor<'a> {
                                     }
                                 }
                                 Some(value) => {
-                                    let value = self.arena.push_str(value)?;
+                                    let value = self.document.arena.push_str(value)?;
+                                    self.document.replace(node, (*attr).value_size, value.len());
                                     (*attr).value = value.as_ptr();
                                     (*attr).value_size = value.len();
-                                    return Ok(Cursor::new(node, self.arena));
+                                    return Ok(Cursor::new(node, self.document));
                                 }
                             }
                         }
@@ -625,11 +706,13 @@ impl<'a> Cursor<'a> {
                     match value {
                         None => {
                             // Attribute already non existent
-                            Ok(Cursor::new(node, self.arena))
+                            Ok(Cursor::new(node, self.document))
                         }
                         Some(value) => {
                             // Add a new attribute
-                            let attribute = self.arena.alloc_attribute(name, value)?.as_ptr();
+                            let attribute =
+                                self.document.arena.alloc_attribute(name, value)?.as_ptr();
+                            touch(node, true);
                             if (*tag).attributes.is_null() {
                                 (*tag).attributes = attribute;
                             }
@@ -639,7 +722,7 @@ impl<'a> Cursor<'a> {
                             }
                             (*tag).last_attribute = attribute;
 
-                            Ok(Cursor::new(node, self.arena))
+                            Ok(Cursor::new(node, self.document))
                         }
                     }
                 }
@@ -647,30 +730,146 @@ impl<'a> Cursor<'a> {
         }
     }
 
+    /// Removes the attribute with the given name, if it exists.
+    pub fn remove_attribute<'b>(&self, name: &'b str) -> Result<Cursor<'a>, ParseError> {
+        self.set_attribute(name, None)
+    }
+
+    /// Changes the name of the tag element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use iks::{Document, ParseError};
+    /// # fn main() -> Result<(), ParseError> {
+    ///
+    /// let doc = Document::from_str("<a><b x='1' y='2'>old</b></a>")?;
+    /// doc.find_tag("b").rename("c")?.remove_attribute("x")?.set_text("new")?;
+    ///
+    /// assert_eq!(doc.to_string(), "<a><c y=\"2\">new</c></a>");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn rename<'b>(&self, tag_name: &'b str) -> Result<Cursor<'a>, ParseError> {
+        let node = cursor_edit_guards!(self);
+
+        unsafe {
+            match (*node).payload {
+                NodePayload::CData(_) => Err(ParseErrorKind::CDataRename.into()),
+                NodePayload::Tag(tag) => {
+                    if (*tag).as_str() != tag_name {
+                        let name = self.document.arena.push_str(tag_name)?;
+                        self.document.replace(node, (*tag).name_size, name.len());
+                        touch(node, true);
+                        (*tag).name = name.as_ptr();
+                        (*tag).name_size = name.len();
+                    }
+                    Ok(Cursor::new(node, self.document))
+                }
+            }
+        }
+    }
+
+    /// Changes the value of the character data element.
+    pub fn set_cdata<'b>(&self, cdata: &'b str) -> Result<Cursor<'a>, ParseError> {
+        let node = cursor_edit_guards!(self);
+
+        unsafe {
+            match (*node).payload {
+                NodePayload::Tag(_) => Err(ParseErrorKind::TagSetCData.into()),
+                NodePayload::CData(old) => {
+                    if (*old).as_str() != cdata {
+                        let value = self.document.arena.push_str(cdata)?;
+                        self.document.replace(node, (*old).value_size, value.len());
+                        touch(node, true);
+                        (*old).value = value.as_ptr();
+                        (*old).value_size = value.len();
+                    }
+                    Ok(Cursor::new(node, self.document))
+                }
+            }
+        }
+    }
+
+    /// Removes all children of the tag element.
+    pub fn clear_children(&self) -> Result<Cursor<'a>, ParseError> {
+        let node = cursor_edit_guards!(self);
+
+        unsafe {
+            let NodePayload::Tag(tag) = (*node).payload else {
+                return Err(ParseErrorKind::CDataChildren.into());
+            };
+            if !(*tag).children.is_null() {
+                touch(node, false);
+                let mut bytes = 0;
+                let mut child = (*tag).children;
+                while !child.is_null() {
+                    bytes += subtree_bytes(child);
+                    let next = (*child).next;
+                    (*child).parent = null_mut();
+                    (*child).next = null_mut();
+                    (*child).previous = null_mut();
+                    child = next;
+                }
+                (*tag).children = null_mut();
+                (*tag).last_child = null_mut();
+                self.document.abandon(node, bytes);
+            }
+        }
+        Ok(Cursor::new(node, self.document))
+    }
+
+    /// Replaces all children of the tag element with a single text node.
+    ///
+    /// An empty text leaves the tag without children.
+    pub fn set_text<'b>(&self, text: &'b str) -> Result<Cursor<'a>, ParseError> {
+        let node = cursor_edit_guards!(self);
+
+        unsafe {
+            if let NodePayload::CData(_) = (*node).payload {
+                return Err(ParseErrorKind::CDataChildren.into());
+            }
+        }
+        self.clear_children()?;
+        if !text.is_empty() {
+            self.clone().insert_cdata(text)?;
+        }
+        Ok(Cursor::new(node, self.document))
+    }
+
     pub fn insert_cdata<'b>(self, cdata: &'b str) -> Result<Cursor<'a>, ParseError> {
         let node = cursor_edit_guards!(self);
 
         unsafe {
             match (*node).payload {
-                NodePayload::CData(_) => Err(ParseError::BadXml(description::CDATA_CHILDREN)),
+                NodePayload::CData(_) => Err(ParseErrorKind::CDataChildren.into()),
                 NodePayload::Tag(tag) => {
                     let last = (*tag).last_child;
                     if !last.is_null()
                         && let NodePayload::CData(cdata_node) = (*last).payload
                     {
                         let old_s = (*cdata_node).as_str();
-                        let s = self.arena.concat_str(old_s, cdata)?;
+                        let s = self.document.arena.concat_str(old_s, cdata)?;
+                        if s.as_ptr() == old_s.as_ptr() {
+                            self.document.replace(last, 0, cdata.len());
+                        } else {
+                            self.document.replace(last, old_s.len(), s.len());
+                        }
                         (*cdata_node).value = s.as_ptr();
                         (*cdata_node).value_size = s.len();
+                        touch(last, true);
 
-                        return Ok(Cursor::new(last, self.arena));
+                        return Ok(Cursor::new(last, self.document));
                     }
 
-                    let new_cdata = self.arena.alloc_cdata(cdata)?.as_ptr();
+                    let new_cdata = self.document.arena.alloc_cdata(cdata)?.as_ptr();
                     let new_node = self
+                        .document
                         .arena
                         .alloc_node(NodePayload::CData(new_cdata))?
                         .as_ptr();
+                    touch(node, false);
 
                     (*new_node).parent = node;
                     if (*tag).children.is_null() {
@@ -682,7 +881,7 @@ impl<'a> Cursor<'a> {
                     }
                     (*tag).last_child = new_node;
 
-                    Ok(Cursor::new(new_node, self.arena))
+                    Ok(Cursor::new(new_node, self.document))
                 }
             }
         }
@@ -693,26 +892,34 @@ impl<'a> Cursor<'a> {
 
         unsafe {
             if (*node).parent.is_null() {
-                return Err(ParseError::BadXml(description::ROOT_SIBLING));
+                return Err(ParseErrorKind::RootSibling.into());
             }
 
             if let NodePayload::CData(old_cdata) = (*node).payload {
                 let old_s = (*old_cdata).as_str();
-                let s = self.arena.concat_str(old_s, cdata)?;
+                let s = self.document.arena.concat_str(old_s, cdata)?;
+                if s.as_ptr() == old_s.as_ptr() {
+                    self.document.replace(node, 0, cdata.len());
+                } else {
+                    self.document.replace(node, old_s.len(), s.len());
+                }
                 (*old_cdata).value = s.as_ptr();
                 (*old_cdata).value_size = s.len();
+                touch(node, true);
 
-                return Ok(Cursor::new(node, self.arena));
+                return Ok(Cursor::new(node, self.document));
             }
 
-            let new_cdata = self.arena.alloc_cdata(cdata)?.as_ptr();
+            let new_cdata = self.document.arena.alloc_cdata(cdata)?.as_ptr();
             let new_node = self
+                .document
                 .arena
                 .alloc_node(NodePayload::CData(new_cdata))?
                 .as_ptr();
 
             let parent = (*node).parent;
             (*new_node).parent = parent;
+            touch(parent, false);
 
             let next = (*node).next;
             (*new_node).next = next;
@@ -731,7 +938,7 @@ impl<'a> Cursor<'a> {
             (*new_node).previous = node;
             (*node).next = new_node;
 
-            Ok(Cursor::new(new_node, self.arena))
+            Ok(Cursor::new(new_node, self.document))
         }
     }
 
@@ -740,17 +947,19 @@ impl<'a> Cursor<'a> {
 
         unsafe {
             if (*node).parent.is_null() {
-                return Err(ParseError::BadXml(description::ROOT_SIBLING));
+                return Err(ParseErrorKind::RootSibling.into());
             }
 
-            let new_cdata = self.arena.alloc_cdata(cdata)?.as_ptr();
+            let new_cdata = self.document.arena.alloc_cdata(cdata)?.as_ptr();
             let new_node = self
+                .document
                 .arena
                 .alloc_node(NodePayload::CData(new_cdata))?
                 .as_ptr();
 
             let parent = (*node).parent;
             (*new_node).parent = parent;
+            touch(parent, false);
 
             let previous = (*node).previous;
             (*new_node).previous = previous;
@@ -770,7 +979,7 @@ impl<'a> Cursor<'a> {
             (*new_node).next = node;
             (*node).previous = new_node;
 
-            Ok(Cursor::new(new_node, self.arena))
+            Ok(Cursor::new(new_node, self.document))
         }
     }
 
@@ -779,35 +988,12 @@ impl<'a> Cursor<'a> {
         if node.is_null() {
             return;
         }
+        // Root element is never removed
         unsafe {
-            let parent = (*node).parent;
-            if parent.is_null() {
-                // Cannot remove the root element
-                return;
+            if !(*node).parent.is_null() {
+                self.document.abandon(node, subtree_bytes(node));
             }
-            // Fix siblings
-            if !(*node).next.is_null() {
-                (*(*node).next).previous = (*node).previous;
-            }
-            if !(*node).previous.is_null() {
-                (*(*node).previous).next = (*node).next;
-            }
-            // Fix parent
-            match (*parent).payload {
-                NodePayload::Tag(tag) => {
-                    if (*tag).children == node {
-                        (*tag).children = (*node).next;
-                    }
-                    if (*tag).last_child == node {
-                        (*tag).last_child = (*node).previous;
-                    }
-                }
-                NodePayload::CData(_) => {}
-            }
-            // Fix self
-            (*node).parent = null_mut();
-            (*node).next = null_mut();
-            (*node).previous = null_mut();
+            unlink(node);
         }
     }
 
@@ -820,7 +1006,7 @@ impl<'a> Cursor<'a> {
 
         unsafe {
             let node = self.node;
-            Cursor::new((*node).next, self.arena)
+            Cursor::new((*node).next, self.document)
         }
     }
 
@@ -842,7 +1028,7 @@ impl<'a> Cursor<'a> {
 
         unsafe {
             let node = self.node;
-            Cursor::new((*node).previous, self.arena)
+            Cursor::new((*node).previous, self.document)
         }
     }
 
@@ -864,7 +1050,7 @@ impl<'a> Cursor<'a> {
 
         unsafe {
             let node = self.node;
-            Cursor::new((*node).parent, self.arena)
+            Cursor::new((*node).parent, self.document)
         }
     }
 
@@ -891,7 +1077,7 @@ impl<'a> Cursor<'a> {
                 NodePayload::CData(_) => {
                     null_cursor!(self)
                 }
-                NodePayload::Tag(tag) => Cursor::new((*tag).children, self.arena),
+                NodePayload::Tag(tag) => Cursor::new((*tag).children, self.document),
             }
         }
     }
@@ -905,7 +1091,7 @@ impl<'a> Cursor<'a> {
                 NodePayload::CData(_) => {
                     null_cursor!(self)
                 }
-                NodePayload::Tag(tag) => Cursor::new((*tag).last_child, self.arena),
+                NodePayload::Tag(tag) => Cursor::new((*tag).last_child, self.document),
             }
         }
     }
@@ -1173,7 +1359,7 @@ impl<'a> Cursor<'a> {
 
     pub fn insert_document<'b>(self, cursor: Cursor<'b>) -> Result<Cursor<'a>, ParseError> {
         if self.node.is_null() || cursor.is_null() {
-            return Err(ParseError::BadXml(description::NULL_CURSOR_EDIT));
+            return Err(ParseErrorKind::NullCursorEdit.into());
         }
 
         let mut visitor = cursor.visitor();
@@ -1208,10 +1394,10 @@ impl<'a> Cursor<'a> {
 
     pub fn to_document(&self) -> Result<Document, ParseError> {
         if self.node.is_null() {
-            return Err(ParseError::BadXml(description::NULL_CURSOR_EDIT));
+            return Err(ParseErrorKind::NullCursorEdit.into());
         }
         if !self.is_tag() {
-            return Err(ParseError::BadXml(description::CDATA_TO_DOCUMENT));
+            return Err(ParseErrorKind::CDataToDocument.into());
         }
         let doc = Document::with_size_hint(self.name(), self.str_size())?;
         let mut visitor = self.visitor();
@@ -1249,7 +1435,7 @@ impl Clone for Cursor<'_> {
     fn clone(&self) -> Self {
         Cursor {
             node: self.get_node_ptr(),
-            arena: self.arena,
+            document: self.document,
         }
     }
 }
@@ -1262,49 +1448,7 @@ impl Debug for Cursor<'_> {
 
 impl<'a> std::fmt::Display for Cursor<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.node.is_null() {
-            return Result::Ok(());
-        }
-
-        let mut visitor = self.visitor();
-        while let Some(step) = visitor.next() {
-            match step {
-                VisitorStep::StartTag(tag) => {
-                    f.write_str("<")?;
-                    f.write_str(tag.as_str())?;
-                    let mut attr = tag.attributes;
-                    while !attr.is_null() {
-                        f.write_str(" ")?;
-                        unsafe {
-                            f.write_str((*attr).name_as_str())?;
-                            f.write_str("=\"")?;
-                            escape_fmt((*attr).value_as_str(), f)?;
-                            f.write_str("\"")?;
-                            attr = (*attr).next;
-                        }
-                    }
-                    if tag.children.is_null() {
-                        f.write_str("/>")?;
-                    } else {
-                        f.write_str(">")?;
-                    }
-                }
-                VisitorStep::EndTag(tag) => {
-                    if tag.children.is_null() {
-                        // Already handled
-                    } else {
-                        f.write_str("</")?;
-                        f.write_str(tag.as_str())?;
-                        f.write_str(">")?;
-                    }
-                }
-                VisitorStep::CData(cdata) => {
-                    escape_fmt(cdata.as_str(), f)?;
-                }
-            }
-        }
-
-        Result::Ok(())
+        SerializeOptions::compact().write_cursor(self, f)
     }
 }
 
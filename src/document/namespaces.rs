@@ -0,0 +1,223 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use crate::ParseError;
+use crate::XML_NS;
+use crate::XMLNS_NS;
+use crate::parser::declared_prefix;
+
+use super::Cursor;
+use super::Document;
+
+fn split_name(name: &str) -> (&str, &str) {
+    name.split_once(':').unwrap_or(("", name))
+}
+
+fn declaration_name(prefix: &str) -> String {
+    if prefix.is_empty() {
+        "xmlns".to_string()
+    } else {
+        format!("xmlns:{prefix}")
+    }
+}
+
+impl<'a> Cursor<'a> {
+    // Tags whose declarations are in scope, starting from the closest
+    fn scope_tags(&self) -> impl Iterator<Item = Cursor<'a>> {
+        let own = if self.is_tag() {
+            Some(self.clone())
+        } else {
+            None
+        };
+        own.into_iter().chain(self.clone().ancestor())
+    }
+
+    /// Returns the namespace URI bound to the prefix at this element.
+    ///
+    /// The empty prefix looks up the default namespace. Declarations
+    /// are resolved through the `xmlns` attributes of the element and
+    /// its ancestors. The `xml` and `xmlns` prefixes are always bound.
+    pub fn lookup_namespace(&self, prefix: &str) -> Option<&'a str> {
+        match prefix {
+            "xml" => return Some(XML_NS),
+            "xmlns" => return Some(XMLNS_NS),
+            _ => (),
+        }
+        for tag in self.scope_tags() {
+            for (name, value) in tag.attributes() {
+                if declared_prefix(name) == Some(prefix) {
+                    if value.is_empty() {
+                        // Undeclared default namespace
+                        return None;
+                    }
+                    return Some(value);
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns a prefix bound to the namespace URI at this element.
+    ///
+    /// The empty prefix is returned if the URI is the default namespace.
+    pub fn lookup_prefix(&self, namespace_uri: &str) -> Option<&'a str> {
+        match namespace_uri {
+            XML_NS => return Some("xml"),
+            XMLNS_NS => return Some("xmlns"),
+            _ => (),
+        }
+        for tag in self.scope_tags() {
+            for (name, value) in tag.attributes() {
+                if let Some(prefix) = declared_prefix(name)
+                    && value == namespace_uri
+                    && self.lookup_namespace(prefix) == Some(value)
+                {
+                    return Some(prefix);
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the namespace URI of the tag name.
+    pub fn namespace_uri(&self) -> Option<&'a str> {
+        if !self.is_tag() {
+            return None;
+        }
+        let (prefix, _) = split_name(self.name());
+        self.lookup_namespace(prefix)
+    }
+
+    /// Returns the tag name without the namespace prefix.
+    pub fn local_name(&self) -> &str {
+        split_name(self.name()).1
+    }
+
+    /// Finds the first child tag with the given namespace URI and local name.
+    ///
+    /// The empty URI matches the tags without a namespace.
+    pub fn find_tag_ns(self, namespace_uri: &str, local_name: &str) -> Cursor<'a> {
+        let mut child = self.first_child();
+        while !child.is_null() {
+            if child.is_tag()
+                && child.local_name() == local_name
+                && child.namespace_uri().unwrap_or("") == namespace_uri
+            {
+                break;
+            }
+            child = child.next();
+        }
+        child
+    }
+
+    /// Returns the value of the attribute with the given namespace URI and local name.
+    ///
+    /// The empty URI matches the attributes without a prefix, which
+    /// are not in any namespace.
+    pub fn attribute_ns(&self, namespace_uri: &str, local_name: &str) -> Option<&'a str> {
+        if !self.is_tag() {
+            return None;
+        }
+        for (name, value) in self.clone().attributes() {
+            let (uri, local) = match split_name(name) {
+                ("", "xmlns") => (Some(XMLNS_NS), name),
+                ("", local) => (Some(""), local),
+                (prefix, local) => (self.lookup_namespace(prefix), local),
+            };
+            if local == local_name && uri == Some(namespace_uri) {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Inserts a copy of the given element as the last child, keeping its namespaces.
+    ///
+    /// Unlike the [insert_document](Cursor::insert_document) method,
+    /// prefixes used in the copied subtree are declared again on its
+    /// top element when they are bound to a different namespace, or not
+    /// bound at all, at the new location.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use iks::{Document, ParseError};
+    /// # fn main() -> Result<(), ParseError> {
+    ///
+    /// let roster = Document::from_str(
+    ///     "<iq xmlns:r='jabber:iq:roster'><r:query><r:item jid='a@b'/></r:query></iq>",
+    /// )?;
+    /// let doc = Document::new("message")?;
+    /// doc.root().insert_document_ns(roster.find_tag("r:query"))?;
+    ///
+    /// assert_eq!(
+    ///     doc.to_string(),
+    ///     "<message><r:query xmlns:r=\"jabber:iq:roster\"><r:item jid=\"a@b\"/></r:query></message>"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn insert_document_ns<'b>(self, cursor: Cursor<'b>) -> Result<Cursor<'a>, ParseError> {
+        let parent = self.insert_document(cursor.clone())?;
+        if !cursor.is_tag() {
+            return Ok(parent);
+        }
+        let top = parent.clone().last_child();
+
+        let mut prefixes: Vec<String> = Vec::new();
+        for tag in cursor.clone().descendant_or_self() {
+            if !tag.is_tag() {
+                continue;
+            }
+            prefixes.push(split_name(tag.name()).0.to_string());
+            for (name, _) in tag.attributes() {
+                if declared_prefix(name).is_none()
+                    && let Some((prefix, _)) = name.split_once(':')
+                {
+                    prefixes.push(prefix.to_string());
+                }
+            }
+        }
+        prefixes.sort_unstable();
+        prefixes.dedup();
+
+        for prefix in prefixes.iter().map(String::as_str) {
+            if prefix == "xml" || prefix == "xmlns" {
+                continue;
+            }
+            let name = declaration_name(prefix);
+            if cursor.attribute(&name).is_some() {
+                // Already declared on the copied element
+                continue;
+            }
+            let source = cursor.lookup_namespace(prefix);
+            if source == top.lookup_namespace(prefix) {
+                continue;
+            }
+            match source {
+                Some(uri) => {
+                    top.insert_attribute(&name, uri)?;
+                }
+                None if prefix.is_empty() => {
+                    top.insert_attribute(&name, "")?;
+                }
+                None => (),
+            }
+        }
+        Ok(parent)
+    }
+}
+
+impl Document {
+    pub fn find_tag_ns<'a>(&'a self, namespace_uri: &str, local_name: &str) -> Cursor<'a> {
+        self.root().find_tag_ns(namespace_uri, local_name)
+    }
+}
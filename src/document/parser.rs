@@ -8,14 +8,21 @@
 ** the License, or (at your option) any later version.
 */
 
+use std::io::Read;
+
 use crate::Location;
 use crate::ParseError;
-use crate::SaxElements;
+use crate::ParseErrorKind;
+use crate::ParserLimits;
+use crate::ReadError;
+use crate::SaxElement;
 use crate::SaxParser;
+use crate::parser::DEFAULT_READ_BUFFER_SIZE;
+use crate::parser::read_some;
 
 use super::Document;
 use super::DocumentBuilder;
-use super::error::description;
+use super::lossless::SourceRecorder;
 
 /// A DOM (Document Object Model) parser.
 ///
@@ -55,6 +62,9 @@ use super::error::description;
 pub struct DocumentParser {
     builder: DocumentBuilder,
     parser: SaxParser,
+    open_tags: Vec<Location>,
+    recorder: Option<SourceRecorder>,
+    diagnostics: Vec<ParseError>,
 }
 
 impl DocumentParser {
@@ -63,6 +73,9 @@ impl DocumentParser {
         DocumentParser {
             builder: DocumentBuilder::new(),
             parser: SaxParser::new(),
+            open_tags: Vec::new(),
+            recorder: None,
+            diagnostics: Vec::new(),
         }
     }
 
@@ -76,41 +89,195 @@ impl DocumentParser {
         DocumentParser {
             builder: DocumentBuilder::with_size_hint(size_hint),
             parser: SaxParser::new(),
+            open_tags: Vec::new(),
+            recorder: None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Creates a new `DocumentParser` which enforces the given resource limits.
+    ///
+    /// Limits are applied by the underlying [SaxParser], and they are
+    /// kept when the parser is reused via [take_document](DocumentParser::take_document).
+    pub fn with_limits(limits: ParserLimits) -> DocumentParser {
+        DocumentParser {
+            builder: DocumentBuilder::new(),
+            parser: SaxParser::with_limits(limits),
+            open_tags: Vec::new(),
+            recorder: None,
+            diagnostics: Vec::new(),
         }
     }
 
+    /// Enables or disables the lossless mode.
+    ///
+    /// In the lossless mode, the original XML text is kept in the
+    /// document along with the source ranges of the elements, so the
+    /// document can be written back with the
+    /// [to_lossless_string](Document::to_lossless_string) method
+    /// without losing the formatting, comments, and the prolog. This
+    /// needs additional memory for a copy of the whole text.
+    ///
+    /// The mode must be set before parsing a document, and it is kept
+    /// when the parser is reused via [take_document](DocumentParser::take_document).
+    pub fn set_lossless(&mut self, lossless: bool) {
+        self.recorder = if lossless {
+            Some(SourceRecorder::new())
+        } else {
+            None
+        };
+    }
+
+    /// Returns true if the lossless mode is enabled.
+    pub fn lossless(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Enables or disables the error recovery mode.
+    ///
+    /// In the recovery mode, the syntax errors are recorded as
+    /// diagnostics as described in [SaxParser::set_recovery], and the
+    /// parser checks the tag names as in [SaxParser::set_strict]. A
+    /// mismatched end tag closes the open tags up to the nearest one
+    /// with the same name, or it is skipped if there is no such tag.
+    /// The mismatches are recorded as diagnostics too, so the parsing
+    /// continues and a document is built from the rest of the input.
+    ///
+    /// Errors found when finishing the document, resource limit and
+    /// memory errors are always returned. The mode is kept when the
+    /// parser is reused via [take_document](DocumentParser::take_document).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use iks::{DocumentParser, ParseError};
+    /// # fn main() -> Result<(), ParseError> {
+    ///
+    /// let mut parser = DocumentParser::new();
+    /// parser.set_recovery(true);
+    /// parser.parse_bytes(b"<a><b><c></b><d/></a>")?;
+    /// let document = parser.take_document()?;
+    ///
+    /// assert_eq!(document.to_string(), "<a><b><c/></b><d/></a>");
+    /// assert_eq!(parser.diagnostics().len(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_recovery(&mut self, recovery: bool) {
+        self.parser.set_recovery(recovery);
+        self.parser.set_strict(recovery);
+        self.builder.set_recovery(recovery);
+    }
+
+    /// Returns true if the error recovery mode is enabled.
+    pub fn recovery(&self) -> bool {
+        self.parser.recovery()
+    }
+
+    /// Returns the errors recorded in the recovery mode.
+    ///
+    /// Diagnostics are kept when the parser is reused, until they are
+    /// taken with the [take_diagnostics](DocumentParser::take_diagnostics)
+    /// method.
+    pub fn diagnostics(&self) -> &[ParseError] {
+        &self.diagnostics
+    }
+
+    /// Returns the recorded errors and clears them from the parser.
+    pub fn take_diagnostics(&mut self) -> Vec<ParseError> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
     /// Parses the given XML bytes.
     ///
     /// This method can be called multiple times to parse the entire
     /// XML text in chunks.
     pub fn parse_bytes(&mut self, bytes: &[u8]) -> Result<(), ParseError> {
-        let mut elements = SaxElements::new(&mut self.parser, bytes);
-        loop {
-            match elements.next() {
-                Some(Ok(element)) => {
-                    self.builder.append_element(&element)?;
+        let result = self.parse_elements(bytes);
+        let mut diagnostics = self.parser.take_diagnostics();
+        if !diagnostics.is_empty() {
+            if self.diagnostics.try_reserve(diagnostics.len()).is_err() {
+                return Err(ParseError::NoMemory);
+            }
+            self.diagnostics.append(&mut diagnostics);
+        }
+        result
+    }
+
+    fn parse_elements(&mut self, bytes: &[u8]) -> Result<(), ParseError> {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.push_bytes(bytes)?;
+        }
+        let mut pos = 0;
+        while let Some((element, size, span)) = self.parser.parse_bytes_spanned(&bytes[pos..])? {
+            pos += size;
+            if let Some(recorder) = &mut self.recorder {
+                recorder.record(&element, &span)?;
+            }
+            let is_start_tag = matches!(element, SaxElement::StartTag(_));
+            let is_end_tag = matches!(element, SaxElement::EndTag(_) | SaxElement::StartTagEmpty);
+            if let Err(err) = self.builder.append_element(&element) {
+                if self.parser.recovery()
+                    && matches!(err.kind(), Some(ParseErrorKind::TagMismatch(_)))
+                {
+                    // Already recorded by the strict parser, and the
+                    // builder has closed the same tags
+                    self.open_tags.truncate(self.builder.depth());
+                    continue;
                 }
-                Some(Err(err)) => return Err(err),
-                None => {
-                    break;
+                return Err(self.locate_error(err));
+            }
+            if is_start_tag {
+                // Start locations are only needed for the tag mismatch errors
+                if self.open_tags.try_reserve(1).is_err() {
+                    return Err(ParseError::NoMemory);
                 }
+                self.open_tags.push(self.parser.location());
+            } else if is_end_tag {
+                self.open_tags.pop();
             }
         }
         Ok(())
     }
 
+    /// Parses all of the XML bytes from the given reader.
+    ///
+    /// This method reads the input in chunks until the end of the input
+    /// is reached. Errors from the reader are returned separately from
+    /// the parse errors. Use the [with_size_hint](DocumentParser::with_size_hint)
+    /// constructor when the input size is known in advance.
+    pub fn parse_reader<R: Read>(&mut self, mut reader: R) -> Result<(), ReadError> {
+        let mut buffer = vec![0u8; DEFAULT_READ_BUFFER_SIZE];
+        loop {
+            let len = read_some(&mut reader, &mut buffer)?;
+            if len == 0 {
+                return Ok(());
+            }
+            self.parse_bytes(&buffer[..len])?;
+        }
+    }
+
+    fn locate_error(&self, err: ParseError) -> ParseError {
+        let location = self.parser.location();
+        let mut err = err.at(location);
+        if let ParseError::BadXml {
+            kind: ParseErrorKind::TagMismatch(mismatch),
+            ..
+        } = &mut err
+        {
+            mismatch.open_location = self.open_tags.last().copied();
+            mismatch.close_location = Some(location);
+        }
+        err
+    }
+
     /// Finishes parsing and returns the document tree.
     ///
     /// This method applies some final checks like if there is any
     /// unfinished element or the document does indeed contain a
     /// root element, so it might return an error.
     pub fn into_document(mut self) -> Result<Document, ParseError> {
-        self.parser.parse_finish()?;
-        let doc = self.builder.take();
-        match doc {
-            None => Err(ParseError::BadXml(description::NO_DOCUMENT)),
-            Some(doc) => Ok(doc),
-        }
+        self.take_document()
     }
 
     /// Finishes parsing and returns the document tree.
@@ -126,9 +293,20 @@ impl DocumentParser {
         self.parser.parse_finish()?;
         let doc = self.builder.take();
         self.parser.reset();
+        self.open_tags.clear();
         match doc {
-            None => Err(ParseError::BadXml(description::NO_DOCUMENT)),
-            Some(doc) => Ok(doc),
+            None => {
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.clear();
+                }
+                Err(ParseErrorKind::NoDocument.into())
+            }
+            Some(mut doc) => {
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.attach(&mut doc)?;
+                }
+                Ok(doc)
+            }
         }
     }
 
@@ -0,0 +1,341 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use std::ptr::null_mut;
+
+use crate::ParseError;
+use crate::ParseErrorKind;
+
+use super::ArenaExt;
+use super::Cursor;
+use super::Node;
+use super::NodePayload;
+use super::Tag;
+use super::compact::node_bytes;
+use super::compact::subtree_bytes;
+use super::lossless::touch;
+
+// Returns the tag of a node which can have children
+unsafe fn parent_tag(node: *mut Node) -> Result<*mut Tag, ParseError> {
+    unsafe {
+        match (*node).payload {
+            NodePayload::Tag(tag) => Ok(tag),
+            NodePayload::CData(_) => Err(ParseErrorKind::CDataChildren.into()),
+        }
+    }
+}
+
+// Detaches the node and its subtree from the parent and siblings.
+pub(super) unsafe fn unlink(node: *mut Node) {
+    unsafe {
+        let parent = (*node).parent;
+        if parent.is_null() {
+            return;
+        }
+        touch(parent, false);
+        // Fix siblings
+        if !(*node).next.is_null() {
+            (*(*node).next).previous = (*node).previous;
+        }
+        if !(*node).previous.is_null() {
+            (*(*node).previous).next = (*node).next;
+        }
+        // Fix parent
+        if let NodePayload::Tag(tag) = (*parent).payload {
+            if (*tag).children == node {
+                (*tag).children = (*node).next;
+            }
+            if (*tag).last_child == node {
+                (*tag).last_child = (*node).previous;
+            }
+        }
+        // Fix self
+        (*node).parent = null_mut();
+        (*node).next = null_mut();
+        (*node).previous = null_mut();
+    }
+}
+
+// Links a detached node between the given siblings of the parent.
+unsafe fn link(node: *mut Node, parent: *mut Node, previous: *mut Node, next: *mut Node) {
+    unsafe {
+        let tag = match (*parent).payload {
+            NodePayload::Tag(tag) => tag,
+            NodePayload::CData(_) => {
+                // Callers check the parent type
+                unreachable!();
+            }
+        };
+        touch(parent, false);
+        (*node).parent = parent;
+        (*node).previous = previous;
+        (*node).next = next;
+        if previous.is_null() {
+            (*tag).children = node;
+        } else {
+            (*previous).next = node;
+        }
+        if next.is_null() {
+            (*tag).last_child = node;
+        } else {
+            (*next).previous = node;
+        }
+    }
+}
+
+// Checks if the node is the ancestor or the same node as the other one.
+unsafe fn contains(node: *mut Node, other: *mut Node) -> bool {
+    let mut current = other;
+    while !current.is_null() {
+        if current == node {
+            return true;
+        }
+        current = unsafe { (*current).parent };
+    }
+    false
+}
+
+impl<'a> Cursor<'a> {
+    // Common checks of the move methods, returns the moved and the target nodes.
+    fn move_guards(&self, target: &Cursor) -> Result<(*mut Node, *mut Node), ParseError> {
+        let node = self.get_node_ptr();
+        let target_node = target.get_node_ptr();
+        if node.is_null() || target_node.is_null() {
+            return Err(ParseErrorKind::NullCursorEdit.into());
+        }
+        if !std::ptr::eq(self.document, target.document) {
+            return Err(ParseErrorKind::DocumentMismatch.into());
+        }
+        unsafe {
+            if (*node).parent.is_null() {
+                return Err(ParseErrorKind::RootMove.into());
+            }
+            if node != target_node && contains(node, target_node) {
+                return Err(ParseErrorKind::MoveIntoSubtree.into());
+            }
+        }
+        Ok((node, target_node))
+    }
+
+    /// Moves the element and its subtree before the target element.
+    ///
+    /// Both elements must be in the same document. Other cursors
+    /// pointing into the moved subtree stay valid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use iks::{Document, ParseError};
+    /// # fn main() -> Result<(), ParseError> {
+    ///
+    /// let doc = Document::from_str("<a><b/><c/><d/></a>")?;
+    /// doc.find_tag("d").move_before(&doc.find_tag("b"))?;
+    /// doc.find_tag("b").move_after(&doc.find_tag("c"))?;
+    ///
+    /// assert_eq!(doc.to_string(), "<a><d/><c/><b/></a>");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn move_before(self, target: &Cursor) -> Result<Cursor<'a>, ParseError> {
+        let (node, target_node) = self.move_guards(target)?;
+        if node == target_node {
+            return Ok(self);
+        }
+        unsafe {
+            let parent = (*target_node).parent;
+            if parent.is_null() {
+                return Err(ParseErrorKind::RootSibling.into());
+            }
+            let was_live = self.document.is_live(node);
+            unlink(node);
+            link(node, parent, (*target_node).previous, target_node);
+            self.document.relocate(node, was_live);
+        }
+        Ok(self)
+    }
+
+    /// Moves the element and its subtree after the target element.
+    pub fn move_after(self, target: &Cursor) -> Result<Cursor<'a>, ParseError> {
+        let (node, target_node) = self.move_guards(target)?;
+        if node == target_node {
+            return Ok(self);
+        }
+        unsafe {
+            let parent = (*target_node).parent;
+            if parent.is_null() {
+                return Err(ParseErrorKind::RootSibling.into());
+            }
+            let was_live = self.document.is_live(node);
+            unlink(node);
+            link(node, parent, target_node, (*target_node).next);
+            self.document.relocate(node, was_live);
+        }
+        Ok(self)
+    }
+
+    /// Moves the element and its subtree to the end of the target's children.
+    pub fn move_into(self, target: &Cursor) -> Result<Cursor<'a>, ParseError> {
+        let (node, target_node) = self.move_guards(target)?;
+        if node == target_node {
+            return Err(ParseErrorKind::MoveIntoSubtree.into());
+        }
+        unsafe {
+            let tag = parent_tag(target_node)?;
+            let was_live = self.document.is_live(node);
+            unlink(node);
+            link(node, target_node, (*tag).last_child, null_mut());
+            self.document.relocate(node, was_live);
+        }
+        Ok(self)
+    }
+
+    /// Replaces the element with the given element, which is moved from its place.
+    ///
+    /// The replaced element is removed from the document. Returns the
+    /// cursor of the moved element.
+    pub fn replace_with(self, other: &Cursor) -> Result<Cursor<'a>, ParseError> {
+        let (moved, target_node) = other.move_guards(&self)?;
+        if moved == target_node {
+            return Ok(self);
+        }
+        unsafe {
+            let parent = (*target_node).parent;
+            if parent.is_null() {
+                return Err(ParseErrorKind::RootMove.into());
+            }
+            let was_live = self.document.is_live(moved);
+            unlink(moved);
+            let previous = (*target_node).previous;
+            let next = (*target_node).next;
+            // Moved element is no longer in the subtree of the replaced one
+            self.document
+                .abandon(target_node, subtree_bytes(target_node));
+            unlink(target_node);
+            link(moved, parent, previous, next);
+            self.document.relocate(moved, was_live);
+            Ok(Cursor::new(moved, self.document))
+        }
+    }
+
+    /// Wraps the element with a new tag, and returns the new tag.
+    pub fn wrap<'b>(self, tag_name: &'b str) -> Result<Cursor<'a>, ParseError> {
+        let node = self.get_node_ptr();
+        if node.is_null() {
+            return Err(ParseErrorKind::NullCursorEdit.into());
+        }
+        unsafe {
+            let parent = (*node).parent;
+            if parent.is_null() {
+                return Err(ParseErrorKind::RootMove.into());
+            }
+            let new_tag = self.document.arena.alloc_tag(tag_name)?.as_ptr();
+            let new_node = self
+                .document
+                .arena
+                .alloc_node(NodePayload::Tag(new_tag))?
+                .as_ptr();
+            let previous = (*node).previous;
+            let next = (*node).next;
+            unlink(node);
+            link(new_node, parent, previous, next);
+            link(node, new_node, null_mut(), null_mut());
+            Ok(Cursor::new(new_node, self.document))
+        }
+    }
+
+    /// Replaces the tag with its children, and returns the parent.
+    pub fn unwrap(self) -> Result<Cursor<'a>, ParseError> {
+        let node = self.get_node_ptr();
+        if node.is_null() {
+            return Err(ParseErrorKind::NullCursorEdit.into());
+        }
+        unsafe {
+            let parent = (*node).parent;
+            if parent.is_null() {
+                return Err(ParseErrorKind::RootMove.into());
+            }
+            let tag = parent_tag(node)?;
+            let mut previous = (*node).previous;
+            let next = (*node).next;
+            self.document.abandon(node, node_bytes(node));
+            unlink(node);
+            let mut child = (*tag).children;
+            while !child.is_null() {
+                let following = (*child).next;
+                unlink(child);
+                link(child, parent, previous, next);
+                previous = child;
+                child = following;
+            }
+            Ok(Cursor::new(parent, self.document))
+        }
+    }
+
+    /// Sorts the children of the element by the given key.
+    ///
+    /// The sort is stable, so the children with the same key keep
+    /// their order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use iks::{Document, ParseError};
+    /// # fn main() -> Result<(), ParseError> {
+    ///
+    /// let doc = Document::from_str("<r><i n='b'/><i n='c'/><i n='a'/></r>")?;
+    /// doc.root().sort_children_by(|child| child.attribute("n").map(str::to_string))?;
+    ///
+    /// assert_eq!(doc.to_string(), "<r><i n=\"a\"/><i n=\"b\"/><i n=\"c\"/></r>");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sort_children_by<K, F>(self, mut key: F) -> Result<Cursor<'a>, ParseError>
+    where
+        K: Ord,
+        F: FnMut(&Cursor<'a>) -> K,
+    {
+        let node = self.get_node_ptr();
+        if node.is_null() {
+            return Err(ParseErrorKind::NullCursorEdit.into());
+        }
+        let tag = unsafe { parent_tag(node)? };
+        let mut children: Vec<(K, *mut Node)> = Vec::new();
+        let mut child = self.clone().first_child();
+        while !child.is_null() {
+            if children.try_reserve(1).is_err() {
+                return Err(ParseError::NoMemory);
+            }
+            children.push((key(&child), child.get_node_ptr()));
+            child = child.next();
+        }
+        if children.len() < 2 {
+            return Ok(self);
+        }
+        children.sort_by(|a, b| a.0.cmp(&b.0));
+        unsafe {
+            touch(node, false);
+            let mut previous: *mut Node = null_mut();
+            for (_, child) in children {
+                (*child).previous = previous;
+                (*child).next = null_mut();
+                if previous.is_null() {
+                    (*tag).children = child;
+                } else {
+                    (*previous).next = child;
+                }
+                previous = child;
+            }
+            (*tag).last_child = previous;
+        }
+        Ok(self)
+    }
+}
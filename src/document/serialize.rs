@@ -0,0 +1,335 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use std::fmt::Write;
+
+use super::super::entities::escape_fmt;
+use super::Cursor;
+use super::Document;
+use super::NodePayload;
+use super::Tag;
+use super::VisitorStep;
+
+/// Indentation of the nested tags.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Indent {
+    /// Given number of space characters for each level.
+    Spaces(usize),
+    /// A tab character for each level.
+    Tab,
+}
+
+/// Line ending written between the indented lines.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Newline {
+    /// A single "\n" character.
+    Lf,
+    /// A "\r\n" pair.
+    CrLf,
+}
+
+/// Quote character of the attribute values.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Quote {
+    /// Double quote character.
+    Double,
+    /// Single quote character.
+    Single,
+}
+
+/// Formatting options for serializing the documents.
+///
+/// The default value is the same compact format used by the
+/// [to_string](Cursor::to_string) method and the `Display` trait.
+///
+/// Indentation is only added between the tags whose parent has no
+/// character data children. Once a tag with text content is reached,
+/// the rest of its subtree is written inline, so the mixed content
+/// text is never changed.
+///
+/// # Examples
+///
+/// ```
+/// use std::str::FromStr;
+/// use iks::{Document, ParseError, SerializeOptions};
+/// # fn main() -> Result<(), ParseError> {
+///
+/// let doc = Document::from_str("<a><b x='1'/><c>some <i>text</i></c></a>")?;
+/// let options = SerializeOptions::pretty();
+///
+/// assert_eq!(
+///     doc.root().to_string_with(&options),
+///     "<a>\n  <b x=\"1\"/>\n  <c>some <i>text</i></c>\n</a>"
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SerializeOptions {
+    /// Indentation of the nested tags, or None for the compact output.
+    pub indent: Option<Indent>,
+
+    /// Line ending written before the indented tags.
+    pub newline: Newline,
+
+    /// Quote character of the attribute values.
+    pub quote: Quote,
+
+    /// Writes the tags without children as `<a/>` instead of `<a></a>`.
+    pub self_closing: bool,
+
+    /// Writes an `<?xml version="1.0" encoding="UTF-8"?>` header first.
+    pub declaration: bool,
+}
+
+impl SerializeOptions {
+    /// Returns the options for the compact output without any extra whitespace.
+    pub fn compact() -> Self {
+        SerializeOptions {
+            indent: None,
+            newline: Newline::Lf,
+            quote: Quote::Double,
+            self_closing: true,
+            declaration: false,
+        }
+    }
+
+    /// Returns the options for the human readable output indented with two spaces.
+    pub fn pretty() -> Self {
+        SerializeOptions {
+            indent: Some(Indent::Spaces(2)),
+            ..SerializeOptions::compact()
+        }
+    }
+}
+
+impl Default for SerializeOptions {
+    fn default() -> Self {
+        SerializeOptions::compact()
+    }
+}
+
+// Size of the output chunks written to the io writers
+const CHUNK_SIZE: usize = 4096;
+
+// Adapts an io writer to the fmt::Write trait, keeping the actual error.
+//
+// Small pieces of the output are collected into fixed size chunks, so
+// the writer is not called for each tag name and attribute.
+pub(super) struct IoWriter<W: std::io::Write> {
+    writer: W,
+    chunk: Vec<u8>,
+    error: Option<std::io::Error>,
+}
+
+impl<W: std::io::Write> IoWriter<W> {
+    pub(super) fn write_with<F>(writer: W, write_fn: F) -> std::io::Result<()>
+    where
+        F: FnOnce(&mut Self) -> std::fmt::Result,
+    {
+        let mut out = IoWriter {
+            writer,
+            chunk: Vec::with_capacity(CHUNK_SIZE),
+            error: None,
+        };
+        if write_fn(&mut out).is_err() || out.flush_chunk().is_err() {
+            return Err(out
+                .error
+                .unwrap_or_else(|| std::io::Error::other("formatting error")));
+        }
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> std::fmt::Result {
+        self.writer.write_all(bytes).map_err(|err| {
+            self.error = Some(err);
+            std::fmt::Error
+        })
+    }
+
+    fn flush_chunk(&mut self) -> std::fmt::Result {
+        if !self.chunk.is_empty() {
+            let chunk = std::mem::take(&mut self.chunk);
+            let result = self.write_bytes(&chunk);
+            self.chunk = chunk;
+            self.chunk.clear();
+            result?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: std::io::Write> Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        if self.chunk.len() + s.len() > CHUNK_SIZE {
+            self.flush_chunk()?;
+            if s.len() >= CHUNK_SIZE {
+                return self.write_bytes(s.as_bytes());
+            }
+        }
+        self.chunk.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+}
+
+fn has_only_tags(tag: &Tag) -> bool {
+    let mut child = tag.children;
+    unsafe {
+        while !child.is_null() {
+            if let NodePayload::CData(_) = (*child).payload {
+                return false;
+            }
+            child = (*child).next;
+        }
+    }
+    true
+}
+
+impl SerializeOptions {
+    fn write_newline<W: Write>(&self, level: usize, out: &mut W) -> std::fmt::Result {
+        out.write_str(match self.newline {
+            Newline::Lf => "\n",
+            Newline::CrLf => "\r\n",
+        })?;
+        for _ in 0..level {
+            match self.indent {
+                Some(Indent::Spaces(width)) => {
+                    for _ in 0..width {
+                        out.write_char(' ')?;
+                    }
+                }
+                Some(Indent::Tab) => out.write_char('\t')?,
+                None => (),
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn write_cursor<W: Write>(&self, cursor: &Cursor, out: &mut W) -> std::fmt::Result {
+        if cursor.is_null() {
+            return Ok(());
+        }
+        let quote = match self.quote {
+            Quote::Double => "\"",
+            Quote::Single => "'",
+        };
+        if self.declaration {
+            write!(
+                out,
+                "<?xml version={quote}1.0{quote} encoding={quote}UTF-8{quote}?>"
+            )?;
+        }
+        // Indentation of the children of each open tag, only kept
+        // when indenting so that compact output does not allocate
+        let pretty = self.indent.is_some();
+        let mut indented: Vec<bool> = Vec::new();
+        let mut first = !self.declaration;
+        let mut visitor = cursor.visitor();
+        while let Some(step) = visitor.next() {
+            match step {
+                VisitorStep::StartTag(tag) => {
+                    let indent = pretty && indented.last().copied().unwrap_or(true);
+                    if indent && !first {
+                        self.write_newline(indented.len(), out)?;
+                    }
+                    out.write_char('<')?;
+                    out.write_str(tag.as_str())?;
+                    let mut attr = tag.attributes;
+                    while !attr.is_null() {
+                        unsafe {
+                            out.write_char(' ')?;
+                            out.write_str((*attr).name_as_str())?;
+                            out.write_char('=')?;
+                            out.write_str(quote)?;
+                            escape_fmt((*attr).value_as_str(), out)?;
+                            out.write_str(quote)?;
+                            attr = (*attr).next;
+                        }
+                    }
+                    if !tag.children.is_null() {
+                        out.write_char('>')?;
+                        if pretty {
+                            indented.push(indent && has_only_tags(tag));
+                        }
+                    } else if self.self_closing {
+                        out.write_str("/>")?;
+                    } else {
+                        out.write_str("></")?;
+                        out.write_str(tag.as_str())?;
+                        out.write_char('>')?;
+                    }
+                }
+                VisitorStep::EndTag(tag) => {
+                    if !tag.children.is_null() {
+                        if pretty && indented.pop().unwrap_or(false) {
+                            self.write_newline(indented.len(), out)?;
+                        }
+                        out.write_str("</")?;
+                        out.write_str(tag.as_str())?;
+                        out.write_char('>')?;
+                    }
+                }
+                VisitorStep::CData(cdata) => {
+                    escape_fmt(cdata.as_str(), out)?;
+                }
+            }
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+impl Cursor<'_> {
+    /// Returns the XML text of the element with the given formatting options.
+    pub fn to_string_with(&self, options: &SerializeOptions) -> String {
+        let mut buf = String::with_capacity(self.str_size());
+        // Writing into a String cannot fail
+        let _ = options.write_cursor(self, &mut buf);
+        buf
+    }
+
+    /// Writes the XML text of the element with the given formatting options.
+    ///
+    /// The escaped text is streamed to the writer in fixed size chunks,
+    /// without building the whole text in the memory first.
+    pub fn write_to<W: std::io::Write>(
+        &self,
+        writer: W,
+        options: &SerializeOptions,
+    ) -> std::io::Result<()> {
+        IoWriter::write_with(writer, |out| options.write_cursor(self, out))
+    }
+
+    /// Writes the XML text of the element into a [fmt::Write](std::fmt::Write) sink.
+    pub fn format_to<W: Write>(&self, out: &mut W, options: &SerializeOptions) -> std::fmt::Result {
+        options.write_cursor(self, out)
+    }
+}
+
+impl Document {
+    /// Returns the XML text of the document with the given formatting options.
+    pub fn to_string_with(&self, options: &SerializeOptions) -> String {
+        self.root().to_string_with(options)
+    }
+
+    /// Writes the XML text of the document with the given formatting options.
+    pub fn write_to<W: std::io::Write>(
+        &self,
+        writer: W,
+        options: &SerializeOptions,
+    ) -> std::io::Result<()> {
+        self.root().write_to(writer, options)
+    }
+
+    /// Writes the XML text of the document into a [fmt::Write](std::fmt::Write) sink.
+    pub fn format_to<W: Write>(&self, out: &mut W, options: &SerializeOptions) -> std::fmt::Result {
+        self.root().format_to(out, options)
+    }
+}
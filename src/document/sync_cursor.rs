@@ -8,8 +8,10 @@
 ** the License, or (at your option) any later version.
 */
 
+use std::fmt::Display;
 use std::marker::Send;
 use std::ptr::null_mut;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
 
@@ -20,6 +22,8 @@ use super::sync_iterators::SyncChildren;
 use crate::Cursor;
 use crate::Document;
 use crate::ParseError;
+use crate::ParseErrorKind;
+use crate::ValueError;
 
 pub struct SyncAttributes {
     sync_cursor: SyncCursor,
@@ -75,7 +79,7 @@ macro_rules! tag_edit_method {
         pub fn $method(mut self, tag_name: &str) -> Result<Self, ParseError> {
             {
                 let document = self.document.lock().unwrap();
-                let current = Cursor::new(self.node, &document.arena);
+                let current = Cursor::new(self.node, &document);
                 let new = current.$method(tag_name)?;
                 self.node = new.get_node_ptr();
             }
@@ -89,7 +93,7 @@ macro_rules! cdata_edit_method {
         pub fn $method(mut self, cdata: &str) -> Result<Self, ParseError> {
             {
                 let document = self.document.lock().unwrap();
-                let current = Cursor::new(self.node, &document.arena);
+                let current = Cursor::new(self.node, &document);
                 let new = current.$method(cdata)?;
                 self.node = new.get_node_ptr();
             }
@@ -98,12 +102,30 @@ macro_rules! cdata_edit_method {
     };
 }
 
+macro_rules! move_method {
+    ($method:ident) => {
+        pub fn $method(mut self, target: &SyncCursor) -> Result<Self, ParseError> {
+            if !Arc::ptr_eq(&self.document, &target.document) {
+                return Err(ParseErrorKind::DocumentMismatch.into());
+            }
+            {
+                let document = self.document.lock().unwrap();
+                let current = Cursor::new(self.node, &document);
+                let target = Cursor::new(target.node, &document);
+                let new = current.$method(&target)?;
+                self.node = new.get_node_ptr();
+            }
+            Ok(self)
+        }
+    };
+}
+
 macro_rules! navigation_method {
     ($method:ident) => {
         pub fn $method(mut self) -> Self {
             {
                 let document = self.document.lock().unwrap();
-                let new = Cursor::new(self.node, &document.arena).$method();
+                let new = Cursor::new(self.node, &document).$method();
                 self.node = new.get_node_ptr();
             }
             self
@@ -146,7 +168,7 @@ impl SyncCursor {
     ) -> Result<Self, ParseError> {
         {
             let document = self.document.lock().unwrap();
-            let current = Cursor::new(self.node, &document.arena);
+            let current = Cursor::new(self.node, &document);
             let new = current.insert_attribute(name, value)?;
             self.node = new.get_node_ptr();
         }
@@ -166,13 +188,153 @@ impl SyncCursor {
     ) -> Result<Self, ParseError> {
         {
             let document = self.document.lock().unwrap();
-            let current = Cursor::new(self.node, &document.arena);
+            let current = Cursor::new(self.node, &document);
             let new = current.set_attribute(name, value)?;
             self.node = new.get_node_ptr();
         }
         Ok(self)
     }
 
+    /// Removes an attribute of the current tag element.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is poisoned.
+    ///
+    pub fn remove_attribute(mut self, name: &str) -> Result<Self, ParseError> {
+        {
+            let document = self.document.lock().unwrap();
+            let current = Cursor::new(self.node, &document);
+            let new = current.remove_attribute(name)?;
+            self.node = new.get_node_ptr();
+        }
+        Ok(self)
+    }
+
+    tag_edit_method!(rename);
+    cdata_edit_method!(set_cdata);
+    cdata_edit_method!(set_text);
+
+    /// Removes all children of the current tag element.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is poisoned.
+    ///
+    pub fn clear_children(mut self) -> Result<Self, ParseError> {
+        {
+            let document = self.document.lock().unwrap();
+            let new = Cursor::new(self.node, &document).clear_children()?;
+            self.node = new.get_node_ptr();
+        }
+        Ok(self)
+    }
+
+    /// Sets the attribute to the string representation of the value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is poisoned.
+    ///
+    pub fn set_attribute_value<T: Display>(
+        mut self,
+        name: &str,
+        value: T,
+    ) -> Result<Self, ParseError> {
+        {
+            let document = self.document.lock().unwrap();
+            let current = Cursor::new(self.node, &document);
+            let new = current.set_attribute_value(name, value)?;
+            self.node = new.get_node_ptr();
+        }
+        Ok(self)
+    }
+
+    //
+    // Restructure
+    //
+
+    move_method!(move_before);
+    move_method!(move_after);
+    move_method!(move_into);
+    move_method!(replace_with);
+    tag_edit_method!(wrap);
+
+    /// Replaces the tag with its children, and moves to the parent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is poisoned.
+    ///
+    pub fn unwrap(mut self) -> Result<Self, ParseError> {
+        {
+            let document = self.document.lock().unwrap();
+            let new = Cursor::new(self.node, &document).unwrap()?;
+            self.node = new.get_node_ptr();
+        }
+        Ok(self)
+    }
+
+    /// Sorts the children of the element by the given key.
+    ///
+    /// The key function is called while the document lock is held.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is poisoned. A panic in the key function
+    /// poisons the mutex. Using another [SyncCursor] of the same
+    /// document inside the key function deadlocks.
+    ///
+    pub fn sort_children_by<K, F>(self, key: F) -> Result<Self, ParseError>
+    where
+        K: Ord,
+        F: FnMut(&Cursor) -> K,
+    {
+        {
+            let document = self.document.lock().unwrap();
+            Cursor::new(self.node, &document).sort_children_by(key)?;
+        }
+        Ok(self)
+    }
+
+    /// Compacts the document, and moves to the same element in the new arena.
+    ///
+    /// See [Document::compact](crate::Document::compact) for the details.
+    /// The cursor becomes null if its element was removed from the
+    /// document.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::BadXml` if there are other cursors or
+    /// iterators sharing the document, since their elements and
+    /// returned strings would be freed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is poisoned.
+    ///
+    pub fn compact(mut self) -> Result<Self, ParseError> {
+        let Some(document) = Arc::get_mut(&mut self.document) else {
+            return Err(ParseErrorKind::DocumentShared.into());
+        };
+        let document = document.get_mut().unwrap();
+        self.node = document.compact_with(self.node)?;
+        Ok(self)
+    }
+
+    /// Returns the arena bytes used by the removed elements.
+    ///
+    /// See [Document::wasted_bytes] for the details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is poisoned.
+    ///
+    pub fn wasted_bytes(&self) -> usize {
+        let document = self.document.lock().unwrap();
+        document.wasted_bytes()
+    }
+
     /// Removes the tag element from the document.
     ///
     /// # Panics
@@ -181,7 +343,7 @@ impl SyncCursor {
     ///
     pub fn remove(self) {
         let document = self.document.lock().unwrap();
-        let current = Cursor::new(self.node, &document.arena);
+        let current = Cursor::new(self.node, &document);
         current.remove();
     }
 
@@ -220,7 +382,7 @@ impl SyncCursor {
     pub fn find_tag(mut self, tag_name: &str) -> Self {
         {
             let document = self.document.lock().unwrap();
-            let next = Cursor::new(self.node, &document.arena).find_tag(tag_name);
+            let next = Cursor::new(self.node, &document).find_tag(tag_name);
             self.node = next.get_node_ptr();
         }
         self
@@ -235,8 +397,7 @@ impl SyncCursor {
     pub fn find_tag_with_attribute(mut self, attribute_name: &str) -> Self {
         {
             let document = self.document.lock().unwrap();
-            let next =
-                Cursor::new(self.node, &document.arena).find_tag_with_attribute(attribute_name);
+            let next = Cursor::new(self.node, &document).find_tag_with_attribute(attribute_name);
             self.node = next.get_node_ptr();
         }
         self
@@ -251,7 +412,7 @@ impl SyncCursor {
     pub fn find_tag_with_attribute_value(mut self, attribute_name: &str, value: &str) -> Self {
         {
             let document = self.document.lock().unwrap();
-            let next = Cursor::new(self.node, &document.arena)
+            let next = Cursor::new(self.node, &document)
                 .find_tag_with_attribute_value(attribute_name, value);
             self.node = next.get_node_ptr();
         }
@@ -286,19 +447,26 @@ impl SyncCursor {
     ///
     pub fn has_children(&self) -> bool {
         let document = self.document.lock().unwrap();
-        Cursor::new(self.node, &document.arena).has_children()
+        Cursor::new(self.node, &document).has_children()
     }
 
+    /// Returns the name of the current tag element.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is poisoned.
+    ///
     pub fn name(&self) -> &str {
         if self.node.is_null() {
             return "";
         }
+        let _document = self.document.lock().unwrap();
         // SAFETY:
         // Invariants:
         // 1. Returned reference must not outlive the pointed memory.
         // 2. Pointed string must be valid UTF-8.
         // 3. Pointed string must not change while the reference is alive.
-        // 4. Dereferenced members must be immutable as there is no lock taken.
+        // 4. Dereferenced members must not be changed while they are read.
         // Guards:
         // a. Elided lifetime ensures the liveness of self while reference is alive.
         // b. While self is alive, Arc keeps a reference count on the backing Arena (1).
@@ -306,8 +474,8 @@ impl SyncCursor {
         // c.1. SaxParser validates input bytes.
         // c.2. Edit methods only accept &str.
         // d. Arena strings are immutable, never moved or changed until Arena is dropped (3).
-        // e. Only the navigational members of a tag node are mutated after the construction,
-        // and they are not accessed here (4).
+        // e. Name of a tag node can be changed by the rename method, so it is read
+        // under the lock (4). The old name stays in the arena (3).
         unsafe {
             match (*self.node).payload {
                 NodePayload::CData(_) => "",
@@ -364,6 +532,91 @@ impl SyncCursor {
         }
     }
 
+    /// Returns the concatenated character data of the element and its descendants.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is poisoned.
+    ///
+    pub fn text(&self) -> String {
+        let document = self.document.lock().unwrap();
+        Cursor::new(self.node, &document).text()
+    }
+
+    /// Returns the character data with the whitespace normalized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is poisoned.
+    ///
+    pub fn normalized_text(&self) -> String {
+        let document = self.document.lock().unwrap();
+        Cursor::new(self.node, &document).normalized_text()
+    }
+
+    /// Returns the character data of the first child tag with the given name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is poisoned.
+    ///
+    pub fn child_text(&self, tag_name: &str) -> Option<String> {
+        let document = self.document.lock().unwrap();
+        Cursor::new(self.node, &document).child_text(tag_name)
+    }
+
+    /// Returns the value of the given attribute converted to the type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is poisoned.
+    ///
+    pub fn attribute_as<T>(&self, name: &str) -> Result<Option<T>, ValueError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let document = self.document.lock().unwrap();
+        Cursor::new(self.node, &document).attribute_as(name)
+    }
+
+    /// Returns the value of the given attribute as an XML Schema boolean.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is poisoned.
+    ///
+    pub fn attribute_bool(&self, name: &str) -> Result<Option<bool>, ValueError> {
+        let document = self.document.lock().unwrap();
+        Cursor::new(self.node, &document).attribute_bool(name)
+    }
+
+    /// Returns the character data of the element converted to the type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is poisoned.
+    ///
+    pub fn text_as<T>(&self) -> Result<T, ValueError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let document = self.document.lock().unwrap();
+        Cursor::new(self.node, &document).text_as()
+    }
+
+    /// Returns the character data of the element as an XML Schema boolean.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is poisoned.
+    ///
+    pub fn text_bool(&self) -> Result<bool, ValueError> {
+        let document = self.document.lock().unwrap();
+        Cursor::new(self.node, &document).text_bool()
+    }
+
     /// Returns the currently pointer subdocument as a new Document.
     ///
     /// # Panics
@@ -372,7 +625,7 @@ impl SyncCursor {
     ///
     pub fn to_document(&self) -> Result<Self, ParseError> {
         let document = self.document.lock().unwrap();
-        let new_document = Cursor::new(self.node, &document.arena).to_document()?;
+        let new_document = Cursor::new(self.node, &document).to_document()?;
         Ok(SyncCursor::new(new_document))
     }
 
@@ -386,8 +639,8 @@ impl SyncCursor {
         {
             let self_document = self.document.lock().unwrap();
             let other_document = document.document.lock().unwrap();
-            let new_document = Cursor::new(self.node, &self_document.arena)
-                .insert_document(Cursor::new(document.node, &other_document.arena))?;
+            let new_document = Cursor::new(self.node, &self_document)
+                .insert_document(Cursor::new(document.node, &other_document))?;
             self.node = new_document.node;
         }
         Ok(self)
@@ -401,7 +654,7 @@ impl SyncCursor {
     ///
     pub fn str_size(&self) -> usize {
         let document = self.document.lock().unwrap();
-        Cursor::new(self.node, &document.arena).str_size()
+        Cursor::new(self.node, &document).str_size()
     }
 
     /// Returns the XML string representation.
@@ -416,7 +669,7 @@ impl SyncCursor {
     )]
     pub fn to_string(&self) -> String {
         let document = self.document.lock().unwrap();
-        Cursor::new(self.node, &document.arena).to_string()
+        Cursor::new(self.node, &document).to_string()
     }
 }
 
@@ -432,7 +685,7 @@ impl Clone for SyncCursor {
 impl std::fmt::Display for SyncCursor {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let document = self.document.lock().unwrap();
-        let cursor = Cursor::new(self.node, &document.arena);
+        let cursor = Cursor::new(self.node, &document);
         std::fmt::Display::fmt(&cursor, f)
     }
 }
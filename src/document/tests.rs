@@ -10,7 +10,12 @@
 
 use std::thread;
 
-use super::error::description::*;
+use crate::ParserLimits;
+use crate::SaxParser;
+use crate::TagMismatch;
+use crate::XML_NS;
+use crate::XMLNS_NS;
+
 use super::*;
 
 fn check_doc_xml(doc: &Document, expected: &str) {
@@ -60,16 +65,16 @@ fn attributes() {
     assert!(a.insert_attribute("i", "1").unwrap().is_tag());
     assert_eq!(
         a.insert_attribute("i", "1").unwrap_err(),
-        ParseError::BadXml(description::DUPLICATE_ATTRIBUTE)
+        ParseErrorKind::DuplicateAttribute.into()
     );
     assert!(a.insert_attribute("j", "2").unwrap().is_tag());
     assert_eq!(
         a.insert_attribute("i", "1").unwrap_err(),
-        ParseError::BadXml(description::DUPLICATE_ATTRIBUTE)
+        ParseErrorKind::DuplicateAttribute.into()
     );
     assert_eq!(
         a.insert_attribute("j", "1").unwrap_err(),
-        ParseError::BadXml(description::DUPLICATE_ATTRIBUTE)
+        ParseErrorKind::DuplicateAttribute.into()
     );
     let _ = doc
         .insert_tag("b")
@@ -368,23 +373,45 @@ fn cursor_tofrom_doc() {
     )
 }
 
+fn tag_mismatch(xml: &str) -> TagMismatch {
+    match Document::from_str(xml) {
+        Err(ParseError::BadXml {
+            kind: ParseErrorKind::TagMismatch(mismatch),
+            location,
+        }) => {
+            assert_eq!(location, mismatch.close_location);
+            *mismatch
+        }
+        result => unreachable!("expected tag mismatch, got {result:?}"),
+    }
+}
+
 #[test]
 fn bad_doc_parser() {
+    let mismatch = tag_mismatch("<a>lala</b>");
+    assert_eq!(mismatch.open_name, "a");
+    assert_eq!(mismatch.close_name, "b");
+    assert_eq!(mismatch.open_location.unwrap().bytes, 2);
+    assert_eq!(mismatch.close_location.unwrap().bytes, 10);
+
+    let mismatch = tag_mismatch("<a><b><c/></d></a>");
+    assert_eq!(mismatch.open_name, "b");
+    assert_eq!(mismatch.close_name, "d");
+    assert_eq!(mismatch.open_location.unwrap().bytes, 5);
+
+    let mismatch = tag_mismatch("<a><b><c/></b><d></d>\n<e></e2></a>");
+    assert_eq!(mismatch.open_name, "e");
+    assert_eq!(mismatch.close_name, "e2");
+    assert_eq!(mismatch.open_location.unwrap().lines, 1);
+    assert_eq!(mismatch.open_location.unwrap().column, 2);
+    assert_eq!(mismatch.close_location.unwrap().column, 7);
+
+    let err = Document::from_str("<a><b x=\"1\" y=\"2\" x=\"abc\"/></a>").unwrap_err();
+    assert_eq!(err.kind(), Some(&ParseErrorKind::DuplicateAttribute));
+    assert!(err.location().is_some());
     assert_eq!(
-        Document::from_str("<a>lala</b>").err(),
-        Some(ParseError::BadXml(TAG_MISMATCH))
-    );
-    assert_eq!(
-        Document::from_str("<a><b><c/></d></a>").err(),
-        Some(ParseError::BadXml(TAG_MISMATCH))
-    );
-    assert_eq!(
-        Document::from_str("<a><b><c/></b><d></d><e></e2></a>").err(),
-        Some(ParseError::BadXml(TAG_MISMATCH))
-    );
-    assert_eq!(
-        Document::from_str("<a><b x=\"1\" y=\"2\" x=\"abc\"/></a>").err(),
-        Some(ParseError::BadXml(DUPLICATE_ATTRIBUTE))
+        Document::from_str("").unwrap_err().kind(),
+        Some(&ParseErrorKind::DocNoContent)
     );
 }
 
@@ -437,3 +464,1015 @@ fn prints() {
     let s4 = format!("{}", c);
     assert!(s4.len() > 0);
 }
+
+#[test]
+fn doc_parser_limits() {
+    let limits = ParserLimits {
+        max_depth: 2,
+        ..ParserLimits::default()
+    };
+    let mut parser = DocumentParser::with_limits(limits);
+    assert_eq!(parser.parse_bytes(b"<a><b/><b>x</b></a>"), Ok(()));
+    let doc = parser.take_document().unwrap();
+    assert_eq!(doc.to_string(), "<a><b/><b>x</b></a>");
+    assert_eq!(
+        parser.parse_bytes(b"<a><b><c/></b></a>"),
+        Err(ParseError::TooDeep)
+    );
+}
+
+#[test]
+fn doc_readers() {
+    let xml = "<a><b>123</b><c x='1'/></a>";
+    let doc = Document::from_reader(xml.as_bytes()).unwrap();
+    check_doc_xml(&doc, "<a><b>123</b><c x=\"1\"/></a>");
+
+    let mut parser = DocumentParser::new();
+    parser.parse_reader(&b"<a><b>"[..]).unwrap();
+    parser.parse_reader(&b"x</b></a>"[..]).unwrap();
+    assert_eq!(
+        parser.take_document().unwrap().to_string(),
+        "<a><b>x</b></a>"
+    );
+
+    assert!(matches!(
+        Document::from_reader("<a></b>".as_bytes()),
+        Err(ReadError::Parse(ParseError::BadXml { .. }))
+    ));
+    assert!(matches!(
+        Document::from_file("/nonexistent/iks/file.xml"),
+        Err(ReadError::Io(_))
+    ));
+}
+
+#[test]
+fn builder_recovery() {
+    fn build(xml: &str) -> (String, usize) {
+        let mut parser = SaxParser::new();
+        parser.set_recovery(true);
+        let mut builder = DocumentBuilder::new();
+        builder.set_recovery(true);
+        let mut mismatches = 0;
+        let mut elements = parser.elements(xml.as_bytes());
+        while let Some(element) = elements.next() {
+            if let Err(err) = builder.append_element(&element.unwrap()) {
+                assert!(matches!(err.kind(), Some(ParseErrorKind::TagMismatch(_))));
+                mismatches += 1;
+            }
+        }
+        (builder.take().unwrap().to_string(), mismatches)
+    }
+
+    assert_eq!(
+        build("<a><b><c></b><d/></a>"),
+        ("<a><b><c/></b><d/></a>".to_string(), 1)
+    );
+    assert_eq!(build("<a><b></x></b></a>"), ("<a><b/></a>".to_string(), 1));
+}
+
+#[test]
+fn parser_recovery() {
+    let mut parser = DocumentParser::new();
+    parser.set_recovery(true);
+    assert!(parser.recovery());
+    parser
+        .parse_bytes(b"<a><b><c></b><d x='1' x='2'/></a>")
+        .unwrap();
+    let doc = parser.take_document().unwrap();
+    assert_eq!(doc.to_string(), "<a><b><c/></b><d x=\"1\"/></a>");
+    let diagnostics = parser.take_diagnostics();
+    assert_eq!(diagnostics.len(), 2);
+    let Some(ParseErrorKind::TagMismatch(mismatch)) = diagnostics[0].kind() else {
+        unreachable!("expected a tag mismatch");
+    };
+    assert_eq!(mismatch.open_name, "c");
+    assert_eq!(mismatch.close_name, "b");
+    assert_eq!(
+        diagnostics[1].kind(),
+        Some(&ParseErrorKind::DuplicateAttribute)
+    );
+
+    // Parser is reused with the same mode
+    for chunk in b"<a><b></x></b><c>text</a>".chunks(3) {
+        parser.parse_bytes(chunk).unwrap();
+    }
+    let doc = parser.take_document().unwrap();
+    assert_eq!(doc.to_string(), "<a><b/><c>text</c></a>");
+    assert_eq!(parser.diagnostics().len(), 2);
+
+    parser.set_recovery(false);
+    assert!(parser.parse_bytes(b"<a><b></a>").is_err());
+}
+
+#[test]
+fn doc_line_endings() {
+    let unix = Document::from_str("<a b='1\n2'>\n<c>x\ny</c>\n</a>").unwrap();
+    let windows = Document::from_str("<a b='1\r\n2'>\r\n<c>x\r\ny</c>\r\n</a>").unwrap();
+    assert_eq!(unix.to_string(), windows.to_string());
+    assert_eq!(windows.root().attribute("b"), Some("1 2"));
+}
+
+#[test]
+fn builder_text_origins() {
+    let xml = "<a>x&lt;<![CDATA[<b/>]]>&#65;</a>";
+    let mut parser = SaxParser::new();
+    parser.set_text_origins(true);
+    let mut builder = DocumentBuilder::new();
+    let mut elements = parser.elements(xml.as_bytes());
+    while let Some(element) = elements.next() {
+        builder.append_element(&element.unwrap()).unwrap();
+    }
+    let doc = builder.take().unwrap();
+    assert_eq!(doc.root().first_child().cdata(), "x<<b/>A");
+    assert_eq!(doc.to_string(), "<a>x&lt;&lt;b/&gt;A</a>");
+}
+
+fn parse_lossless(xml: &str) -> Document {
+    let mut parser = DocumentParser::new();
+    parser.set_lossless(true);
+    // Chunked parsing must give the same source ranges
+    for chunk in xml.as_bytes().chunks(3) {
+        parser.parse_bytes(chunk).unwrap();
+    }
+    parser.into_document().unwrap()
+}
+
+#[test]
+fn lossless_round_trip() {
+    let xmls = [
+        "<a/>",
+        "\u{feff}<?xml version='1.0' encoding='UTF-8'?>\n<!-- head -->\n<a\n  x = 'single'\n  y=\"double\" ></a >\n<!-- tail -->\n",
+        "<a>text &amp; &#65; <![CDATA[<raw>]]><!-- c --> more<b/><c  ></c\t></a>",
+        "<config>\r\n  <item key='a'/>\r\n  <item key='b'>value</item>\r\n</config>",
+    ];
+    for xml in xmls {
+        let doc = parse_lossless(xml);
+        assert_eq!(doc.to_lossless_string(), xml);
+    }
+
+    // Not parsed in lossless mode
+    let doc = Document::from_str("<a  x='1'><!-- c --><b/></a>").unwrap();
+    assert_eq!(doc.to_lossless_string(), doc.to_string());
+}
+
+#[test]
+fn lossless_edits() {
+    let xml = "<?xml version='1.0'?>\n<a>\n  <!-- first -->\n  <b x = '1' />\n  <c y='2'>  <d/>\n  </c>\n  <e>old &amp; text</e>\n</a>\n";
+
+    let doc = parse_lossless(xml);
+    doc.find_tag("b").set_attribute("x", Some("<2>")).unwrap();
+    assert_eq!(
+        doc.to_lossless_string(),
+        "<?xml version='1.0'?>\n<a>\n  <!-- first -->\n  <b x=\"&lt;2&gt;\"/>\n  <c y='2'>  <d/>\n  </c>\n  <e>old &amp; text</e>\n</a>\n"
+    );
+
+    let doc = parse_lossless(xml);
+    doc.find_tag("e")
+        .first_child()
+        .append_cdata(" more")
+        .unwrap();
+    doc.find_tag("c").find_tag("d").insert_tag("f").unwrap();
+    assert_eq!(
+        doc.to_lossless_string(),
+        "<?xml version='1.0'?>\n<a>\n  <!-- first -->\n  <b x = '1' />\n  <c y='2'>  <d><f/></d>\n  </c>\n  <e>old &amp; text more</e>\n</a>\n"
+    );
+
+    let doc = parse_lossless(xml);
+    doc.find_tag("c").remove();
+    doc.find_tag("e")
+        .append_tag("g")
+        .unwrap()
+        .insert_cdata("new")
+        .unwrap();
+    assert_eq!(
+        doc.to_lossless_string(),
+        "<?xml version='1.0'?>\n<a>\n  <!-- first -->\n  <b x = '1' />\n  \n  <e>old &amp; text</e><g>new</g>\n</a>\n"
+    );
+
+    let doc = parse_lossless("<a>\n  <b/>\n</a>");
+    doc.find_tag("b").remove();
+    assert_eq!(doc.to_lossless_string(), "<a>\n  \n</a>");
+    doc.root().first_child().remove();
+    doc.root().first_child().remove();
+    assert_eq!(doc.to_lossless_string(), "<a></a>");
+}
+
+#[test]
+fn serialize_options() {
+    let doc =
+        Document::from_str("<a><b x='1'><c/></b><d>t <i>x</i></d><e y='\"'></e></a>").unwrap();
+
+    let compact = SerializeOptions::default();
+    assert_eq!(doc.to_string_with(&compact), doc.to_string());
+    assert_eq!(
+        doc.find_tag("d").to_string_with(&compact),
+        "<d>t <i>x</i></d>"
+    );
+
+    assert_eq!(
+        doc.to_string_with(&SerializeOptions::pretty()),
+        "<a>\n  <b x=\"1\">\n    <c/>\n  </b>\n  <d>t <i>x</i></d>\n  <e y=\"&quot;\"/>\n</a>"
+    );
+
+    let options = SerializeOptions {
+        indent: Some(Indent::Tab),
+        newline: Newline::CrLf,
+        quote: Quote::Single,
+        self_closing: false,
+        declaration: true,
+    };
+    assert_eq!(
+        doc.to_string_with(&options),
+        "<?xml version='1.0' encoding='UTF-8'?>\r\n<a>\r\n\t<b x='1'>\r\n\t\t<c></c>\r\n\t</b>\r\n\t<d>t <i>x</i></d>\r\n\t<e y='&quot;'></e>\r\n</a>"
+    );
+
+    // Existing whitespace is mixed content, and never changed
+    let doc = Document::from_str("<a>\n<b> <c/></b></a>").unwrap();
+    assert_eq!(
+        doc.to_string_with(&SerializeOptions::pretty()),
+        "<a>\n<b> <c/></b></a>"
+    );
+
+    let mut buf: Vec<u8> = Vec::new();
+    doc.write_to(&mut buf, &SerializeOptions::pretty()).unwrap();
+    assert_eq!(buf, b"<a>\n<b> <c/></b></a>");
+}
+
+#[test]
+fn canonical_xml() {
+    // W3C Canonical XML 1.0 example 3.3, without the DTD default attribute
+    let doc = Document::from_str(
+        r#"<doc>
+   <e1   />
+   <e2   ></e2>
+   <e3   name = "elem3"   id="elem3"   />
+   <e4   name="elem4"   id="elem4"   ></e4>
+   <e5 a:attr="out" b:attr="sorted" attr2="all" attr="I'm"
+      xmlns:b="http://www.ietf.org"
+      xmlns:a="http://www.w3.org"
+      xmlns="http://example.org"/>
+   <e6 xmlns="" xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="" xmlns:a="http://www.w3.org">
+            <e9 xmlns="" xmlns:a="http://www.ietf.org"/>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#,
+    )
+    .unwrap();
+    assert_eq!(
+        doc.to_canonical_string(&Canonicalization::Inclusive),
+        r#"<doc>
+   <e1></e1>
+   <e2></e2>
+   <e3 id="elem3" name="elem3"></e3>
+   <e4 id="elem4" name="elem4"></e4>
+   <e5 xmlns="http://example.org" xmlns:a="http://www.w3.org" xmlns:b="http://www.ietf.org" attr="I'm" attr2="all" b:attr="sorted" a:attr="out"></e5>
+   <e6 xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="">
+            <e9 xmlns:a="http://www.ietf.org"></e9>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#
+    );
+
+    // W3C Canonical XML 1.0 example 3.4, without the DTD attribute types
+    let doc = Document::from_str(
+        r#"<doc>
+   <text>First line&#x0d;&#10;Second line</text>
+   <value>&#x32;</value>
+   <compute><![CDATA[value>"0" && value<"10" ?"valid":"error"]]></compute>
+   <compute expr='value>"0" &amp;&amp; value&lt;"10" ?"valid":"error"'>valid</compute>
+   <norm attr=' &apos;   &#x20;&#13;&#xa;&#9;   &apos; '/>
+</doc>"#,
+    )
+    .unwrap();
+    assert_eq!(
+        doc.to_canonical_string(&Canonicalization::Inclusive),
+        r#"<doc>
+   <text>First line&#xD;
+Second line</text>
+   <value>2</value>
+   <compute>value&gt;"0" &amp;&amp; value&lt;"10" ?"valid":"error"</compute>
+   <compute expr="value>&quot;0&quot; &amp;&amp; value&lt;&quot;10&quot; ?&quot;valid&quot;:&quot;error&quot;">valid</compute>
+   <norm attr=" '    &#xD;&#xA;&#x9;   ' "></norm>
+</doc>"#
+    );
+}
+
+#[test]
+fn canonical_xml_subsets() {
+    // W3C Exclusive XML Canonicalization 1.0 examples in section 2.2
+    let doc1 = Document::from_str(
+        r#"<n0:local xmlns:n0="foo:bar" xmlns:n3="ftp://example.org">
+  <n1:elem2 xmlns:n1="http://example.net" xml:lang="en">
+    <n3:stuff xmlns:n3="ftp://example.org"/>
+  </n1:elem2>
+</n0:local>"#,
+    )
+    .unwrap();
+    let doc2 = Document::from_str(
+        r#"<n2:pdu xmlns:n1="http://example.com" xmlns:n2="http://foo.example" xml:lang="fr" xml:space="retain">
+  <n1:elem2 xmlns:n1="http://example.net" xml:lang="en">
+    <n3:stuff xmlns:n3="ftp://example.org"/>
+  </n1:elem2>
+</n2:pdu>"#,
+    )
+    .unwrap();
+    let elem1 = doc1.find_tag("n1:elem2");
+    let elem2 = doc2.find_tag("n1:elem2");
+
+    assert_eq!(
+        elem1.to_canonical_string(&Canonicalization::Inclusive),
+        r#"<n1:elem2 xmlns:n0="foo:bar" xmlns:n1="http://example.net" xmlns:n3="ftp://example.org" xml:lang="en">
+    <n3:stuff></n3:stuff>
+  </n1:elem2>"#
+    );
+    assert_eq!(
+        elem2.to_canonical_string(&Canonicalization::Inclusive),
+        r#"<n1:elem2 xmlns:n1="http://example.net" xmlns:n2="http://foo.example" xml:lang="en" xml:space="retain">
+    <n3:stuff xmlns:n3="ftp://example.org"></n3:stuff>
+  </n1:elem2>"#
+    );
+
+    let exclusive = Canonicalization::Exclusive(Vec::new());
+    let expected = r#"<n1:elem2 xmlns:n1="http://example.net" xml:lang="en">
+    <n3:stuff xmlns:n3="ftp://example.org"></n3:stuff>
+  </n1:elem2>"#;
+    assert_eq!(elem1.to_canonical_string(&exclusive), expected);
+    assert_eq!(elem2.to_canonical_string(&exclusive), expected);
+
+    let exclusive = Canonicalization::Exclusive(vec!["n0".to_string(), "#default".to_string()]);
+    assert_eq!(
+        elem1.to_canonical_string(&exclusive),
+        r#"<n1:elem2 xmlns:n0="foo:bar" xmlns:n1="http://example.net" xml:lang="en">
+    <n3:stuff xmlns:n3="ftp://example.org"></n3:stuff>
+  </n1:elem2>"#
+    );
+
+    let doc = Document::from_str("<a xmlns='urn:a'><b xmlns=''><c/></b></a>").unwrap();
+    assert_eq!(
+        doc.to_canonical_string(&exclusive),
+        "<a xmlns=\"urn:a\"><b xmlns=\"\"><c></c></b></a>"
+    );
+    let mut buf: Vec<u8> = Vec::new();
+    doc.find_tag("b")
+        .write_canonical(&mut buf, &Canonicalization::Inclusive)
+        .unwrap();
+    assert_eq!(buf, b"<b><c></c></b>");
+}
+
+struct ChunkRecorder {
+    bytes: Vec<u8>,
+    writes: Vec<usize>,
+    fail: bool,
+}
+
+impl std::io::Write for ChunkRecorder {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.fail {
+            return Err(std::io::Error::other("sink closed"));
+        }
+        self.bytes.extend_from_slice(buf);
+        self.writes.push(buf.len());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn streaming_output() {
+    let doc = Document::new("a").unwrap();
+    let long_text = "x & y ".repeat(1000);
+    for i in 0..100 {
+        let tag = doc.insert_tag("b").unwrap();
+        tag.set_attribute("n", Some(&i.to_string())).unwrap();
+        tag.insert_cdata("some <text>").unwrap();
+    }
+    doc.insert_tag("c")
+        .unwrap()
+        .insert_cdata(&long_text)
+        .unwrap();
+    let expected = doc.to_string();
+
+    let mut sink = ChunkRecorder {
+        bytes: Vec::new(),
+        writes: Vec::new(),
+        fail: false,
+    };
+    doc.write_to(&mut sink, &SerializeOptions::default())
+        .unwrap();
+    assert_eq!(String::from_utf8(sink.bytes).unwrap(), expected);
+    assert!(sink.writes.len() < 10);
+    assert!(sink.writes.iter().all(|size| *size <= 4096));
+
+    let mut sink = ChunkRecorder {
+        bytes: Vec::new(),
+        writes: Vec::new(),
+        fail: true,
+    };
+    let err = doc
+        .write_to(&mut sink, &SerializeOptions::default())
+        .unwrap_err();
+    assert_eq!(err.to_string(), "sink closed");
+
+    let mut buf = String::new();
+    doc.find_tag("b")
+        .format_to(&mut buf, &SerializeOptions::default())
+        .unwrap();
+    assert_eq!(buf, "<b n=\"0\">some &lt;text&gt;</b>");
+}
+
+#[test]
+fn namespace_queries() {
+    let doc = Document::from_str(
+        "<iq xmlns='jabber:client' xmlns:r='jabber:iq:roster'>\
+         <query xmlns='jabber:iq:roster' ver='1'><item jid='a@b'/></query>\
+         <r:query r:ver='2' xml:lang='en'/>\
+         <x xmlns=''>text</x>\
+         </iq>",
+    )
+    .unwrap();
+    let root = doc.root();
+    assert_eq!(root.namespace_uri(), Some("jabber:client"));
+    assert_eq!(root.local_name(), "iq");
+
+    let query = doc.find_tag_ns("jabber:iq:roster", "query");
+    assert_eq!(query.attribute("ver"), Some("1"));
+    assert_eq!(
+        query
+            .clone()
+            .find_tag_ns("jabber:iq:roster", "item")
+            .attribute("jid"),
+        Some("a@b")
+    );
+    let query = query.next();
+    assert_eq!(query.namespace_uri(), Some("jabber:iq:roster"));
+    assert_eq!(query.name(), "r:query");
+    assert_eq!(query.local_name(), "query");
+    assert_eq!(query.attribute_ns("jabber:iq:roster", "ver"), Some("2"));
+    assert_eq!(query.attribute_ns("", "ver"), None);
+    assert_eq!(query.attribute_ns(XML_NS, "lang"), Some("en"));
+    assert_eq!(root.attribute_ns(XMLNS_NS, "r"), Some("jabber:iq:roster"));
+    assert!(doc.find_tag_ns("jabber:client", "query").is_null());
+
+    let x = doc.find_tag_ns("", "x");
+    assert_eq!(x.namespace_uri(), None);
+    assert_eq!(
+        x.clone().first_child().lookup_namespace("r"),
+        Some("jabber:iq:roster")
+    );
+    assert_eq!(x.lookup_namespace(""), None);
+    assert_eq!(x.lookup_namespace("y"), None);
+    assert_eq!(x.lookup_prefix("jabber:iq:roster"), Some("r"));
+    assert_eq!(x.lookup_prefix("jabber:client"), None);
+    assert_eq!(root.lookup_prefix("jabber:client"), Some(""));
+    assert_eq!(root.lookup_prefix(XML_NS), Some("xml"));
+}
+
+#[test]
+fn insert_document_ns() {
+    let source = Document::from_str(
+        "<iq xmlns='jabber:client' xmlns:r='jabber:iq:roster'>\
+         <r:query a:x='1' xmlns:a='urn:a'><item r:n='2'/></r:query>\
+         </iq>",
+    )
+    .unwrap();
+    let query = source.find_tag("r:query");
+
+    let doc = Document::from_str("<message xmlns='jabber:client'/>").unwrap();
+    doc.root().insert_document_ns(query.clone()).unwrap();
+    check_doc_xml(
+        &doc,
+        "<message xmlns=\"jabber:client\"><r:query a:x=\"1\" xmlns:a=\"urn:a\" xmlns:r=\"jabber:iq:roster\"><item r:n=\"2\"/></r:query></message>",
+    );
+    let copy = doc.find_tag_ns("jabber:iq:roster", "query");
+    assert_eq!(
+        copy.clone().first_tag().namespace_uri(),
+        Some("jabber:client")
+    );
+
+    let doc = Document::from_str("<message xmlns='urn:other' xmlns:r='urn:r'/>").unwrap();
+    doc.root().insert_document_ns(query).unwrap();
+    check_doc_xml(
+        &doc,
+        "<message xmlns=\"urn:other\" xmlns:r=\"urn:r\"><r:query a:x=\"1\" xmlns:a=\"urn:a\" xmlns=\"jabber:client\" xmlns:r=\"jabber:iq:roster\"><item r:n=\"2\"/></r:query></message>",
+    );
+
+    // Unprefixed elements without a namespace
+    let source = Document::from_str("<a><b/></a>").unwrap();
+    let doc = Document::from_str("<c xmlns='urn:c'/>").unwrap();
+    doc.root().insert_document_ns(source.root()).unwrap();
+    check_doc_xml(&doc, "<c xmlns=\"urn:c\"><a xmlns=\"\"><b/></a></c>");
+    assert_eq!(doc.find_tag("a").namespace_uri(), None);
+}
+
+fn diff_strings(old: &str, new: &str, options: &DiffOptions) -> Vec<String> {
+    let old = Document::from_str(old).unwrap();
+    let new = Document::from_str(new).unwrap();
+    old.diff(&new, options)
+        .iter()
+        .map(|edit| edit.to_string())
+        .collect()
+}
+
+#[test]
+fn diff_documents() {
+    let options = DiffOptions::default();
+    assert!(
+        diff_strings(
+            "<a x='1' y='2'><b/>t</a>",
+            "<a y='2' x='1'><b/>t</a>",
+            &options
+        )
+        .is_empty()
+    );
+    assert_eq!(
+        diff_strings(
+            "<a x='1' y='2'><b/>t</a>",
+            "<a y='2' x='1'><b/>t</a>",
+            &DiffOptions {
+                ignore_attribute_order: false,
+                ..DiffOptions::default()
+            }
+        ),
+        ["@ /a: attribute order"]
+    );
+
+    assert_eq!(
+        diff_strings(
+            "<r><i jid='a'/><i jid='b'>x</i><i jid='c'/><n>old</n></r>",
+            "<r><i jid='c'/><i jid='b' sub='both'>y</i><n>new</n><i jid='d'/></r>",
+            &options
+        ),
+        [
+            "- /r/i[1]: <i jid=\"a\"/>",
+            "+ /r/i[3]: <i jid=\"d\"/>",
+            "@ /r/i[2] sub: (none) -> \"both\"",
+            "~ /r/i[2]/text()[1]: \"x\" -> \"y\"",
+            "~ /r/n/text()[1]: \"old\" -> \"new\"",
+        ]
+    );
+
+    assert_eq!(
+        diff_strings(
+            "<a><i x='1'/><i x='2'/></a>",
+            "<a><i x='2'/><i x='1'/></a>",
+            &options
+        ),
+        ["> /a/i[1] -> /a/i[2]"]
+    );
+    assert_eq!(
+        diff_strings("<a><b><c/></b><d/></a>", "<a><d><c/></d><b/></a>", &options),
+        ["> /a/b/c -> /a/d/c",]
+    );
+
+    // Large lists are paired by the identity attributes
+    let mut old = String::from("<query>");
+    let mut new = String::from("<query>");
+    for i in 0..2000 {
+        old.push_str(&format!(
+            "<item jid='{i}@b' sub='both'><group>G</group></item>"
+        ));
+        new.insert_str(
+            7,
+            &format!("<item jid='{i}@b' sub='to'><group>G</group></item>"),
+        );
+    }
+    old.push_str("</query>");
+    new.push_str("</query>");
+    let edits = diff_strings(&old, &new, &options);
+    assert_eq!(edits.len(), 2000);
+    assert_eq!(edits[0], "@ /query/item[2000] sub: \"both\" -> \"to\"");
+    assert_eq!(edits[1999], "@ /query/item[1] sub: \"both\" -> \"to\"");
+
+    assert_eq!(
+        diff_strings("<a>\n  <b/>\n</a>", "<a><b/></a>", &options),
+        ["- /a/text()[1]: \n  ", "- /a/text()[2]: \n"]
+    );
+    let options = DiffOptions {
+        ignore_whitespace: true,
+        ..DiffOptions::default()
+    };
+    assert!(diff_strings("<a>\n  <b/>\n</a>", "<a><b/></a>", &options).is_empty());
+
+    assert_eq!(
+        diff_strings("<a/>", "<b/>", &options),
+        ["- /a: <a/>", "+ /b: <b/>"]
+    );
+
+    let old = "<iq xmlns='jabber:client'><query xmlns='jabber:iq:roster' ver='1'/></iq>";
+    let new = "<c:iq xmlns:c='jabber:client' xmlns:r='jabber:iq:roster'><r:query ver='1'/></c:iq>";
+    assert_eq!(diff_strings(old, new, &options).len(), 2);
+    let options = DiffOptions {
+        ignore_prefixes: true,
+        ..DiffOptions::default()
+    };
+    assert!(diff_strings(old, new, &options).is_empty());
+
+    let old = Document::from_str(old).unwrap();
+    let new = Document::from_str(new).unwrap();
+    assert!(old.semantic_eq(&new));
+    let other = Document::from_str(
+        "<iq xmlns='jabber:client'>\n<query xmlns='jabber:iq:roster' ver='2'/></iq>",
+    )
+    .unwrap();
+    assert!(!old.semantic_eq(&other));
+    assert!(old.find_tag("query").semantic_eq(&new.find_tag("r:query")));
+}
+
+fn move_error(result: Result<Cursor, ParseError>) -> ParseErrorKind {
+    let Err(ParseError::BadXml { kind, .. }) = result else {
+        unreachable!("expected an edit error");
+    };
+    kind
+}
+
+#[test]
+fn restructure() {
+    let doc = Document::from_str("<a><b><c/>text</b><d/><e/></a>").unwrap();
+    let b = doc.find_tag("b");
+    let c = b.clone().find_tag("c");
+
+    c.clone().move_into(&doc.find_tag("d")).unwrap();
+    check_doc_xml(&doc, "<a><b>text</b><d><c/></d><e/></a>");
+    doc.find_tag("e").move_before(&b).unwrap();
+    check_doc_xml(&doc, "<a><e/><b>text</b><d><c/></d></a>");
+    doc.find_tag("e").move_after(&doc.find_tag("d")).unwrap();
+    check_doc_xml(&doc, "<a><b>text</b><d><c/></d><e/></a>");
+    c.clone().move_after(&b).unwrap();
+    check_doc_xml(&doc, "<a><b>text</b><c/><d/><e/></a>");
+    // Cursors stay valid after moves
+    assert_eq!(c.clone().previous().name(), "b");
+    assert_eq!(c.clone().parent().name(), "a");
+
+    let wrapper = c.clone().wrap("w").unwrap();
+    check_doc_xml(&doc, "<a><b>text</b><w><c/></w><d/><e/></a>");
+    wrapper.unwrap().unwrap();
+    check_doc_xml(&doc, "<a><b>text</b><c/><d/><e/></a>");
+    b.clone().unwrap().unwrap();
+    check_doc_xml(&doc, "<a>text<c/><d/><e/></a>");
+
+    let d = doc.find_tag("d");
+    let e = d.clone().replace_with(&doc.find_tag("e")).unwrap();
+    check_doc_xml(&doc, "<a>text<c/><e/></a>");
+    assert_eq!(e.name(), "e");
+    assert!(d.parent().is_null());
+
+    doc.root()
+        .sort_children_by(|child| match child.is_tag() {
+            true => child.name().to_string(),
+            false => String::new(),
+        })
+        .unwrap();
+    check_doc_xml(&doc, "<a>text<c/><e/></a>");
+    doc.root()
+        .sort_children_by(|child| std::cmp::Reverse(child.name().to_string()))
+        .unwrap();
+    check_doc_xml(&doc, "<a><e/><c/>text</a>");
+    assert_eq!(doc.root().last_child().cdata(), "text");
+
+    // Errors
+    let root = doc.root();
+    let e = doc.find_tag("e");
+    assert_eq!(
+        move_error(root.clone().move_into(&e)),
+        ParseErrorKind::RootMove
+    );
+    assert_eq!(
+        move_error(e.clone().move_before(&root)),
+        ParseErrorKind::RootSibling
+    );
+    assert_eq!(
+        move_error(e.clone().move_into(&e)),
+        ParseErrorKind::MoveIntoSubtree
+    );
+    assert_eq!(
+        move_error(e.clone().move_into(&root.clone().last_child())),
+        ParseErrorKind::CDataChildren
+    );
+    assert_eq!(move_error(root.clone().wrap("x")), ParseErrorKind::RootMove);
+    assert_eq!(move_error(root.clone().unwrap()), ParseErrorKind::RootMove);
+    let inner = e.clone().insert_tag("f").unwrap();
+    assert_eq!(
+        move_error(inner.replace_with(&e)),
+        ParseErrorKind::MoveIntoSubtree
+    );
+    let other = Document::from_str("<x><y/></x>").unwrap();
+    assert_eq!(
+        move_error(e.clone().move_into(&other.root())),
+        ParseErrorKind::DocumentMismatch
+    );
+    let text = root.clone().last_child();
+    assert_eq!(
+        move_error(text.clone().unwrap()),
+        ParseErrorKind::CDataChildren
+    );
+    assert_eq!(
+        move_error(text.sort_children_by(|child| child.is_tag())),
+        ParseErrorKind::CDataChildren
+    );
+    check_doc_xml(&doc, "<a><e><f/></e><c/>text</a>");
+
+    let cursor = SyncCursor::new(Document::from_str("<a><b/><c/></a>").unwrap());
+    let target = cursor.clone().first_tag();
+    cursor
+        .clone()
+        .first_child()
+        .next()
+        .move_before(&target)
+        .unwrap()
+        .wrap("w")
+        .unwrap();
+    assert_eq!(cursor.to_string(), "<a><w><c/></w><b/></a>");
+    cursor
+        .clone()
+        .first_tag()
+        .unwrap()
+        .unwrap()
+        .sort_children_by(|child| child.name().to_string())
+        .unwrap();
+    assert_eq!(cursor.to_string(), "<a><b/><c/></a>");
+    let other = SyncCursor::new(Document::from_str("<x/>").unwrap());
+    assert!(cursor.first_tag().move_into(&other).is_err());
+}
+
+#[test]
+fn in_place_edits() {
+    let doc = Document::from_str("<a><b x='1' y='2'>old<c/>text</b><d/></a>").unwrap();
+    let b = doc.find_tag("b");
+    let c = b.clone().find_tag("c");
+
+    b.rename("e").unwrap().remove_attribute("x").unwrap();
+    check_doc_xml(&doc, "<a><e y=\"2\">old<c/>text</e><d/></a>");
+    b.remove_attribute("none").unwrap();
+    b.remove_attribute("y").unwrap();
+    check_doc_xml(&doc, "<a><e>old<c/>text</e><d/></a>");
+
+    b.clone().first_child().set_cdata("new & <old>").unwrap();
+    check_doc_xml(&doc, "<a><e>new &amp; &lt;old&gt;<c/>text</e><d/></a>");
+    b.set_text("only").unwrap();
+    check_doc_xml(&doc, "<a><e>only</e><d/></a>");
+    // Removed children are detached
+    assert!(c.clone().parent().is_null());
+    assert!(c.next().is_null());
+    doc.root().clear_children().unwrap();
+    check_doc_xml(&doc, "<a/>");
+    doc.root().set_text("").unwrap();
+    check_doc_xml(&doc, "<a/>");
+    doc.root().set_text("x").unwrap().insert_tag("y").unwrap();
+    check_doc_xml(&doc, "<a>x<y/></a>");
+
+    // Errors
+    let text = doc.root().first_child();
+    assert_eq!(move_error(text.rename("b")), ParseErrorKind::CDataRename);
+    assert_eq!(
+        move_error(text.set_text("b")),
+        ParseErrorKind::CDataChildren
+    );
+    assert_eq!(
+        move_error(text.clear_children()),
+        ParseErrorKind::CDataChildren
+    );
+    assert_eq!(
+        move_error(text.remove_attribute("b")),
+        ParseErrorKind::CDataAttribute
+    );
+    assert_eq!(
+        move_error(doc.root().set_cdata("b")),
+        ParseErrorKind::TagSetCData
+    );
+
+    let doc = parse_lossless("<a>\n  <b x = '1' y='2'>old</b >\n  <c><d/> </c>\n</a>\n");
+    doc.find_tag("b").rename("e").unwrap();
+    doc.find_tag("c").set_text("new").unwrap();
+    assert_eq!(
+        doc.to_lossless_string(),
+        "<a>\n  <e x=\"1\" y=\"2\">old</e>\n  <c>new</c>\n</a>\n"
+    );
+
+    let cursor = SyncCursor::new(Document::from_str("<a x='1'><b/></a>").unwrap());
+    cursor
+        .clone()
+        .remove_attribute("x")
+        .unwrap()
+        .rename("r")
+        .unwrap()
+        .clear_children()
+        .unwrap()
+        .set_text("text")
+        .unwrap()
+        .first_child()
+        .set_cdata("more")
+        .unwrap();
+    assert_eq!(cursor.to_string(), "<r>more</r>");
+}
+
+#[test]
+fn compaction() {
+    let mut doc = Document::from_str("<a x='1'><b>text</b><c y='2'><d/>more</c><e/></a>").unwrap();
+    assert_eq!(doc.wasted_bytes(), 0);
+    doc.find_tag("b").remove();
+    for i in 0..50 {
+        doc.find_tag("c")
+            .set_attribute("y", Some(&i.to_string()))
+            .unwrap();
+        doc.find_tag("e").set_text("replaced text").unwrap();
+    }
+    let xml = doc.to_string();
+    let before = doc.arena_stats();
+    assert!(doc.wasted_bytes() > 0);
+
+    doc.compact().unwrap();
+    assert_eq!(doc.to_string(), xml);
+    assert_eq!(doc.wasted_bytes(), 0);
+    let after = doc.arena_stats();
+    assert_eq!(after.chunks, 1);
+    assert!(after.allocated_bytes < before.allocated_bytes);
+    // Compacted document is still editable
+    doc.find_tag("c").insert_tag("f").unwrap();
+    check_doc_xml(
+        &doc,
+        "<a x=\"1\"><c y=\"49\"><d/>more<f/></c><e>replaced text</e></a>",
+    );
+
+    let xml = "<?xml version='1.0'?>\n<a>\n  <!-- c -->\n  <b x = '1' />\n  <c>old</c>\n</a>\n";
+    let mut doc = parse_lossless(xml);
+    doc.compact().unwrap();
+    assert_eq!(doc.to_lossless_string(), xml);
+    doc.find_tag("c").set_text("new").unwrap();
+    doc.compact().unwrap();
+    assert_eq!(
+        doc.to_lossless_string(),
+        "<?xml version='1.0'?>\n<a>\n  <!-- c -->\n  <b x = '1' />\n  <c>new</c>\n</a>\n"
+    );
+
+    let cursor = SyncCursor::new(Document::from_str("<a><b/><c/></a>").unwrap());
+    let c = cursor.clone().first_tag().next();
+    cursor.clone().first_tag().remove();
+    let Err(ParseError::BadXml { kind, .. }) = c.clone().compact() else {
+        unreachable!("expected a shared document error");
+    };
+    assert_eq!(kind, ParseErrorKind::DocumentShared);
+    drop(cursor);
+    let c = c.compact().unwrap();
+    assert_eq!(c.name(), "c");
+    assert_eq!(c.wasted_bytes(), 0);
+    assert_eq!(c.clone().root().to_string(), "<a><c/></a>");
+
+    let b = SyncCursor::new(Document::from_str("<a><b/></a>").unwrap()).first_tag();
+    b.clone().remove();
+    assert!(b.compact().unwrap().is_null());
+}
+
+// Compaction must free exactly the counted bytes
+fn check_wasted_bytes(mut doc: Document) {
+    let used = doc.arena_stats().used_bytes;
+    let wasted = doc.wasted_bytes();
+    doc.compact().unwrap();
+    assert_eq!(doc.arena_stats().used_bytes, used - wasted);
+}
+
+#[test]
+fn wasted_bytes_count() {
+    let doc = Document::from_str("<a>x&amp;y<b c='1' d='2'>t</b><e><f/>g</e></a>").unwrap();
+    let b = doc.find_tag("b");
+    b.clone().set_attribute("c", Some("3")).unwrap();
+    b.clone().remove_attribute("d").unwrap();
+    b.clone().rename("bb").unwrap();
+    b.clone().first_child().set_cdata("u").unwrap();
+    b.clone().first_child().append_cdata("v").unwrap();
+    b.clone().insert_cdata("w").unwrap();
+    doc.find_tag("e").unwrap().unwrap();
+    doc.root().insert_tag("h").unwrap().insert_tag("i").unwrap();
+    doc.find_tag("h").replace_with(&doc.find_tag("f")).unwrap();
+    check_wasted_bytes(doc);
+
+    let doc = Document::from_str("<a><b><c x='1'>text</c></b><d/></a>").unwrap();
+    let b = doc.find_tag("b");
+    let c = b.clone().first_child();
+    b.clone().remove();
+    // Removed elements are counted once, and their new values too
+    c.clone().set_attribute("x", Some("2")).unwrap();
+    c.clone().first_child().set_cdata("other").unwrap();
+    c.clone().remove();
+    b.clone().clear_children().unwrap();
+    check_wasted_bytes(doc);
+
+    let doc = Document::from_str("<a><b><c>text</c></b><d/></a>").unwrap();
+    let b = doc.find_tag("b");
+    let c = b.clone().first_child();
+    b.remove();
+    // Moving back into the document makes the bytes live again
+    c.clone().move_into(&doc.find_tag("d")).unwrap();
+    doc.find_tag("d").set_text("new").unwrap();
+    check_wasted_bytes(doc);
+
+    let doc = parse_lossless("<a>\n  <b x='1'/>\n  <c>old</c>\n</a>\n");
+    doc.find_tag("b").remove();
+    doc.find_tag("c").set_text("new").unwrap();
+    check_wasted_bytes(doc);
+}
+
+#[test]
+fn text_content() {
+    let doc = Document::from_str(
+        "<a>\n  <b>Hello <i>big</i> world</b>\n  <c>  x\t\n y  </c><d/><e> </e>\n</a>",
+    )
+    .unwrap();
+    let b = doc.find_tag("b");
+    assert_eq!(b.text(), "Hello big world");
+    assert_eq!(
+        b.clone().text_chunks().collect::<Vec<&str>>(),
+        vec!["Hello ", "big", " world"]
+    );
+    assert_eq!(b.clone().first_child().text(), "Hello ");
+    assert_eq!(doc.find_tag("c").text(), "  x\t\n y  ");
+    assert_eq!(doc.find_tag("c").normalized_text(), "x y");
+    assert_eq!(doc.find_tag("d").text(), "");
+    assert_eq!(doc.find_tag("e").normalized_text(), "");
+    assert_eq!(doc.normalized_text(), "Hello big world x y");
+    assert_eq!(doc.child_text("c"), Some("  x\t\n y  ".to_string()));
+    assert_eq!(doc.child_text("d"), Some(String::new()));
+    assert_eq!(doc.child_text("i"), None);
+    assert_eq!(doc.find_tag("x").text(), "");
+
+    // Whitespace spanning text nodes
+    let doc = Document::from_str("<a> one <b> </b> two<c/>three </a>").unwrap();
+    assert_eq!(doc.normalized_text(), "one twothree");
+
+    let cursor = SyncCursor::new(Document::from_str("<a><b>x <c>y</c></b></a>").unwrap());
+    assert_eq!(cursor.text(), "x y");
+    assert_eq!(cursor.normalized_text(), "x y");
+    assert_eq!(cursor.child_text("b"), Some("x y".to_string()));
+    assert_eq!(cursor.child_text("c"), None);
+}
+
+#[test]
+fn typed_values() {
+    let doc = Document::from_str(
+        "<item count=' 42 ' ratio='0.5' flag='0' on='true' bad='yes'><n>\n  7\n</n><b>1</b><s>x y</s></item>",
+    )
+    .unwrap();
+    let item = doc.root();
+    assert_eq!(item.attribute_as::<u32>("count"), Ok(Some(42)));
+    assert_eq!(item.attribute_as::<f64>("ratio"), Ok(Some(0.5)));
+    assert_eq!(item.attribute_as::<u32>("none"), Ok(None));
+    assert_eq!(item.attribute_bool("flag"), Ok(Some(false)));
+    assert_eq!(item.attribute_bool("on"), Ok(Some(true)));
+    assert_eq!(item.attribute_bool("none"), Ok(None));
+    assert_eq!(item.clone().find_tag("n").text_as::<i64>(), Ok(7));
+    assert_eq!(item.clone().find_tag("b").text_bool(), Ok(true));
+    assert_eq!(
+        item.clone().find_tag("s").text_as::<String>(),
+        Ok("x y".to_string())
+    );
+
+    let Err(err) = item.attribute_bool("bad") else {
+        unreachable!("expected a value error");
+    };
+    assert_eq!(err.tag, "item");
+    assert_eq!(err.attribute.as_deref(), Some("bad"));
+    assert_eq!(err.value, "yes");
+    assert_eq!(
+        err.to_string(),
+        "invalid value 'yes' of attribute 'bad' in tag 'item': expected one of 'true', 'false', '1' or '0'"
+    );
+    let Err(err) = item.attribute_as::<i8>("ratio") else {
+        unreachable!("expected a value error");
+    };
+    assert_eq!(
+        err.to_string(),
+        "invalid value '0.5' of attribute 'ratio' in tag 'item': invalid digit found in string"
+    );
+    let Err(err) = item.clone().find_tag("s").first_child().text_as::<u8>() else {
+        unreachable!("expected a value error");
+    };
+    assert_eq!(err.tag, "s");
+    assert_eq!(err.attribute, None);
+    assert_eq!(
+        err.to_string(),
+        "invalid text 'x y' in tag 's': invalid digit found in string"
+    );
+
+    item.set_attribute_value("count", 43).unwrap();
+    item.set_attribute_value("flag", true).unwrap();
+    item.set_attribute_value("new", 'c').unwrap();
+    assert_eq!(item.attribute_as::<u32>("count"), Ok(Some(43)));
+    assert_eq!(item.attribute_bool("flag"), Ok(Some(true)));
+    assert_eq!(item.attribute("new"), Some("c"));
+
+    let cursor = SyncCursor::new(Document::from_str("<a n='1'><b>false</b></a>").unwrap());
+    let cursor = cursor.set_attribute_value("n", 2.5).unwrap();
+    assert_eq!(cursor.attribute_as::<f32>("n"), Ok(Some(2.5)));
+    assert_eq!(cursor.attribute_bool("m"), Ok(None));
+    let b = cursor.first_tag();
+    assert_eq!(b.text_bool(), Ok(false));
+    assert!(b.text_as::<u8>().is_err());
+}
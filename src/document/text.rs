@@ -0,0 +1,103 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use super::Cursor;
+use super::Document;
+use super::TextChunks;
+
+// Whitespace characters of the XML specification
+fn is_xml_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+impl<'a> Cursor<'a> {
+    /// Returns an iterator over the character data of the element and its descendants.
+    ///
+    /// The chunks are borrowed from the document in the document order.
+    pub fn text_chunks(self) -> TextChunks<'a> {
+        TextChunks::new(self)
+    }
+
+    /// Returns the concatenated character data of the element and its descendants.
+    ///
+    /// This is the string value of the element in the XPath terms.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use iks::{Document, ParseError};
+    /// # fn main() -> Result<(), ParseError> {
+    ///
+    /// let doc = Document::from_str(
+    ///     "<message><body>Hello <b>world</b>!</body><subject>\n  Say  hi\n</subject></message>",
+    /// )?;
+    ///
+    /// assert_eq!(doc.find_tag("body").text(), "Hello world!");
+    /// assert_eq!(doc.find_tag("subject").normalized_text(), "Say hi");
+    /// assert_eq!(doc.root().child_text("body"), Some("Hello world!".to_string()));
+    /// assert_eq!(doc.root().child_text("thread"), None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn text(&self) -> String {
+        self.clone().text_chunks().collect()
+    }
+
+    /// Returns the character data with the whitespace normalized.
+    ///
+    /// Follows the XPath `normalize-space` function: leading and
+    /// trailing whitespace is removed, and the whitespace sequences
+    /// inside are replaced with a single space, even when they span
+    /// several text nodes.
+    pub fn normalized_text(&self) -> String {
+        let mut buf = String::new();
+        let mut pending_space = false;
+        for chunk in self.clone().text_chunks() {
+            for c in chunk.chars() {
+                if is_xml_space(c) {
+                    pending_space = !buf.is_empty();
+                } else {
+                    if pending_space {
+                        buf.push(' ');
+                        pending_space = false;
+                    }
+                    buf.push(c);
+                }
+            }
+        }
+        buf
+    }
+
+    /// Returns the character data of the first child tag with the given name.
+    ///
+    /// Returns `None` if there is no such child tag.
+    pub fn child_text(&self, tag_name: &str) -> Option<String> {
+        let child = self.clone().find_tag(tag_name);
+        if child.is_null() {
+            return None;
+        }
+        Some(child.text())
+    }
+}
+
+impl Document {
+    pub fn text(&self) -> String {
+        self.root().text()
+    }
+
+    pub fn normalized_text(&self) -> String {
+        self.root().normalized_text()
+    }
+
+    pub fn child_text(&self, tag_name: &str) -> Option<String> {
+        self.root().child_text(tag_name)
+    }
+}
@@ -0,0 +1,157 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::ParseError;
+
+use super::Cursor;
+use super::ValueError;
+
+fn trim_xml_space(value: &str) -> &str {
+    value.trim_matches([' ', '\t', '\r', '\n'])
+}
+
+// XML Schema boolean lexical forms
+fn parse_boolean(value: &str) -> Result<bool, &'static str> {
+    match value {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err("expected one of 'true', 'false', '1' or '0'"),
+    }
+}
+
+impl<'a> Cursor<'a> {
+    // Name of the tag for the error messages
+    fn value_tag_name(&self) -> String {
+        if self.is_tag() {
+            self.name().to_string()
+        } else {
+            self.clone().parent().name().to_string()
+        }
+    }
+
+    fn value_error(&self, attribute: Option<&str>, value: &str, reason: String) -> ValueError {
+        ValueError {
+            tag: self.value_tag_name(),
+            attribute: attribute.map(str::to_string),
+            value: value.to_string(),
+            reason,
+        }
+    }
+
+    /// Returns the value of the given attribute converted to the type.
+    ///
+    /// Leading and trailing whitespace of the value is ignored.
+    /// Returns `Ok(None)` if the attribute does not exist.
+    ///
+    /// Note that the [FromStr] implementation of the `bool` type only
+    /// accepts the `true` and `false` values, use the
+    /// [attribute_bool](Cursor::attribute_bool) method for the XML
+    /// Schema boolean values.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [ValueError] which names the tag, the attribute and
+    /// the value if the conversion fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use iks::Document;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///
+    /// let doc = Document::from_str("<server port='5222' tls='1'><timeout>30</timeout></server>")?;
+    /// let server = doc.root();
+    ///
+    /// assert_eq!(server.attribute_as::<u16>("port")?, Some(5222));
+    /// assert_eq!(server.attribute_bool("tls")?, Some(true));
+    /// assert_eq!(server.clone().find_tag("timeout").text_as::<u32>()?, 30);
+    ///
+    /// let err = server.attribute_as::<u8>("port").unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "invalid value '5222' of attribute 'port' in tag 'server': number too large to fit in target type"
+    /// );
+    ///
+    /// server.set_attribute_value("port", 5223)?;
+    /// assert_eq!(server.attribute("port"), Some("5223"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn attribute_as<T>(&self, name: &str) -> Result<Option<T>, ValueError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let Some(value) = self.attribute(name) else {
+            return Ok(None);
+        };
+        match trim_xml_space(value).parse::<T>() {
+            Ok(result) => Ok(Some(result)),
+            Err(err) => Err(self.value_error(Some(name), value, err.to_string())),
+        }
+    }
+
+    /// Returns the value of the given attribute as an XML Schema boolean.
+    ///
+    /// The `true` and `1` values are true, and the `false` and `0`
+    /// values are false. Returns `Ok(None)` if the attribute does not
+    /// exist.
+    pub fn attribute_bool(&self, name: &str) -> Result<Option<bool>, ValueError> {
+        let Some(value) = self.attribute(name) else {
+            return Ok(None);
+        };
+        match parse_boolean(trim_xml_space(value)) {
+            Ok(result) => Ok(Some(result)),
+            Err(reason) => Err(self.value_error(Some(name), value, reason.to_string())),
+        }
+    }
+
+    /// Returns the character data of the element converted to the type.
+    ///
+    /// The text is concatenated as in the [text](Cursor::text) method,
+    /// and the leading and trailing whitespace is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [ValueError] which names the tag and the text if the
+    /// conversion fails.
+    pub fn text_as<T>(&self) -> Result<T, ValueError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let text = self.text();
+        trim_xml_space(&text)
+            .parse::<T>()
+            .map_err(|err| self.value_error(None, &text, err.to_string()))
+    }
+
+    /// Returns the character data of the element as an XML Schema boolean.
+    pub fn text_bool(&self) -> Result<bool, ValueError> {
+        let text = self.text();
+        parse_boolean(trim_xml_space(&text))
+            .map_err(|reason| self.value_error(None, &text, reason.to_string()))
+    }
+
+    /// Sets the attribute to the string representation of the value.
+    ///
+    /// Adds the attribute if it does not exist.
+    pub fn set_attribute_value<T: Display>(
+        &self,
+        name: &str,
+        value: T,
+    ) -> Result<Cursor<'a>, ParseError> {
+        self.set_attribute(name, Some(&value.to_string()))
+    }
+}
@@ -63,7 +63,7 @@ pub fn escape(s: &str, output: &mut String) {
     }
 }
 
-pub fn escape_fmt(s: &str, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+pub fn escape_fmt<W: std::fmt::Write>(s: &str, f: &mut W) -> std::fmt::Result {
     let bytes = s.as_bytes();
     let mut i: usize = 0;
     let mut back: usize = 0;
@@ -55,11 +55,17 @@
 //! the Iksemel. Validates and processes byte streams and generates
 //! XML elements.
 //!
+//! Namespace Resolver: Wraps the Sax Parser and resolves the prefixed
+//! tag and attribute names into namespace URIs and local names.
+//!
 //! Document: Builds and queries XML element trees inside Arenas.
 //!
 //! Document Parser: Parses an XML byte stream into an XML element
 //! tree structure.
 //!
+//! Xml Writer: Writes a stream of XML elements with escaping and
+//! well-formedness checks, without building a document tree.
+//!
 //! Stream Parser: Parses an XML byte stream into an XMPP Stream
 //! with individual top level elements.
 //!
@@ -69,11 +75,15 @@
 //! XmppClient: A complete client implementation using blocking-io
 //! operations on top of the XmppClientProtocol.
 //!
+//! AsyncXmppClient: The same client using the tokio async-io, which
+//! is available with the `async` feature.
+//!
 
 mod arena;
 mod document;
 mod entities;
 mod parser;
+mod writer;
 #[cfg(feature = "xmpp")]
 mod xmpp;
 mod xpath;
@@ -84,24 +94,54 @@ pub use arena::Arena;
 pub use arena::ArenaStats;
 pub use arena::NoMemory;
 
+pub use parser::ElementSpan;
 pub use parser::Location;
+pub use parser::NamespaceElement;
+pub use parser::NamespaceElements;
+pub use parser::NamespaceResolver;
 pub use parser::ParseError;
+pub use parser::ParseErrorKind;
+pub use parser::ParserLimits;
+pub use parser::QName;
+pub use parser::ReadError;
 pub use parser::SaxElement;
 pub use parser::SaxElements;
 pub use parser::SaxParser;
+pub use parser::SaxReader;
+pub use parser::Span;
+pub use parser::TagMismatch;
+pub use parser::XML_NS;
+pub use parser::XMLNS_NS;
+pub use parser::XmlDeclaration;
 
 pub use document::Ancestor;
 pub use document::Attributes;
+pub use document::Canonicalization;
 pub use document::Children;
 pub use document::Cursor;
 pub use document::DescendantOrSelf;
+pub use document::DiffEdit;
+pub use document::DiffOptions;
 pub use document::Document;
 pub use document::DocumentBuilder;
 pub use document::DocumentParser;
 pub use document::FollowingSibling;
+pub use document::Indent;
+pub use document::Newline;
 pub use document::PrecedingSibling;
+pub use document::Quote;
+pub use document::SerializeOptions;
 pub use document::SyncCursor;
+pub use document::TextChunks;
+pub use document::ValueError;
+
+pub use writer::WriteError;
+pub use writer::XmlWriter;
 
+#[cfg(feature = "async")]
+pub use xmpp::AsyncStreamReader;
+#[cfg(feature = "async")]
+pub use xmpp::AsyncXmppClient;
 #[cfg(feature = "xmpp")]
 pub use xmpp::BadJid;
 #[cfg(feature = "xmpp")]
@@ -126,3 +166,5 @@ pub use xmpp::constants::CLIENT_PORT as XMPP_CLIENT_PORT;
 pub use xmpp::constants::SERVER_PORT as XMPP_SERVER_PORT;
 
 pub use xpath::XPath;
+pub use xpath::XPathSequence;
+pub use xpath::XPathValue;
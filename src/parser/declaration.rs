@@ -0,0 +1,116 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use super::ParseErrorKind;
+
+/// Maximum number of bytes collected for an XML declaration.
+pub(super) const MAX_DECLARATION_SIZE: usize = 256;
+
+/// The XML declaration at the beginning of a document.
+///
+/// This struct is returned from the
+/// [xml_declaration()](crate::SaxParser::xml_declaration) method
+/// after a declaration such as `<?xml version="1.0" encoding="UTF-8"?>`
+/// is parsed.
+///
+/// Only the UTF-8 encoding is supported, so the parser rejects the
+/// declarations with any other encoding.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct XmlDeclaration {
+    /// Version of the XML specification, for example "1.0".
+    pub version: String,
+    /// Encoding of the document if it is declared.
+    pub encoding: Option<String>,
+    /// Value of the standalone document declaration if it is given.
+    pub standalone: Option<bool>,
+}
+
+fn skip_whitespace(s: &str) -> &str {
+    s.trim_start_matches([' ', '\t', '\r', '\n'])
+}
+
+// Parses a pseudo attribute, returns the name, value and the rest.
+fn pseudo_attribute(s: &str) -> Option<(&str, &str, &str)> {
+    let (name, rest) = s.split_once('=')?;
+    let name = name.trim_end_matches([' ', '\t', '\r', '\n']);
+    let rest = skip_whitespace(rest);
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let (value, rest) = rest[1..].split_once(quote)?;
+    Some((name, value, rest))
+}
+
+fn is_version(value: &str) -> bool {
+    match value.strip_prefix("1.") {
+        Some(minor) => !minor.is_empty() && minor.bytes().all(|c| c.is_ascii_digit()),
+        None => false,
+    }
+}
+
+fn is_encoding(value: &str) -> bool {
+    let mut chars = value.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+impl XmlDeclaration {
+    // Parses the declaration text between the "<?xml" and "?>" markers.
+    pub(super) fn parse(s: &str) -> Result<XmlDeclaration, ParseErrorKind> {
+        let mut version = None;
+        let mut encoding = None;
+        let mut standalone = None;
+        let mut rest = s;
+        loop {
+            let next = skip_whitespace(rest);
+            if next.is_empty() {
+                break;
+            }
+            if next.len() == rest.len() {
+                // Pseudo attributes must be separated with whitespace
+                return Err(ParseErrorKind::XmlDeclBad);
+            }
+            let (name, value, next) = pseudo_attribute(next).ok_or(ParseErrorKind::XmlDeclBad)?;
+            // Pseudo attributes must be in this order
+            match name {
+                "version" if version.is_none() && encoding.is_none() && standalone.is_none() => {
+                    if !is_version(value) {
+                        return Err(ParseErrorKind::XmlDeclBad);
+                    }
+                    version = Some(value.to_string());
+                }
+                "encoding" if version.is_some() && encoding.is_none() && standalone.is_none() => {
+                    if !is_encoding(value) {
+                        return Err(ParseErrorKind::XmlDeclBad);
+                    }
+                    if !value.eq_ignore_ascii_case("UTF-8") {
+                        return Err(ParseErrorKind::XmlDeclEncoding(value.to_string()));
+                    }
+                    encoding = Some(value.to_string());
+                }
+                "standalone" if version.is_some() && standalone.is_none() => {
+                    standalone = match value {
+                        "yes" => Some(true),
+                        "no" => Some(false),
+                        _ => return Err(ParseErrorKind::XmlDeclBad),
+                    };
+                }
+                _ => return Err(ParseErrorKind::XmlDeclBad),
+            }
+            rest = next;
+        }
+        match version {
+            Some(version) => Ok(XmlDeclaration {
+                version,
+                encoding,
+                standalone,
+            }),
+            None => Err(ParseErrorKind::XmlDeclBad),
+        }
+    }
+}
@@ -11,14 +11,13 @@
 use std::error::Error;
 use std::fmt::Display;
 
+use super::Location;
+
 /// The error type for the SAX/DOM parsing operations.
 ///
 /// These categories are designed to be as few as possible and correspond to the distinct
 /// actions you might take based on the nature of the problem.
-///
-/// Location of the error is available from [location()](super::SaxParser::location)
-/// method.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ParseError {
     /// Parser could not allocate the memory needed for parsing buffers.
     ///
@@ -44,82 +43,409 @@ pub enum ParseError {
 
     /// A syntax error is encountered in the XML input.
     ///
-    /// The static string describes the actual syntax issue found
-    /// by the parser.
+    /// The kind describes the actual syntax issue found by the parser,
+    /// and the location points to the position in the input where the
+    /// issue is detected. Location is not available for the errors
+    /// returned from the document editing methods, since they are not
+    /// related to any input.
     ///
     /// Certain errors, such as mismatched names of the start and end tags,
     /// or duplicate attribute names in the same tag, are not checked by the
-    /// [SaxParser](super::SaxParser), since that requires a potentially large
-    /// state to be maintained.
+    /// [SaxParser](super::SaxParser) by default, since that requires a
+    /// potentially large state to be maintained. They are checked when the
+    /// [strict](super::SaxParser::set_strict) mode is enabled.
     ///
-    /// Users of the parser can implement these checks if they want to. The
-    /// [DocumentParser](crate::DocumentParser)
-    /// for example, already builds an in-memory representation of the XML
-    /// tree structure, and applies all these checks without additional cost.
+    /// The [DocumentParser](crate::DocumentParser) always applies these
+    /// checks, since it already builds an in-memory representation of the
+    /// XML tree structure, and can do them without additional cost.
     ///
     /// Best action is to abort the current operation and relay the error
     /// details to the user.
-    BadXml(&'static str),
+    BadXml {
+        /// What kind of syntax issue is found.
+        kind: ParseErrorKind,
+        /// Where the issue is found in the input.
+        location: Option<Location>,
+    },
+
+    /// Tags are nested deeper than the [max_depth](super::ParserLimits::max_depth) limit.
+    ///
+    /// This and the following errors are only returned when the parser is
+    /// created with [ParserLimits](super::ParserLimits). Input is otherwise
+    /// valid up to this point, but too expensive to process.
+    ///
+    /// Best action is to abort the current operation and reject the input
+    /// source, for example by closing the network connection.
+    TooDeep,
+
+    /// A tag or attribute name is longer than the
+    /// [max_name_length](super::ParserLimits::max_name_length) limit.
+    NameTooLong,
+
+    /// A tag has more attributes than the
+    /// [max_attributes](super::ParserLimits::max_attributes) limit.
+    TooManyAttributes,
+
+    /// An attribute value is longer than the
+    /// [max_attribute_value_length](super::ParserLimits::max_attribute_value_length) limit.
+    AttributeValueTooLong,
+
+    /// A character data run is longer than the
+    /// [max_cdata_length](super::ParserLimits::max_cdata_length) limit.
+    CDataTooLong,
+
+    /// Input is longer than the
+    /// [max_total_bytes](super::ParserLimits::max_total_bytes) limit.
+    InputTooLong,
+}
+
+impl ParseError {
+    /// Returns the kind of the syntax error if this is a [BadXml](ParseError::BadXml) error.
+    pub fn kind(&self) -> Option<&ParseErrorKind> {
+        match self {
+            ParseError::BadXml { kind, .. } => Some(kind),
+            _ => None,
+        }
+    }
+
+    /// Returns the input location of the syntax error if it is known.
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            ParseError::BadXml { location, .. } => *location,
+            _ => None,
+        }
+    }
+
+    /// Sets the location of a syntax error if it is not already set.
+    pub(crate) fn at(mut self, new_location: Location) -> ParseError {
+        if let ParseError::BadXml { location, .. } = &mut self
+            && location.is_none()
+        {
+            *location = Some(new_location);
+        }
+        self
+    }
 }
 
 impl Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ParseError::NoMemory => write!(f, "not enough memory"),
-            ParseError::BadXml(msg) => write!(f, "invalid xml syntax: {msg}"),
+            ParseError::BadXml { kind, .. } => write!(f, "invalid xml syntax: {kind}"),
+            ParseError::TooDeep => write!(f, "tags are nested too deep"),
+            ParseError::NameTooLong => write!(f, "name is too long"),
+            ParseError::TooManyAttributes => write!(f, "tag has too many attributes"),
+            ParseError::AttributeValueTooLong => write!(f, "attribute value is too long"),
+            ParseError::CDataTooLong => write!(f, "character data is too long"),
+            ParseError::InputTooLong => write!(f, "input is too long"),
         }
     }
 }
 
 impl Error for ParseError {}
 
-pub(super) mod description {
-    pub(in super::super) const UTF8_INVALID_CONT_BYTE: &str = "invalid UTF8 continuation byte";
-    pub(in super::super) const UTF8_OVERLONG_SEQUENCE: &str = "overlong UTF8 sequence";
-    pub(in super::super) const UTF8_INVALID_PREFIX_BYTE: &str = "invalid UTF8 prefix byte";
-    pub(in super::super) const CHAR_INVALID: &str = "invalid XML character";
-    pub(in super::super) const DOC_NO_CONTENT: &str = "document has no root tag";
-    pub(in super::super) const DOC_OPEN_TAGS: &str = "document has unclosed tags";
-    pub(in super::super) const DOC_OPEN_MARKUP: &str =
-        "document epilog has unclosed PI or comment tag";
-    pub(in super::super) const DOC_CDATA_WITHOUT_PARENT: &str =
-        "character data not allowed outside of the root tag";
-    pub(in super::super) const TAG_CLOSE_WITHOUT_OPEN: &str = "close tag without open";
-    pub(in super::super) const TAG_WHITESPACE_START: &str = "tag cannot start with whitespace";
-    pub(in super::super) const TAG_OUTSIDE_ROOT: &str = "tag cannot be outside of the root tag";
-    pub(in super::super) const TAG_EMPTY_NAME: &str = "tag has no name";
-    pub(in super::super) const TAG_DOUBLE_END: &str = "end tag has standalone ending too";
-    pub(in super::super) const TAG_END_TAG_ATTRIBUTES: &str = "end tag cannot have attributes";
-    pub(in super::super) const TAG_EMPTY_TAG_MISSING_END: &str =
-        "empty element tags must end after the '/'";
-    pub(in super::super) const TAG_ATTRIBUTE_WITHOUT_EQUAL: &str =
-        "tag attributes must have '=' before the value";
-    pub(in super::super) const TAG_ATTRIBUTE_WITHOUT_QUOTE: &str =
-        "tag attribute value must be double or single quotes";
-    pub(in super::super) const TAG_ATTRIBUTE_BAD_NAME: &str =
-        "tag attribute names cannot have '/', '<' or '>'";
-    pub(in super::super) const TAG_ATTRIBUTE_BAD_VALUE: &str =
-        "tag attribute value cannot have '<' character without a reference";
-    pub(in super::super) const REFERENCE_INVALID_DECIMAL: &str =
-        "non digit in decimal character reference";
-    pub(in super::super) const REFERENCE_INVALID_HEX: &str =
-        "non hex digit in hexadecimal character reference";
-    pub(in super::super) const REFERENCE_CUSTOM_ENTITY: &str =
-        "non-predefined entity references are not supported";
-    pub(in super::super) const COMMENT_MISSING_DASH: &str =
-        "comment tag should start with double dash";
-    pub(in super::super) const COMMENT_MISSING_END: &str =
-        "comment tag should end after double dash";
-    pub(in super::super) const MARKUP_CDATA_SECTION_BAD_START: &str =
-        "character data sections must start with '[CDATA['";
-    pub(in super::super) const MARKUP_DOCTYPE_BAD_START: &str =
-        "doctype must start with 'DOCTYPE '";
-    pub(in super::super) const MARKUP_CDATA_SECTION_OUTSIDE_ROOT: &str =
-        "character data sections cannot be outside of the root tag";
-    pub(in super::super) const MARKUP_UNRECOGNIZED: &str =
-        "markup is not a comment, character data section, or document type declaration";
-    pub(in super::super) const PI_MISSING_END: &str =
-        "processing instruction must end after closing the '?'";
-    pub(in super::super) const TAG_YIELD_MISMATCH: &str =
-        "parser was on /, > or whitespace before yielding the tag name";
+impl From<ParseErrorKind> for ParseError {
+    fn from(kind: ParseErrorKind) -> Self {
+        ParseError::BadXml {
+            kind,
+            location: None,
+        }
+    }
+}
+
+/// Start and end tags with different names.
+///
+/// Locations point to the end of the tag names in the input. They are
+/// not available if the document is built without a parser, for example
+/// with a [DocumentBuilder](crate::DocumentBuilder).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TagMismatch {
+    /// Name of the start tag which is still open.
+    pub open_name: String,
+    /// Location of the start tag.
+    pub open_location: Option<Location>,
+    /// Name of the end tag.
+    pub close_name: String,
+    /// Location of the end tag.
+    pub close_location: Option<Location>,
+}
+
+/// Kinds of the syntax errors in [ParseError::BadXml].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParseErrorKind {
+    /// Invalid UTF8 continuation byte.
+    Utf8InvalidContByte,
+
+    /// Overlong UTF8 sequence.
+    Utf8OverlongSequence,
+
+    /// Invalid UTF8 prefix byte.
+    Utf8InvalidPrefixByte,
+
+    /// Invalid XML character.
+    CharInvalid,
+
+    /// Document has no root tag.
+    DocNoContent,
+
+    /// Document has unclosed tags.
+    DocOpenTags,
+
+    /// Document epilog has unclosed PI or comment tag.
+    DocOpenMarkup,
+
+    /// Character data not allowed outside of the root tag.
+    DocCDataWithoutParent,
+
+    /// Close tag without open.
+    TagCloseWithoutOpen,
+
+    /// Tag cannot start with whitespace.
+    TagWhitespaceStart,
+
+    /// Tag cannot be outside of the root tag.
+    TagOutsideRoot,
+
+    /// Tag has no name.
+    TagEmptyName,
+
+    /// End tag has standalone ending too.
+    TagDoubleEnd,
+
+    /// End tag cannot have attributes.
+    TagEndTagAttributes,
+
+    /// Empty element tags must end after the '/'.
+    TagEmptyTagMissingEnd,
+
+    /// Tag attributes must have '=' before the value.
+    TagAttributeWithoutEqual,
+
+    /// Tag attribute value must be double or single quotes.
+    TagAttributeWithoutQuote,
+
+    /// Tag attribute names cannot have '/', '<' or '>'.
+    TagAttributeBadName,
+
+    /// Tag attribute value cannot have '<' character without a reference.
+    TagAttributeBadValue,
+
+    /// Non digit in decimal character reference.
+    ReferenceInvalidDecimal,
+
+    /// Non hex digit in hexadecimal character reference.
+    ReferenceInvalidHex,
+
+    /// Non-predefined entity references are not supported.
+    ReferenceCustomEntity,
+
+    /// Comment tag should start with double dash.
+    CommentMissingDash,
+
+    /// Comment tag should end after double dash.
+    CommentMissingEnd,
+
+    /// Character data sections must start with '[CDATA['.
+    MarkupCDataSectionBadStart,
+
+    /// Doctype must start with 'DOCTYPE '.
+    MarkupDoctypeBadStart,
+
+    /// Character data sections cannot be outside of the root tag.
+    MarkupCDataSectionOutsideRoot,
+
+    /// Markup is not a comment, character data section, or document type declaration.
+    MarkupUnrecognized,
+
+    /// Processing instruction must end after closing the '?'.
+    PiMissingEnd,
+
+    /// XML declaration is malformed.
+    XmlDeclBad,
+
+    /// XML declaration has an encoding other than UTF-8.
+    ///
+    /// Name of the declared encoding is included.
+    XmlDeclEncoding(String),
+
+    /// Parser was on /, > or whitespace before yielding the tag name.
+    TagYieldMismatch,
+
+    /// Namespace prefix is not declared.
+    NsUnboundPrefix,
+
+    /// Qualified names must have a single colon between non-empty prefix and local name.
+    NsBadQName,
+
+    /// Xml and xmlns prefixes and namespaces cannot be redeclared.
+    NsReservedPrefix,
+
+    /// Prefixed namespace declarations cannot have an empty value.
+    NsEmptyPrefixUri,
+
+    /// Attributes with the same namespace and local name in this tag.
+    NsDuplicateAttribute,
+
+    /// No document parsed yet.
+    NoDocument,
+
+    /// Document must start with a StartTag element.
+    NoStartTag,
+
+    /// Start and end tags have different names.
+    ///
+    /// Names and locations of the both tags are included.
+    TagMismatch(Box<TagMismatch>),
+
+    /// Attribute name already used in this tag.
+    DuplicateAttribute,
+
+    /// Attributes cannot be set on CDATA elements.
+    CDataAttribute,
+
+    /// Child elements cannot be added on CDATA elements.
+    CDataChildren,
+
+    /// Null cursor cannot edit the document.
+    NullCursorEdit,
+
+    /// Root element cannot have siblings.
+    RootSibling,
+
+    /// Cannot make a document out of a CDATA element.
+    CDataToDocument,
+
+    /// Start tag is already closed.
+    StartTagClosed,
+
+    /// Name is not a valid XML name.
+    NameInvalid,
+
+    /// Root element cannot be moved or replaced.
+    RootMove,
+
+    /// Element cannot be moved into its own subtree.
+    MoveIntoSubtree,
+
+    /// Elements are in different documents.
+    DocumentMismatch,
+
+    /// CDATA elements cannot be renamed.
+    CDataRename,
+
+    /// Tag elements cannot have a character data value.
+    TagSetCData,
+
+    /// Document is shared by other cursors.
+    DocumentShared,
+}
+
+impl ParseErrorKind {
+    /// Returns a short description of the error kind.
+    pub fn description(&self) -> &'static str {
+        match self {
+            ParseErrorKind::Utf8InvalidContByte => "invalid UTF8 continuation byte",
+            ParseErrorKind::Utf8OverlongSequence => "overlong UTF8 sequence",
+            ParseErrorKind::Utf8InvalidPrefixByte => "invalid UTF8 prefix byte",
+            ParseErrorKind::CharInvalid => "invalid XML character",
+            ParseErrorKind::DocNoContent => "document has no root tag",
+            ParseErrorKind::DocOpenTags => "document has unclosed tags",
+            ParseErrorKind::DocOpenMarkup => "document epilog has unclosed PI or comment tag",
+            ParseErrorKind::DocCDataWithoutParent => {
+                "character data not allowed outside of the root tag"
+            }
+            ParseErrorKind::TagCloseWithoutOpen => "close tag without open",
+            ParseErrorKind::TagWhitespaceStart => "tag cannot start with whitespace",
+            ParseErrorKind::TagOutsideRoot => "tag cannot be outside of the root tag",
+            ParseErrorKind::TagEmptyName => "tag has no name",
+            ParseErrorKind::TagDoubleEnd => "end tag has standalone ending too",
+            ParseErrorKind::TagEndTagAttributes => "end tag cannot have attributes",
+            ParseErrorKind::TagEmptyTagMissingEnd => "empty element tags must end after the '/'",
+            ParseErrorKind::TagAttributeWithoutEqual => {
+                "tag attributes must have '=' before the value"
+            }
+            ParseErrorKind::TagAttributeWithoutQuote => {
+                "tag attribute value must be double or single quotes"
+            }
+            ParseErrorKind::TagAttributeBadName => {
+                "tag attribute names cannot have '/', '<' or '>'"
+            }
+            ParseErrorKind::TagAttributeBadValue => {
+                "tag attribute value cannot have '<' character without a reference"
+            }
+            ParseErrorKind::ReferenceInvalidDecimal => "non digit in decimal character reference",
+            ParseErrorKind::ReferenceInvalidHex => {
+                "non hex digit in hexadecimal character reference"
+            }
+            ParseErrorKind::ReferenceCustomEntity => {
+                "non-predefined entity references are not supported"
+            }
+            ParseErrorKind::CommentMissingDash => "comment tag should start with double dash",
+            ParseErrorKind::CommentMissingEnd => "comment tag should end after double dash",
+            ParseErrorKind::MarkupCDataSectionBadStart => {
+                "character data sections must start with '[CDATA['"
+            }
+            ParseErrorKind::MarkupDoctypeBadStart => "doctype must start with 'DOCTYPE '",
+            ParseErrorKind::MarkupCDataSectionOutsideRoot => {
+                "character data sections cannot be outside of the root tag"
+            }
+            ParseErrorKind::MarkupUnrecognized => {
+                "markup is not a comment, character data section, or document type declaration"
+            }
+            ParseErrorKind::PiMissingEnd => "processing instruction must end after closing the '?'",
+            ParseErrorKind::XmlDeclBad => "xml declaration is malformed",
+            ParseErrorKind::XmlDeclEncoding(_) => "only the UTF-8 encoding is supported",
+            ParseErrorKind::TagYieldMismatch => {
+                "parser was on /, > or whitespace before yielding the tag name"
+            }
+            ParseErrorKind::NsUnboundPrefix => "namespace prefix is not declared",
+            ParseErrorKind::NsBadQName => {
+                "qualified names must have a single colon between non-empty prefix and local name"
+            }
+            ParseErrorKind::NsReservedPrefix => {
+                "xml and xmlns prefixes and namespaces cannot be redeclared"
+            }
+            ParseErrorKind::NsEmptyPrefixUri => {
+                "prefixed namespace declarations cannot have an empty value"
+            }
+            ParseErrorKind::NsDuplicateAttribute => {
+                "attributes with the same namespace and local name in this tag"
+            }
+            ParseErrorKind::NoDocument => "no document parsed yet",
+            ParseErrorKind::NoStartTag => "document must start with a StartTag element",
+            ParseErrorKind::TagMismatch(_) => "start and end tags have different names",
+            ParseErrorKind::DuplicateAttribute => "attribute name already used in this tag",
+            ParseErrorKind::CDataAttribute => "attributes cannot be set on CDATA elements",
+            ParseErrorKind::CDataChildren => "child elements cannot be added on CDATA elements",
+            ParseErrorKind::NullCursorEdit => "null cursor cannot edit the document",
+            ParseErrorKind::RootSibling => "root element cannot have siblings",
+            ParseErrorKind::CDataToDocument => "cannot make a document out of a CDATA element",
+            ParseErrorKind::StartTagClosed => "start tag is already closed",
+            ParseErrorKind::NameInvalid => "name is not a valid XML name",
+            ParseErrorKind::RootMove => "root element cannot be moved or replaced",
+            ParseErrorKind::MoveIntoSubtree => "element cannot be moved into its own subtree",
+            ParseErrorKind::DocumentMismatch => "elements are in different documents",
+            ParseErrorKind::CDataRename => "CDATA elements cannot be renamed",
+            ParseErrorKind::TagSetCData => "tag elements cannot have a character data value",
+            ParseErrorKind::DocumentShared => "document is shared by other cursors",
+        }
+    }
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseErrorKind::TagMismatch(mismatch) => write!(
+                f,
+                "{}: '{}' and '{}'",
+                self.description(),
+                mismatch.open_name,
+                mismatch.close_name
+            ),
+            ParseErrorKind::XmlDeclEncoding(encoding) => {
+                write!(f, "{}: '{}' declared", self.description(), encoding)
+            }
+            _ => f.write_str(self.description()),
+        }
+    }
 }
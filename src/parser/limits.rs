@@ -0,0 +1,87 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+/// Resource limits applied by the parser to the input.
+///
+/// XML standard does not put any limits on the size of the names,
+/// values, or the nesting depth of the tags. When the input is coming
+/// from an untrusted source, such as a network peer, a degenerate
+/// document can make the parser and the application hold a lot of
+/// memory before detecting a problem. These limits allow rejecting
+/// such input early with a distinct [ParseError](crate::ParseError)
+/// variant for each limit.
+///
+/// The default value has no limits, which is the same behavior as
+/// a parser created without any limits.
+///
+/// # Examples
+///
+/// ```
+/// use iks::{ParseError, ParserLimits, SaxParser};
+///
+/// let limits = ParserLimits {
+///     max_depth: 2,
+///     ..ParserLimits::default()
+/// };
+/// let mut parser = SaxParser::with_limits(limits);
+/// let mut elements = parser.elements(b"<a><b><c/></b></a>");
+/// let mut result = Ok(());
+/// while let Some(element) = elements.next() {
+///     if let Err(err) = element {
+///         result = Err(err);
+///         break;
+///     }
+/// }
+/// assert_eq!(result, Err(ParseError::TooDeep));
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ParserLimits {
+    /// Maximum number of nested open tags.
+    pub max_depth: usize,
+
+    /// Maximum length of a tag or attribute name in bytes.
+    pub max_name_length: usize,
+
+    /// Maximum number of attributes in a single tag.
+    pub max_attributes: usize,
+
+    /// Maximum length of an attribute value in bytes after the
+    /// references are replaced.
+    pub max_attribute_value_length: usize,
+
+    /// Maximum length of a continuous character data in bytes.
+    ///
+    /// Character data between two tags counts as a single run even if
+    /// it is split by references, comments, or CDATA sections.
+    pub max_cdata_length: usize,
+
+    /// Maximum number of bytes in the whole input.
+    pub max_total_bytes: usize,
+}
+
+impl ParserLimits {
+    /// Creates a new limits value without any limits.
+    pub const fn unlimited() -> Self {
+        ParserLimits {
+            max_depth: usize::MAX,
+            max_name_length: usize::MAX,
+            max_attributes: usize::MAX,
+            max_attribute_value_length: usize::MAX,
+            max_cdata_length: usize::MAX,
+            max_total_bytes: usize::MAX,
+        }
+    }
+}
+
+impl Default for ParserLimits {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
@@ -42,6 +42,18 @@ impl Location {
         }
     }
 
+    pub(super) fn advance_plain(&mut self, count: usize) {
+        // Plain bytes never contain a newline character
+        self.bytes += count;
+        self.column += count;
+    }
+
+    pub(super) fn rewind_plain(&mut self, count: usize) {
+        // Plain bytes never contain a newline character
+        self.bytes -= count;
+        self.column -= count;
+    }
+
     pub(super) fn advance(&mut self, c: u8) {
         self.bytes += 1;
         if c == b'\n' {
@@ -8,12 +8,36 @@
 ** the License, or (at your option) any later version.
 */
 
+mod declaration;
 mod error;
+mod limits;
 mod location;
+mod namespace;
+mod reader;
+mod scan;
+mod span;
 
+pub use declaration::XmlDeclaration;
 pub use error::ParseError;
-use error::description;
+pub use error::ParseErrorKind;
+pub use error::TagMismatch;
+pub use limits::ParserLimits;
 pub use location::Location;
+pub use namespace::NamespaceElement;
+pub use namespace::NamespaceElements;
+pub use namespace::NamespaceResolver;
+#[cfg(feature = "xmpp")]
+pub(crate) use namespace::NamespaceStack;
+pub use namespace::QName;
+pub use namespace::XML_NS;
+pub use namespace::XMLNS_NS;
+pub(crate) use namespace::declared_prefix;
+pub(crate) use reader::DEFAULT_READ_BUFFER_SIZE;
+pub use reader::ReadError;
+pub use reader::SaxReader;
+pub(crate) use reader::read_some;
+pub use span::ElementSpan;
+pub use span::Span;
 
 /// An XML element returned from the parser.
 #[derive(Debug, Eq, PartialEq)]
@@ -59,6 +83,18 @@ pub enum SaxElement<'a> {
     /// substitute, collected content is flushed. The [DocumentParser](crate::DocumentParser)
     /// of iksemel automatically concatenates these parts to build a seamless document model.
     CData(&'a str),
+
+    /// A part of the text content inside a `<![CDATA[...]]>` section.
+    ///
+    /// This is only sent when the [text origins](SaxParser::set_text_origins)
+    /// mode is enabled. Otherwise these parts are sent as [CData](SaxElement::CData).
+    CDataSection(&'a str),
+
+    /// The replacement text of a character or entity reference in the text content.
+    ///
+    /// This is only sent when the [text origins](SaxParser::set_text_origins)
+    /// mode is enabled. Otherwise these parts are sent as [CData](SaxElement::CData).
+    Reference(&'a str),
 }
 
 /// An iterator over the elements of an XML text.
@@ -103,6 +139,28 @@ impl<'a> SaxElements<'a> {
             }
         }
     }
+
+    /// Returns the next element parsed from the XML text with its source span.
+    pub fn next_spanned(&mut self) -> Option<Result<(SaxElement<'_>, ElementSpan), ParseError>> {
+        if self.bytes_parsed == self.bytes.len() {
+            None
+        } else {
+            match self
+                .parser
+                .parse_bytes_spanned(&self.bytes[self.bytes_parsed..])
+            {
+                Ok(Some((element, bytes, span))) => {
+                    self.bytes_parsed += bytes;
+                    Some(Ok((element, span)))
+                }
+                Ok(None) => {
+                    self.bytes_parsed = self.bytes.len();
+                    None
+                }
+                Err(err) => Some(Err(err)),
+            }
+        }
+    }
 }
 
 /// SAX (Simple API for XML) based XML parser.
@@ -113,13 +171,18 @@ impl<'a> SaxElements<'a> {
 ///
 /// # SAX Limitations
 ///
-/// This kind of parsing is extremely memory efficient, but it cannot
-/// do certain validations such as checking tag mismatches, or cannot
-/// run complex queries on the document without storing the information
-/// in external memory. The [DocumentParser](crate::DocumentParser) does
-/// all of that by building a [Document](crate::Document) tree in memory
-/// from the SAX events, and should be preferred when you don't have
-/// extreme memory constraints.
+/// This kind of parsing is extremely memory efficient, but it does not
+/// check tag mismatches unless the [strict](SaxParser::set_strict) mode
+/// is enabled, and cannot run complex queries on the document without
+/// storing the information in external memory. The
+/// [DocumentParser](crate::DocumentParser) does all of that by building
+/// a [Document](crate::Document) tree in memory from the SAX events, and
+/// should be preferred when you don't have extreme memory constraints.
+///
+/// Tag names, attributes, and character data are returned as slices
+/// of the given input bytes whenever possible. Only the tokens which
+/// straddle the input blocks or contain references are collected in
+/// an internal buffer.
 ///
 /// # Iksemel Limitations
 ///
@@ -199,11 +262,37 @@ pub struct SaxParser {
     char_ref_buffer: [u8; 4],
     is_value_ref: bool,
     location: Location,
+    limits: ParserLimits,
+    attribute_count: usize,
+    cdata_run: usize,
+    span: ElementSpan,
+    mark: Location,
+    text_start: Location,
+    name_start: Location,
+    name_end: Location,
+    value_start: Location,
+    uni_start: Location,
+    recovery: bool,
+    recover_tag: bool,
+    diagnostics: Vec<ParseError>,
+    strict: bool,
+    normalization: bool,
+    after_cr: bool,
+    open_names: Vec<u8>,
+    open_tags: Vec<(usize, Location)>,
+    attribute_names: Vec<u8>,
+    attribute_starts: Vec<usize>,
+    declaration: Option<XmlDeclaration>,
+    declaration_start: usize,
+    in_declaration: bool,
+    misplaced_declaration: bool,
+    text_origins: bool,
 }
 
 #[derive(Eq, PartialEq)]
 enum State {
     Prolog,
+    Bom,
     TagStart,
     PI,
     PIEnd,
@@ -248,6 +337,7 @@ enum State {
     HexCharReference,
     Entity,
     Epilog,
+    Recover,
 }
 
 const INITIAL_BUFFER_CAPACITY: usize = 128;
@@ -260,26 +350,51 @@ macro_rules! whitespace {
     };
 }
 
-fn is_valid_xml_char(c: u32) -> bool {
+fn is_declaration_target(bytes: &[u8]) -> bool {
+    matches!(
+        bytes,
+        [b'x', b'm', b'l'] | [b'x', b'm', b'l', whitespace!(), ..]
+    )
+}
+
+pub(crate) fn is_valid_xml_char(c: u32) -> bool {
     matches!(c, 0x09 | 0x0a | 0x0d | 0x20..=0xd7ff | 0xe000..=0xfffd | 0x10000..=0x10_ffff)
 }
 
+macro_rules! bad_xml {
+    ($self:ident, $error:expr) => {
+        let error = $error;
+        if !$self.recovery {
+            return Err(error);
+        }
+        // Same byte is processed again in the recover state
+        $self.recover(error)?;
+        continue;
+    };
+}
+
 macro_rules! xml_error {
-    ($a:ident) => {
-        return Err(ParseError::BadXml(description::$a));
+    ($self:ident, $kind:ident) => {
+        bad_xml!(
+            $self,
+            ParseError::BadXml {
+                kind: ParseErrorKind::$kind,
+                location: Some($self.location),
+            }
+        );
     };
 }
 
 macro_rules! yield_element {
     ($self:ident, $c:ident, $pos:ident, $elem:expr) => {
         $self.location.advance($c);
-        return Ok(Some(($elem, $pos + 1)));
+        return Ok(Some(($elem, $pos + 1, $self.span)));
     };
 }
 
 macro_rules! yield_element_inplace {
-    ($pos:ident, $elem:expr) => {
-        return Ok(Some(($elem, $pos)));
+    ($self:ident, $pos:ident, $elem:expr) => {
+        return Ok(Some(($elem, $pos, $self.span)));
     };
 }
 
@@ -288,6 +403,14 @@ impl SaxParser {
     ///
     /// The instance can be reused for multiple documents with the [reset()](SaxParser::reset) method.
     pub fn new() -> SaxParser {
+        SaxParser::with_limits(ParserLimits::unlimited())
+    }
+
+    /// Creates a new SAX parser instance which enforces the given resource limits.
+    ///
+    /// Limits are kept when the parser is [reset](SaxParser::reset) for
+    /// another document.
+    pub fn with_limits(limits: ParserLimits) -> SaxParser {
         SaxParser {
             state: State::Prolog,
             uni_len: 0,
@@ -304,6 +427,31 @@ impl SaxParser {
             char_ref_buffer: [0; 4],
             is_value_ref: false,
             location: Location::new(),
+            limits,
+            attribute_count: 0,
+            cdata_run: 0,
+            span: ElementSpan::default(),
+            mark: Location::new(),
+            text_start: Location::new(),
+            name_start: Location::new(),
+            name_end: Location::new(),
+            value_start: Location::new(),
+            uni_start: Location::new(),
+            recovery: false,
+            recover_tag: false,
+            diagnostics: Vec::new(),
+            strict: false,
+            normalization: true,
+            after_cr: false,
+            open_names: Vec::new(),
+            open_tags: Vec::new(),
+            attribute_names: Vec::new(),
+            attribute_starts: Vec::new(),
+            declaration: None,
+            declaration_start: 0,
+            in_declaration: false,
+            misplaced_declaration: false,
+            text_origins: false,
         }
     }
 
@@ -323,6 +471,206 @@ impl SaxParser {
         self.char_ref_value = 0;
         self.is_value_ref = false;
         self.location = Location::new();
+        self.attribute_count = 0;
+        self.cdata_run = 0;
+        self.span = ElementSpan::default();
+        self.mark = Location::new();
+        self.text_start = Location::new();
+        self.name_start = Location::new();
+        self.name_end = Location::new();
+        self.value_start = Location::new();
+        self.uni_start = Location::new();
+        self.recover_tag = false;
+        self.after_cr = false;
+        self.diagnostics.clear();
+        self.open_names.clear();
+        self.open_tags.clear();
+        self.attribute_names.clear();
+        self.attribute_starts.clear();
+        self.declaration = None;
+        self.declaration_start = 0;
+        self.in_declaration = false;
+        self.misplaced_declaration = false;
+    }
+
+    /// Returns the resource limits enforced by this parser.
+    pub fn limits(&self) -> ParserLimits {
+        self.limits
+    }
+
+    /// Enables or disables the error recovery mode.
+    ///
+    /// In the recovery mode, syntax errors do not stop the parser. Each
+    /// [BadXml](ParseError::BadXml) error is recorded as a diagnostic,
+    /// the input is skipped until the next '<' or '>' character, and
+    /// the parsing continues from there. A start tag interrupted by an
+    /// error is completed with a [StartTagContent](SaxElement::StartTagContent)
+    /// element, so the elements still have a sensible structure.
+    ///
+    /// Resource limit and memory errors are always returned. The mode
+    /// is kept when the parser is [reset](SaxParser::reset).
+    pub fn set_recovery(&mut self, recovery: bool) {
+        self.recovery = recovery;
+    }
+
+    /// Returns true if the error recovery mode is enabled.
+    pub fn recovery(&self) -> bool {
+        self.recovery
+    }
+
+    /// Returns the syntax errors recorded in the recovery mode.
+    pub fn diagnostics(&self) -> &[ParseError] {
+        &self.diagnostics
+    }
+
+    /// Returns the recorded syntax errors and clears them from the parser.
+    pub fn take_diagnostics(&mut self) -> Vec<ParseError> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// Enables or disables the well-formedness checks.
+    ///
+    /// In the strict mode, the parser keeps the names of the open tags
+    /// and the attributes of the current tag, and checks that the end
+    /// tags match the start tags, and no attribute is repeated in a tag.
+    /// These are reported as [TagMismatch](ParseErrorKind::TagMismatch)
+    /// and [DuplicateAttribute](ParseErrorKind::DuplicateAttribute) errors.
+    ///
+    /// Memory used for the checks grows with the nesting depth, and the
+    /// number of attributes in a tag. Use the [ParserLimits] to bound it
+    /// for the untrusted input. The mode is kept when the parser is
+    /// [reset](SaxParser::reset).
+    ///
+    /// In the recovery mode, a mismatched end tag closes the open tags
+    /// up to the nearest one with the same name, or it is skipped if
+    /// there is no such tag. A repeated attribute is skipped too.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Returns true if the well-formedness checks are enabled.
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Enables or disables the line ending and attribute value normalization.
+    ///
+    /// Normalization is enabled by default and follows the XML 1.0
+    /// specification. Each "\r\n" pair and lone "\r" character in the
+    /// character data is returned as a single "\n" character. In the
+    /// attribute values, tab and line ending characters are replaced
+    /// with a space character, where a "\r\n" pair becomes one space.
+    /// Characters written as character references are not changed.
+    ///
+    /// When it is disabled, these characters are returned as they are
+    /// in the input. The mode is kept when the parser is
+    /// [reset](SaxParser::reset).
+    pub fn set_normalization(&mut self, normalization: bool) {
+        self.normalization = normalization;
+    }
+
+    /// Returns true if the line ending and attribute value normalization is enabled.
+    pub fn normalization(&self) -> bool {
+        self.normalization
+    }
+
+    /// Enables or disables the text origins mode.
+    ///
+    /// By default, all the text content is sent as [CData](SaxElement::CData)
+    /// elements. In the text origins mode, the text inside the character data
+    /// sections is sent as [CDataSection](SaxElement::CDataSection) elements,
+    /// and the replacement text of the references is sent as
+    /// [Reference](SaxElement::Reference) elements, so that the text can be
+    /// written back in its original form. The mode is kept when the parser
+    /// is [reset](SaxParser::reset).
+    pub fn set_text_origins(&mut self, text_origins: bool) {
+        self.text_origins = text_origins;
+    }
+
+    /// Returns true if the text origins mode is enabled.
+    pub fn text_origins(&self) -> bool {
+        self.text_origins
+    }
+
+    /// Returns the XML declaration of the document if it is parsed.
+    ///
+    /// The declaration is only allowed at the very beginning of the
+    /// document, optionally after a UTF-8 byte order mark, and is
+    /// rejected with an [XmlDeclBad](ParseErrorKind::XmlDeclBad) error
+    /// anywhere else. Declarations with an encoding other than UTF-8
+    /// are rejected with an
+    /// [XmlDeclEncoding](ParseErrorKind::XmlDeclEncoding) error.
+    pub fn xml_declaration(&self) -> Option<&XmlDeclaration> {
+        self.declaration.as_ref()
+    }
+
+    fn text_element<'a>(&self, s: &'a str) -> SaxElement<'a> {
+        if self.text_origins && self.state == State::CDataSectionBody {
+            SaxElement::CDataSection(s)
+        } else {
+            SaxElement::CData(s)
+        }
+    }
+
+    fn section_element<'a>(&self, s: &'a str) -> SaxElement<'a> {
+        if self.text_origins {
+            SaxElement::CDataSection(s)
+        } else {
+            SaxElement::CData(s)
+        }
+    }
+
+    fn reference_element<'a>(&self, s: &'a str) -> SaxElement<'a> {
+        if self.text_origins {
+            SaxElement::Reference(s)
+        } else {
+            SaxElement::CData(s)
+        }
+    }
+
+    fn push_diagnostic(&mut self, error: ParseError) -> Result<(), ParseError> {
+        if self.diagnostics.try_reserve(1).is_err() {
+            return Err(ParseError::NoMemory);
+        }
+        self.diagnostics.push(error);
+        Ok(())
+    }
+
+    fn recover(&mut self, error: ParseError) -> Result<(), ParseError> {
+        self.push_diagnostic(error)?;
+        match self.state {
+            State::TagName if !self.is_end_tag => {
+                // Start tag is not yielded yet, so it is dropped
+                self.depth -= 1;
+            }
+            State::TagNameContinue | State::EndTagWhitespace if self.is_end_tag => {
+                self.depth -= 1;
+            }
+            State::EmptyTagEnd => {
+                self.depth -= 1;
+            }
+            State::TagNameContinue
+            | State::AttributeWhitespace
+            | State::AttributeName
+            | State::AttributeEq
+            | State::AttributeValueStart
+            | State::AttributeValue
+            | State::AttributeValueContinue => {
+                self.recover_tag = true;
+            }
+            State::Reference
+            | State::Entity
+            | State::CharReference
+            | State::CharReferenceBody
+            | State::HexCharReference => {
+                self.recover_tag = self.is_value_ref;
+            }
+            _ => (),
+        }
+        self.uni_left = 0;
+        self.buffer.clear();
+        self.state = State::Recover;
+        Ok(())
     }
 
     fn extend_buffer(&mut self, bytes: &[u8]) -> Result<(), ParseError> {
@@ -338,6 +686,172 @@ impl SaxParser {
         Ok(())
     }
 
+    fn check_name(&self, len: usize) -> Result<(), ParseError> {
+        if self.buffer.len() + len > self.limits.max_name_length {
+            return Err(ParseError::NameTooLong);
+        }
+        Ok(())
+    }
+
+    fn extend_name(&mut self, bytes: &[u8]) -> Result<(), ParseError> {
+        self.check_name(bytes.len())?;
+        self.extend_buffer(bytes)
+    }
+
+    fn check_value(&self, len: usize) -> Result<(), ParseError> {
+        let value_len = self.buffer.len() - self.value_pos;
+        if value_len + len > self.limits.max_attribute_value_length {
+            return Err(ParseError::AttributeValueTooLong);
+        }
+        Ok(())
+    }
+
+    fn extend_value(&mut self, bytes: &[u8]) -> Result<(), ParseError> {
+        self.check_value(bytes.len())?;
+        self.extend_buffer(bytes)
+    }
+
+    // Copies an attribute name left in the input into the buffer, when
+    // the attribute value cannot be returned from the input as is.
+    fn spill_name(
+        &mut self,
+        bytes: &[u8],
+        name_span: &mut Option<(usize, usize)>,
+    ) -> Result<(), ParseError> {
+        if let Some((name_back, name_end)) = name_span.take() {
+            self.extend_buffer(&bytes[name_back..name_end])?;
+            self.value_pos = self.buffer.len();
+        }
+        Ok(())
+    }
+
+    fn open_name(&self, index: usize) -> &[u8] {
+        let end = match self.open_tags.get(index + 1) {
+            Some((start, _)) => *start,
+            None => self.open_names.len(),
+        };
+        &self.open_names[self.open_tags[index].0..end]
+    }
+
+    // Tag name is in the buffer if it is not empty, otherwise in the input.
+    fn push_open_tag(&mut self, input_name: &[u8]) -> Result<(), ParseError> {
+        let name = if self.buffer.is_empty() {
+            input_name
+        } else {
+            &self.buffer
+        };
+        if self.open_names.try_reserve(name.len()).is_err()
+            || self.open_tags.try_reserve(1).is_err()
+        {
+            return Err(ParseError::NoMemory);
+        }
+        self.open_tags.push((self.open_names.len(), self.location));
+        self.open_names.extend_from_slice(name);
+        self.attribute_names.clear();
+        self.attribute_starts.clear();
+        Ok(())
+    }
+
+    fn truncate_open_tags(&mut self, index: usize) {
+        if let Some((start, _)) = self.open_tags.get(index) {
+            self.open_names.truncate(*start);
+            self.open_tags.truncate(index);
+        }
+    }
+
+    fn pop_open_tag(&mut self) {
+        if let Some(top) = self.open_tags.len().checked_sub(1) {
+            self.truncate_open_tags(top);
+        }
+    }
+
+    // Returns the mismatch error if the end tag cannot be closed.
+    fn close_open_tag(&mut self, input_name: &[u8]) -> Result<Option<ParseError>, ParseError> {
+        let name = if self.buffer.is_empty() {
+            input_name
+        } else {
+            &self.buffer
+        };
+        let Some(top) = self.open_tags.len().checked_sub(1) else {
+            // Strict mode is enabled in the middle of the document
+            return Ok(Some(ParseError::BadXml {
+                kind: ParseErrorKind::TagCloseWithoutOpen,
+                location: Some(self.location),
+            }));
+        };
+        let index = (0..=top).rev().find(|index| self.open_name(*index) == name);
+        if index == Some(top) {
+            self.truncate_open_tags(top);
+            return Ok(None);
+        }
+        let error = ParseError::BadXml {
+            kind: ParseErrorKind::TagMismatch(Box::new(TagMismatch {
+                open_name: String::from_utf8_lossy(self.open_name(top)).to_string(),
+                open_location: Some(self.open_tags[top].1),
+                close_name: String::from_utf8_lossy(name).to_string(),
+                close_location: Some(self.location),
+            })),
+            location: Some(self.location),
+        };
+        match index {
+            Some(index) if self.recovery => {
+                // Tags in between are closed by this end tag
+                self.push_diagnostic(error)?;
+                self.depth -= top - index;
+                self.truncate_open_tags(index);
+                Ok(None)
+            }
+            _ => Ok(Some(error)),
+        }
+    }
+
+    // Returns false if the attribute is already used in the current tag.
+    fn push_attribute(
+        &mut self,
+        bytes: &[u8],
+        name_span: Option<(usize, usize)>,
+    ) -> Result<bool, ParseError> {
+        let name = match name_span {
+            Some((name_back, name_end)) => &bytes[name_back..name_end],
+            None => &self.buffer[0..self.value_pos],
+        };
+        for (index, start) in self.attribute_starts.iter().enumerate() {
+            let end = match self.attribute_starts.get(index + 1) {
+                Some(end) => *end,
+                None => self.attribute_names.len(),
+            };
+            if &self.attribute_names[*start..end] == name {
+                return Ok(false);
+            }
+        }
+        if self.attribute_names.try_reserve(name.len()).is_err()
+            || self.attribute_starts.try_reserve(1).is_err()
+        {
+            return Err(ParseError::NoMemory);
+        }
+        self.attribute_starts.push(self.attribute_names.len());
+        self.attribute_names.extend_from_slice(name);
+        Ok(true)
+    }
+
+    fn count_cdata(&mut self, len: usize) -> Result<(), ParseError> {
+        self.cdata_run = self.cdata_run.saturating_add(len);
+        if self.cdata_run > self.limits.max_cdata_length {
+            return Err(ParseError::CDataTooLong);
+        }
+        Ok(())
+    }
+
+    fn location_after(&self, c: u8) -> Location {
+        let mut location = self.location;
+        location.advance(c);
+        location
+    }
+
+    fn set_span(&mut self, start: Location, end: Location) {
+        self.span = ElementSpan::new(Span::new(start, end));
+    }
+
     fn u32_to_cdata(&mut self) -> usize {
         const DATA_MASK: u32 = 0b0011_1111;
         const DATA_PREFIX: u8 = 0b1000_0000;
@@ -377,19 +891,30 @@ impl SaxParser {
     ///
     /// A completed document should have a root tag and should not have any
     /// unfinished XML constructs, such as open comments or markup.
+    ///
+    /// Errors are returned from this method in the recovery mode too,
+    /// except the unfinished constructs which are already recorded as
+    /// diagnostics.
     pub fn parse_finish(&self) -> Result<(), ParseError> {
         if !self.seen_content {
-            xml_error!(DOC_NO_CONTENT);
+            return self.finish_error(ParseErrorKind::DocNoContent);
         }
         if self.depth > 0 {
-            xml_error!(DOC_OPEN_TAGS);
+            return self.finish_error(ParseErrorKind::DocOpenTags);
         }
-        if self.state != State::Epilog {
-            xml_error!(DOC_OPEN_MARKUP);
+        if !matches!(self.state, State::Epilog | State::Recover) {
+            return self.finish_error(ParseErrorKind::DocOpenMarkup);
         }
         Ok(())
     }
 
+    fn finish_error(&self, kind: ParseErrorKind) -> Result<(), ParseError> {
+        Err(ParseError::BadXml {
+            kind,
+            location: Some(self.location),
+        })
+    }
+
     /// Returns an iterator over the elements parsed from the XML text.
     pub fn elements<'a>(&'a mut self, bytes: &'a [u8]) -> SaxElements<'a> {
         SaxElements::new(self, bytes)
@@ -400,16 +925,75 @@ impl SaxParser {
         &'a mut self,
         bytes: &'a [u8],
     ) -> Result<Option<(SaxElement<'a>, usize)>, ParseError> {
+        self.parse_bytes_spanned(bytes)
+            .map(|result| result.map(|(element, bytes, _)| (element, bytes)))
+    }
+
+    /// Parses given XML bytes and returns the source span of the element.
+    ///
+    /// This is same as the [parse_bytes()](SaxParser::parse_bytes) method
+    /// except that the byte ranges of the returned element in the input
+    /// stream are returned too.
+    pub fn parse_bytes_spanned<'a>(
+        &'a mut self,
+        bytes: &'a [u8],
+    ) -> Result<Option<(SaxElement<'a>, usize, ElementSpan)>, ParseError> {
         let mut pos: usize = 0;
         let mut back: usize = 0;
+        // Attribute name in the input when it is not copied to the buffer
+        let mut name_span: Option<(usize, usize)> = None;
+
+        if matches!(self.state, State::CData | State::CDataSectionBody) {
+            // Previous text is already sent, a new one starts here
+            self.text_start = self.location;
+        }
 
         while pos < bytes.len() {
+            if self.location.bytes >= self.limits.max_total_bytes {
+                return Err(ParseError::InputTooLong);
+            }
+            if self.after_cr {
+                // Previous byte was a carriage return
+                self.after_cr = false;
+                if bytes[pos] == b'\n' {
+                    if self.state == State::AttributeValue {
+                        // Pair is already replaced with a space
+                        back = pos + 1;
+                        self.location.advance(b'\n');
+                        pos += 1;
+                        continue;
+                    }
+                } else if self.state != State::AttributeValue {
+                    self.count_cdata(1)?;
+                    self.set_span(self.mark, self.location);
+                    yield_element_inplace!(self, pos, self.text_element("\n"));
+                }
+            }
+            if self.uni_left == 0 {
+                let stops = match self.state {
+                    State::CData => Some([b'<', b'&', b'<']),
+                    State::AttributeValue if self.is_quot_value => Some([b'\'', b'&', b'<']),
+                    State::AttributeValue => Some([b'"', b'&', b'<']),
+                    State::CommentBody => Some([b'-', b'-', b'-']),
+                    State::Recover => Some([b'<', b'>', b'<']),
+                    _ => None,
+                };
+                if let Some(stops) = stops {
+                    let count = scan::plain_prefix(&bytes[pos..], stops)
+                        .min(self.limits.max_total_bytes - self.location.bytes);
+                    if count > 0 {
+                        pos += count;
+                        self.location.advance_plain(count);
+                        continue;
+                    }
+                }
+            }
             let mut redo: bool = false;
             let c = bytes[pos];
 
             if self.uni_left > 0 {
                 if c & 0xc0 != 0x80 {
-                    xml_error!(UTF8_INVALID_CONT_BYTE);
+                    xml_error!(self, Utf8InvalidContByte);
                 }
                 self.uni_char <<= 6;
                 self.uni_char += c as u32 & 0x3f;
@@ -421,10 +1005,10 @@ impl SaxParser {
                         || (self.uni_len == 3 && self.uni_char <= 0x7ff)
                         || (self.uni_len == 4 && self.uni_char <= 0xffff)
                     {
-                        xml_error!(UTF8_OVERLONG_SEQUENCE);
+                        xml_error!(self, Utf8OverlongSequence);
                     }
                     if !is_valid_xml_char(self.uni_char) {
-                        xml_error!(CHAR_INVALID);
+                        xml_error!(self, CharInvalid);
                     }
                     if pos < (self.uni_len as usize)
                         && (self.state == State::CData || self.state == State::CDataSectionBody)
@@ -432,14 +1016,18 @@ impl SaxParser {
                         // Previously unsent at the end of the buffer
                         self.char_ref_value = self.uni_char;
                         let size = self.u32_to_cdata();
+                        self.count_cdata(size)?;
+                        self.set_span(self.uni_start, self.location_after(c));
                         let s = unsafe {
                             std::str::from_utf8_unchecked(&self.char_ref_buffer[0..size])
                         };
-                        self.state = State::CData;
-                        yield_element!(self, c, pos, SaxElement::CData(s));
+                        yield_element!(self, c, pos, self.text_element(s));
                     }
                 }
+            } else if self.state == State::Recover {
+                // Skipped bytes are not validated
             } else if c & 0x80 == 0x80 {
+                self.uni_start = self.location;
                 if c & 0x60 == 0x40 {
                     self.uni_len = 2;
                     self.uni_left = 1;
@@ -453,46 +1041,75 @@ impl SaxParser {
                     self.uni_left = 3;
                     self.uni_char = c as u32 & 0x07;
                 } else {
-                    xml_error!(UTF8_INVALID_PREFIX_BYTE);
+                    xml_error!(self, Utf8InvalidPrefixByte);
                 }
             } else if c < 0x20 && (c != 0x09 && c != 0x0a && c != 0x0d) {
-                xml_error!(CHAR_INVALID);
+                xml_error!(self, CharInvalid);
             }
 
             match self.state {
                 State::Prolog => match c {
-                    b'<' => self.state = State::TagStart,
+                    b'<' => {
+                        self.mark = self.location;
+                        self.state = State::TagStart;
+                    }
                     whitespace!() => (),
+                    0xef if self.location.bytes == 0 => {
+                        // Possible byte order mark
+                        self.state = State::Bom;
+                    }
                     _ => {
-                        xml_error!(DOC_CDATA_WITHOUT_PARENT);
+                        xml_error!(self, DocCDataWithoutParent);
                     }
                 },
 
+                State::Bom => {
+                    if self.uni_left == 0 {
+                        if self.uni_char != 0xfeff {
+                            xml_error!(self, DocCDataWithoutParent);
+                        }
+                        self.declaration_start = self.location.bytes + 1;
+                        self.state = State::Prolog;
+                    }
+                }
+
                 State::TagStart => match c {
                     b'!' => {
                         self.state = State::Markup;
                     }
-                    b'?' => self.state = State::PI,
+                    b'?' => {
+                        // The target is checked for every instruction since
+                        // a declaration is only allowed at the very start
+                        self.in_declaration = true;
+                        self.misplaced_declaration = self.mark.bytes != self.declaration_start;
+                        self.state = State::PI;
+                    }
                     b'/' => {
                         if self.depth == 0 {
-                            xml_error!(TAG_CLOSE_WITHOUT_OPEN);
+                            xml_error!(self, TagCloseWithoutOpen);
                         }
                         back = pos + 1;
+                        self.name_start = self.location_after(c);
                         self.is_end_tag = true;
                         self.state = State::TagName;
                     }
                     whitespace!() => {
-                        xml_error!(TAG_WHITESPACE_START);
+                        xml_error!(self, TagWhitespaceStart);
                     }
                     b'>' => {
-                        xml_error!(TAG_EMPTY_NAME);
+                        xml_error!(self, TagEmptyName);
                     }
                     _ => {
                         if self.depth == 0 && self.seen_content {
-                            xml_error!(TAG_OUTSIDE_ROOT);
+                            xml_error!(self, TagOutsideRoot);
+                        }
+                        if self.depth >= self.limits.max_depth {
+                            return Err(ParseError::TooDeep);
                         }
                         self.depth += 1;
+                        self.attribute_count = 0;
                         back = pos;
+                        self.name_start = self.location;
                         self.is_end_tag = false;
                         self.seen_content = true;
                         self.state = State::TagName;
@@ -503,62 +1120,62 @@ impl SaxParser {
                     b'-' => self.state = State::CommentStart,
                     b'[' => {
                         if self.depth == 0 {
-                            xml_error!(MARKUP_CDATA_SECTION_OUTSIDE_ROOT);
+                            xml_error!(self, MarkupCDataSectionOutsideRoot);
                         }
                         self.state = State::CDataSectionC;
                     }
                     b'D' => self.state = State::DoctypeDO,
                     _ => {
-                        xml_error!(MARKUP_UNRECOGNIZED);
+                        xml_error!(self, MarkupUnrecognized);
                     }
                 },
 
                 State::DoctypeDO => match c {
                     b'O' => self.state = State::DoctypeDOC,
                     _ => {
-                        xml_error!(MARKUP_DOCTYPE_BAD_START);
+                        xml_error!(self, MarkupDoctypeBadStart);
                     }
                 },
 
                 State::DoctypeDOC => match c {
                     b'C' => self.state = State::DoctypeDOCT,
                     _ => {
-                        xml_error!(MARKUP_DOCTYPE_BAD_START);
+                        xml_error!(self, MarkupDoctypeBadStart);
                     }
                 },
 
                 State::DoctypeDOCT => match c {
                     b'T' => self.state = State::DoctypeDOCTY,
                     _ => {
-                        xml_error!(MARKUP_DOCTYPE_BAD_START);
+                        xml_error!(self, MarkupDoctypeBadStart);
                     }
                 },
 
                 State::DoctypeDOCTY => match c {
                     b'Y' => self.state = State::DoctypeDOCTYP,
                     _ => {
-                        xml_error!(MARKUP_DOCTYPE_BAD_START);
+                        xml_error!(self, MarkupDoctypeBadStart);
                     }
                 },
 
                 State::DoctypeDOCTYP => match c {
                     b'P' => self.state = State::DoctypeDOCTYPE,
                     _ => {
-                        xml_error!(MARKUP_DOCTYPE_BAD_START);
+                        xml_error!(self, MarkupDoctypeBadStart);
                     }
                 },
 
                 State::DoctypeDOCTYPE => match c {
                     b'E' => self.state = State::DoctypeWhitespace,
                     _ => {
-                        xml_error!(MARKUP_DOCTYPE_BAD_START);
+                        xml_error!(self, MarkupDoctypeBadStart);
                     }
                 },
 
                 State::DoctypeWhitespace => match c {
                     whitespace!() => self.state = State::DoctypeSkip,
                     _ => {
-                        xml_error!(MARKUP_DOCTYPE_BAD_START);
+                        xml_error!(self, MarkupDoctypeBadStart);
                     }
                 },
 
@@ -576,82 +1193,109 @@ impl SaxParser {
 
                 State::CDataSectionC => {
                     if c != b'C' {
-                        xml_error!(MARKUP_CDATA_SECTION_BAD_START);
+                        xml_error!(self, MarkupCDataSectionBadStart);
                     }
                     self.state = State::CDataSectionCD;
                 }
 
                 State::CDataSectionCD => {
                     if c != b'D' {
-                        xml_error!(MARKUP_CDATA_SECTION_BAD_START);
+                        xml_error!(self, MarkupCDataSectionBadStart);
                     }
                     self.state = State::CDataSectionCDA;
                 }
 
                 State::CDataSectionCDA => {
                     if c != b'A' {
-                        xml_error!(MARKUP_CDATA_SECTION_BAD_START);
+                        xml_error!(self, MarkupCDataSectionBadStart);
                     }
                     self.state = State::CDataSectionCDAT;
                 }
 
                 State::CDataSectionCDAT => {
                     if c != b'T' {
-                        xml_error!(MARKUP_CDATA_SECTION_BAD_START);
+                        xml_error!(self, MarkupCDataSectionBadStart);
                     }
                     self.state = State::CDataSectionCDATA;
                 }
 
                 State::CDataSectionCDATA => {
                     if c != b'A' {
-                        xml_error!(MARKUP_CDATA_SECTION_BAD_START);
+                        xml_error!(self, MarkupCDataSectionBadStart);
                     }
                     self.state = State::CDataSectionCDATAb;
                 }
 
                 State::CDataSectionCDATAb => {
                     if c != b'[' {
-                        xml_error!(MARKUP_CDATA_SECTION_BAD_START);
+                        xml_error!(self, MarkupCDataSectionBadStart);
                     }
                     back = pos + 1;
+                    self.text_start = self.location_after(c);
                     self.state = State::CDataSectionBody;
                 }
 
                 State::CDataSectionBody => {
                     if c == b']' {
+                        self.mark = self.location;
                         self.state = State::CDataSectionMaybeEnd;
                         if back < pos {
+                            self.count_cdata(pos - back)?;
                             let s = unsafe { std::str::from_utf8_unchecked(&bytes[back..pos]) };
-                            yield_element!(self, c, pos, SaxElement::CData(s));
+                            self.set_span(self.text_start, self.location);
+                            yield_element!(self, c, pos, self.section_element(s));
+                        }
+                    } else if c == b'\r' && self.normalization {
+                        // Carriage return is dropped, and replaced with a
+                        // newline if it is not followed by one
+                        self.mark = self.location;
+                        self.after_cr = true;
+                        if back < pos {
+                            self.count_cdata(pos - back)?;
+                            let s = unsafe { std::str::from_utf8_unchecked(&bytes[back..pos]) };
+                            self.set_span(self.text_start, self.location);
+                            yield_element!(self, c, pos, self.section_element(s));
                         }
+                        back = pos + 1;
+                        self.text_start = self.location_after(c);
                     }
                 }
 
                 State::CDataSectionMaybeEnd => match c {
                     b']' => self.state = State::CDataSectionMaybeEnd2,
                     _ => {
+                        self.count_cdata(1)?;
                         self.state = State::CDataSectionBody;
-                        yield_element_inplace!(pos, SaxElement::CData("]"));
+                        self.set_span(self.mark, self.location);
+                        yield_element_inplace!(self, pos, self.section_element("]"));
                     }
                 },
 
                 State::CDataSectionMaybeEnd2 => match c {
                     b'>' => {
                         back = pos + 1;
+                        self.text_start = self.location_after(c);
                         self.state = State::CData;
                     }
                     b']' => {
-                        yield_element!(self, c, pos, SaxElement::CData("]"));
+                        // Only the first one of the last three brackets is sent
+                        self.count_cdata(1)?;
+                        let start = self.mark;
+                        self.mark.advance(b']');
+                        self.set_span(start, self.mark);
+                        yield_element!(self, c, pos, self.section_element("]"));
                     }
                     _ => {
+                        self.count_cdata(2)?;
                         self.state = State::CDataSectionBody;
-                        yield_element_inplace!(pos, SaxElement::CData("]]"));
+                        self.set_span(self.mark, self.location);
+                        yield_element_inplace!(self, pos, self.section_element("]]"));
                     }
                 },
 
                 State::CommentStart => {
                     if c != b'-' {
-                        xml_error!(COMMENT_MISSING_DASH);
+                        xml_error!(self, CommentMissingDash);
                     }
                     self.state = State::CommentBody;
                 }
@@ -669,10 +1313,11 @@ impl SaxParser {
 
                 State::CommentEnd => {
                     if c != b'>' {
-                        xml_error!(COMMENT_MISSING_END);
+                        xml_error!(self, CommentMissingEnd);
                     }
                     if self.depth > 0 {
                         back = pos + 1;
+                        self.text_start = self.location_after(c);
                         self.state = State::CData;
                     } else if self.seen_content {
                         self.state = State::Epilog;
@@ -684,14 +1329,58 @@ impl SaxParser {
                 State::PI => {
                     if c == b'?' {
                         self.state = State::PIEnd;
+                    } else if self.in_declaration {
+                        if self.buffer.len() >= declaration::MAX_DECLARATION_SIZE {
+                            xml_error!(self, XmlDeclBad);
+                        }
+                        self.extend_buffer(&[c])?;
+                        if self.buffer.len() == 4 {
+                            if !is_declaration_target(&self.buffer) {
+                                // Some other processing instruction
+                                self.in_declaration = false;
+                                self.buffer.clear();
+                            } else if self.misplaced_declaration {
+                                xml_error!(self, XmlDeclBad);
+                            }
+                        }
                     }
                 }
 
                 State::PIEnd => match c {
                     b'>' => {
+                        if self.in_declaration {
+                            self.in_declaration = false;
+                            let result = if is_declaration_target(&self.buffer) {
+                                if self.misplaced_declaration {
+                                    xml_error!(self, XmlDeclBad);
+                                }
+                                Some(
+                                    std::str::from_utf8(&self.buffer[3..])
+                                        .map_err(|_| ParseErrorKind::XmlDeclBad)
+                                        .and_then(XmlDeclaration::parse),
+                                )
+                            } else {
+                                None
+                            };
+                            self.buffer.clear();
+                            match result {
+                                Some(Ok(declaration)) => self.declaration = Some(declaration),
+                                Some(Err(kind)) => {
+                                    bad_xml!(
+                                        self,
+                                        ParseError::BadXml {
+                                            kind,
+                                            location: Some(self.location),
+                                        }
+                                    );
+                                }
+                                None => (),
+                            }
+                        }
                         if self.seen_content {
                             if self.depth > 0 {
                                 back = pos + 1;
+                                self.text_start = self.location_after(c);
                                 self.state = State::CData;
                             } else {
                                 self.state = State::Epilog;
@@ -701,30 +1390,47 @@ impl SaxParser {
                         }
                     }
                     _ => {
-                        xml_error!(PI_MISSING_END);
+                        xml_error!(self, PiMissingEnd);
                     }
                 },
 
                 State::TagName => match c {
                     b'/' | b'>' | whitespace!() => {
-                        if back < pos {
-                            self.extend_buffer(&bytes[back..pos])?;
+                        // Checked before borrowing the name, so recovery can continue
+                        if self.buffer.is_empty() && back == pos {
+                            xml_error!(self, TagEmptyName);
                         }
-                        {
-                            if self.buffer.is_empty() {
-                                xml_error!(TAG_EMPTY_NAME);
-                            }
-                            let s = unsafe { std::str::from_utf8_unchecked(&self.buffer) };
-                            self.state = State::TagNameContinue;
-                            if self.is_end_tag {
-                                if c == b'/' {
-                                    xml_error!(TAG_DOUBLE_END);
-                                }
-                                yield_element_inplace!(pos, SaxElement::EndTag(s));
-                            } else {
-                                yield_element_inplace!(pos, SaxElement::StartTag(s));
+                        if self.is_end_tag && c == b'/' {
+                            xml_error!(self, TagDoubleEnd);
+                        }
+                        if self.buffer.is_empty() {
+                            // Whole name is in the input, no need to copy
+                            self.check_name(pos - back)?;
+                        } else if back < pos {
+                            self.extend_name(&bytes[back..pos])?;
+                        }
+                        if self.strict {
+                            if !self.is_end_tag {
+                                self.push_open_tag(&bytes[back..pos])?;
+                            } else if let Some(error) = self.close_open_tag(&bytes[back..pos])? {
+                                bad_xml!(self, error);
                             }
                         }
+                        self.set_span(self.mark, self.location);
+                        self.span.name = Some(Span::new(self.name_start, self.location));
+                        let name = if self.buffer.is_empty() {
+                            &bytes[back..pos]
+                        } else {
+                            &self.buffer
+                        };
+                        self.cdata_run = 0;
+                        let s = unsafe { std::str::from_utf8_unchecked(name) };
+                        self.state = State::TagNameContinue;
+                        if self.is_end_tag {
+                            yield_element_inplace!(self, pos, SaxElement::EndTag(s));
+                        } else {
+                            yield_element_inplace!(self, pos, SaxElement::StartTag(s));
+                        }
                     }
                     _ => (),
                 },
@@ -733,23 +1439,29 @@ impl SaxParser {
                     self.buffer.clear();
                     match c {
                         b'/' => {
+                            if self.strict {
+                                self.pop_open_tag();
+                            }
                             self.state = State::EmptyTagEnd;
+                            self.set_span(self.location, self.location_after(c));
                             yield_element!(self, c, pos, SaxElement::StartTagEmpty);
                         }
                         b'>' => {
                             if self.is_end_tag {
                                 if self.depth == 0 {
-                                    xml_error!(TAG_CLOSE_WITHOUT_OPEN);
+                                    xml_error!(self, TagCloseWithoutOpen);
                                 }
                                 self.depth -= 1;
                                 if self.depth == 0 {
                                     self.state = State::Epilog;
                                 } else {
                                     back = pos + 1;
+                                    self.text_start = self.location_after(c);
                                     self.state = State::CData;
                                 }
                             } else {
                                 self.state = State::CData;
+                                self.set_span(self.location, self.location_after(c));
                                 yield_element!(self, c, pos, SaxElement::StartTagContent);
                             }
                         }
@@ -761,7 +1473,7 @@ impl SaxParser {
                             }
                         }
                         _ => {
-                            xml_error!(TAG_YIELD_MISMATCH);
+                            xml_error!(self, TagYieldMismatch);
                         }
                     }
                 }
@@ -769,37 +1481,39 @@ impl SaxParser {
                 State::EmptyTagEnd => match c {
                     b'>' => {
                         if self.depth == 0 {
-                            xml_error!(TAG_CLOSE_WITHOUT_OPEN);
+                            xml_error!(self, TagCloseWithoutOpen);
                         }
                         self.depth -= 1;
                         if self.depth == 0 {
                             self.state = State::Epilog;
                         } else {
                             back = pos + 1;
+                            self.text_start = self.location_after(c);
                             self.state = State::CData;
                         }
                     }
                     _ => {
-                        xml_error!(TAG_EMPTY_TAG_MISSING_END);
+                        xml_error!(self, TagEmptyTagMissingEnd);
                     }
                 },
 
                 State::EndTagWhitespace => match c {
                     b'>' => {
                         if self.depth == 0 {
-                            xml_error!(TAG_CLOSE_WITHOUT_OPEN);
+                            xml_error!(self, TagCloseWithoutOpen);
                         }
                         self.depth -= 1;
                         if self.depth == 0 {
                             self.state = State::Epilog;
                         } else {
                             back = pos + 1;
+                            self.text_start = self.location_after(c);
                             self.state = State::CData;
                         }
                     }
                     whitespace!() => (),
                     _ => {
-                        xml_error!(TAG_END_TAG_ATTRIBUTES);
+                        xml_error!(self, TagEndTagAttributes);
                     }
                 },
 
@@ -807,17 +1521,27 @@ impl SaxParser {
                     whitespace!() => (),
                     b'/' => {
                         if self.is_end_tag {
-                            xml_error!(TAG_DOUBLE_END);
+                            xml_error!(self, TagDoubleEnd);
+                        }
+                        if self.strict {
+                            self.pop_open_tag();
                         }
                         self.state = State::EmptyTagEnd;
+                        self.set_span(self.location, self.location_after(c));
                         yield_element!(self, c, pos, SaxElement::StartTagEmpty);
                     }
                     b'>' => {
                         self.state = State::CData;
+                        self.set_span(self.location, self.location_after(c));
                         yield_element!(self, c, pos, SaxElement::StartTagContent);
                     }
                     _ => {
+                        self.attribute_count += 1;
+                        if self.attribute_count > self.limits.max_attributes {
+                            return Err(ParseError::TooManyAttributes);
+                        }
                         back = pos;
+                        self.name_start = self.location;
                         self.state = State::AttributeName;
                         redo = true;
                     }
@@ -825,8 +1549,12 @@ impl SaxParser {
 
                 State::AttributeName => match c {
                     b'=' | whitespace!() => {
-                        if back < pos {
-                            self.extend_buffer(&bytes[back..pos])?;
+                        self.name_end = self.location;
+                        if self.buffer.is_empty() {
+                            self.check_name(pos - back)?;
+                            name_span = Some((back, pos));
+                        } else if back < pos {
+                            self.extend_name(&bytes[back..pos])?;
                         }
                         if c == b'=' {
                             self.state = State::AttributeValueStart;
@@ -835,7 +1563,7 @@ impl SaxParser {
                         }
                     }
                     b'/' | b'>' | b'<' => {
-                        xml_error!(TAG_ATTRIBUTE_BAD_NAME);
+                        xml_error!(self, TagAttributeBadName);
                     }
                     _ => (),
                 },
@@ -844,7 +1572,7 @@ impl SaxParser {
                     b'=' => self.state = State::AttributeValueStart,
                     whitespace!() => (),
                     _ => {
-                        xml_error!(TAG_ATTRIBUTE_WITHOUT_EQUAL);
+                        xml_error!(self, TagAttributeWithoutEqual);
                     }
                 },
 
@@ -853,42 +1581,77 @@ impl SaxParser {
                         self.is_quot_value = false;
                         self.value_pos = self.buffer.len();
                         back = pos + 1;
+                        self.value_start = self.location_after(c);
                         self.state = State::AttributeValue;
                     }
                     b'\'' => {
                         self.is_quot_value = true;
                         self.value_pos = self.buffer.len();
                         back = pos + 1;
+                        self.value_start = self.location_after(c);
                         self.state = State::AttributeValue;
                     }
                     whitespace!() => (),
                     _ => {
-                        xml_error!(TAG_ATTRIBUTE_WITHOUT_QUOTE);
+                        xml_error!(self, TagAttributeWithoutQuote);
                     }
                 },
 
                 State::AttributeValue => {
                     if (self.is_quot_value && c == b'\'') || (!self.is_quot_value && c == b'"') {
-                        if back < pos {
-                            self.extend_buffer(&bytes[back..pos])?;
+                        if self.strict && !self.push_attribute(bytes, name_span)? {
+                            let error = ParseError::BadXml {
+                                kind: ParseErrorKind::DuplicateAttribute,
+                                location: Some(self.location),
+                            };
+                            if !self.recovery {
+                                return Err(error);
+                            }
+                            // Attribute is skipped, rest of the tag is parsed as usual
+                            self.push_diagnostic(error)?;
+                            self.state = State::AttributeValueContinue;
+                            continue;
                         }
-                        let attr = unsafe {
-                            std::str::from_utf8_unchecked(&self.buffer[0..self.value_pos])
+                        self.span = ElementSpan {
+                            span: Span::new(self.name_start, self.location_after(c)),
+                            name: Some(Span::new(self.name_start, self.name_end)),
+                            value: Some(Span::new(self.value_start, self.location)),
                         };
-                        let value = unsafe {
-                            std::str::from_utf8_unchecked(&self.buffer[self.value_pos..])
+                        let (attr, value) = if let Some((name_back, name_end)) = name_span {
+                            // Both name and value are in the input, no need to copy
+                            self.check_value(pos - back)?;
+                            (&bytes[name_back..name_end], &bytes[back..pos])
+                        } else {
+                            if back < pos {
+                                self.extend_value(&bytes[back..pos])?;
+                            }
+                            (
+                                &self.buffer[0..self.value_pos],
+                                &self.buffer[self.value_pos..],
+                            )
                         };
+                        let attr = unsafe { std::str::from_utf8_unchecked(attr) };
+                        let value = unsafe { std::str::from_utf8_unchecked(value) };
                         self.state = State::AttributeValueContinue;
-                        yield_element_inplace!(pos, SaxElement::Attribute(attr, value));
+                        yield_element_inplace!(self, pos, SaxElement::Attribute(attr, value));
                     } else if c == b'&' {
+                        self.spill_name(bytes, &mut name_span)?;
                         if back < pos {
-                            self.extend_buffer(&bytes[back..pos])?;
+                            self.extend_value(&bytes[back..pos])?;
                         }
                         self.ref_buffer.clear();
                         self.is_value_ref = true;
                         self.state = State::Reference;
                     } else if c == b'<' {
-                        xml_error!(TAG_ATTRIBUTE_BAD_VALUE);
+                        xml_error!(self, TagAttributeBadValue);
+                    } else if matches!(c, b'\t' | b'\n' | b'\r') && self.normalization {
+                        self.spill_name(bytes, &mut name_span)?;
+                        if back < pos {
+                            self.extend_value(&bytes[back..pos])?;
+                        }
+                        self.extend_value(b" ")?;
+                        back = pos + 1;
+                        self.after_cr = c == b'\r';
                     }
                 }
 
@@ -899,19 +1662,39 @@ impl SaxParser {
 
                 State::CData => match c {
                     b'<' => {
+                        self.mark = self.location;
                         if back < pos {
+                            self.count_cdata(pos - back)?;
                             let s = unsafe { std::str::from_utf8_unchecked(&bytes[back..pos]) };
                             self.state = State::TagStart;
+                            self.set_span(self.text_start, self.location);
                             yield_element!(self, c, pos, SaxElement::CData(s));
                         }
                         back = pos + 1;
                         self.state = State::TagStart;
                     }
+                    b'\r' if self.normalization => {
+                        // Carriage return is dropped, and replaced with a
+                        // newline if it is not followed by one
+                        self.mark = self.location;
+                        self.after_cr = true;
+                        if back < pos {
+                            self.count_cdata(pos - back)?;
+                            let s = unsafe { std::str::from_utf8_unchecked(&bytes[back..pos]) };
+                            self.set_span(self.text_start, self.location);
+                            yield_element!(self, c, pos, SaxElement::CData(s));
+                        }
+                        back = pos + 1;
+                        self.text_start = self.location_after(c);
+                    }
                     b'&' => {
+                        self.mark = self.location;
                         if back < pos {
+                            self.count_cdata(pos - back)?;
                             let s = unsafe { std::str::from_utf8_unchecked(&bytes[back..pos]) };
                             self.state = State::CDataContinue;
-                            yield_element_inplace!(pos, SaxElement::CData(s));
+                            self.set_span(self.text_start, self.location);
+                            yield_element_inplace!(self, pos, SaxElement::CData(s));
                         }
                         self.ref_buffer.clear();
                         self.is_value_ref = false;
@@ -921,6 +1704,7 @@ impl SaxParser {
                 },
 
                 State::CDataContinue => {
+                    self.mark = self.location;
                     self.ref_buffer.clear();
                     self.is_value_ref = false;
                     self.state = State::Reference;
@@ -946,21 +1730,23 @@ impl SaxParser {
                             b"quot" => "\"",
                             b"apos" => "'",
                             _ => {
-                                xml_error!(REFERENCE_CUSTOM_ENTITY);
+                                xml_error!(self, ReferenceCustomEntity);
                             }
                         };
                         if self.is_value_ref {
-                            self.extend_buffer(ent.as_bytes())?;
+                            self.extend_value(ent.as_bytes())?;
                             back = pos + 1;
                             self.state = State::AttributeValue;
                         } else {
+                            self.count_cdata(ent.len())?;
                             self.state = State::CData;
-                            yield_element!(self, c, pos, SaxElement::CData(ent));
+                            self.set_span(self.mark, self.location_after(c));
+                            yield_element!(self, c, pos, self.reference_element(ent));
                         }
                     }
                     _ => {
                         if self.ref_buffer.len() >= REF_BUFFER_SIZE {
-                            xml_error!(REFERENCE_CUSTOM_ENTITY);
+                            xml_error!(self, ReferenceCustomEntity);
                         }
                         self.ref_buffer.push(c);
                     }
@@ -978,22 +1764,24 @@ impl SaxParser {
                 State::CharReferenceBody => match c {
                     b';' => {
                         if !is_valid_xml_char(self.char_ref_value) {
-                            xml_error!(CHAR_INVALID);
+                            xml_error!(self, CharInvalid);
                         }
                         if self.is_value_ref {
                             let size = self.u32_to_cdata();
                             let mut buf = [0u8; 4];
                             buf.clone_from_slice(&self.char_ref_buffer);
-                            self.extend_buffer(&buf[0..size])?;
+                            self.extend_value(&buf[0..size])?;
                             back = pos + 1;
                             self.state = State::AttributeValue;
                         } else {
                             let size = self.u32_to_cdata();
+                            self.count_cdata(size)?;
+                            self.set_span(self.mark, self.location_after(c));
                             let s = unsafe {
                                 std::str::from_utf8_unchecked(&self.char_ref_buffer[0..size])
                             };
                             self.state = State::CData;
-                            yield_element!(self, c, pos, SaxElement::CData(s));
+                            yield_element!(self, c, pos, self.reference_element(s));
                         }
                     }
                     b'0'..=b'9' => {
@@ -1001,29 +1789,31 @@ impl SaxParser {
                         self.char_ref_value = (self.char_ref_value * 10) + digit;
                     }
                     _ => {
-                        xml_error!(REFERENCE_INVALID_DECIMAL);
+                        xml_error!(self, ReferenceInvalidDecimal);
                     }
                 },
 
                 State::HexCharReference => match c {
                     b';' => {
                         if !is_valid_xml_char(self.char_ref_value) {
-                            xml_error!(CHAR_INVALID);
+                            xml_error!(self, CharInvalid);
                         }
                         if self.is_value_ref {
                             let size = self.u32_to_cdata();
                             let mut buf = [0u8; 4];
                             buf.clone_from_slice(&self.char_ref_buffer);
-                            self.extend_buffer(&buf[0..size])?;
+                            self.extend_value(&buf[0..size])?;
                             back = pos + 1;
                             self.state = State::AttributeValue;
                         } else {
                             let size = self.u32_to_cdata();
+                            self.count_cdata(size)?;
+                            self.set_span(self.mark, self.location_after(c));
                             let s = unsafe {
                                 std::str::from_utf8_unchecked(&self.char_ref_buffer[0..size])
                             };
                             self.state = State::CData;
-                            yield_element!(self, c, pos, SaxElement::CData(s));
+                            yield_element!(self, c, pos, self.reference_element(s));
                         }
                     }
                     b'0'..=b'9' => {
@@ -1039,17 +1829,50 @@ impl SaxParser {
                         self.char_ref_value = (self.char_ref_value * 16) + digit + 10;
                     }
                     _ => {
-                        xml_error!(REFERENCE_INVALID_HEX);
+                        xml_error!(self, ReferenceInvalidHex);
                     }
                 },
 
                 State::Epilog => match c {
-                    b'<' => self.state = State::TagStart,
+                    b'<' => {
+                        self.mark = self.location;
+                        self.state = State::TagStart;
+                    }
                     whitespace!() => (),
                     _ => {
-                        xml_error!(DOC_CDATA_WITHOUT_PARENT);
+                        xml_error!(self, DocCDataWithoutParent);
                     }
                 },
+
+                State::Recover => match c {
+                    b'<' => {
+                        if self.recover_tag {
+                            self.recover_tag = false;
+                            self.set_span(self.location, self.location);
+                            yield_element_inplace!(self, pos, SaxElement::StartTagContent);
+                        }
+                        self.mark = self.location;
+                        self.state = State::TagStart;
+                    }
+                    b'>' => {
+                        if self.recover_tag {
+                            self.recover_tag = false;
+                            self.state = State::CData;
+                            self.set_span(self.location, self.location_after(c));
+                            yield_element!(self, c, pos, SaxElement::StartTagContent);
+                        }
+                        if self.depth > 0 {
+                            back = pos + 1;
+                            self.text_start = self.location_after(c);
+                            self.state = State::CData;
+                        } else if self.seen_content {
+                            self.state = State::Epilog;
+                        } else {
+                            self.state = State::Prolog;
+                        }
+                    }
+                    _ => (),
+                },
             }
 
             if !redo {
@@ -1058,10 +1881,17 @@ impl SaxParser {
             }
         }
 
+        if matches!(self.state, State::AttributeEq | State::AttributeValueStart) {
+            self.spill_name(bytes, &mut name_span)?;
+        }
         if back < pos {
             match self.state {
-                State::TagName | State::AttributeName | State::AttributeValue => {
-                    self.extend_buffer(&bytes[back..pos])?;
+                State::TagName | State::AttributeName => {
+                    self.extend_name(&bytes[back..pos])?;
+                }
+                State::AttributeValue => {
+                    self.spill_name(bytes, &mut name_span)?;
+                    self.extend_value(&bytes[back..pos])?;
                 }
                 State::CData | State::CDataSectionBody => {
                     let mut valid_end = pos;
@@ -1074,8 +1904,12 @@ impl SaxParser {
                         }
                     }
                     if back < valid_end {
+                        self.count_cdata(valid_end - back)?;
+                        let mut end = self.location;
+                        end.rewind_plain(pos - valid_end);
+                        self.set_span(self.text_start, end);
                         let s = unsafe { std::str::from_utf8_unchecked(&bytes[back..valid_end]) };
-                        yield_element_inplace!(pos, SaxElement::CData(s));
+                        yield_element_inplace!(self, pos, self.text_element(s));
                     }
                 }
                 _ => (),
@@ -0,0 +1,581 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use std::ops::Range;
+
+use super::Location;
+use super::ParseError;
+use super::ParseErrorKind;
+use super::ParserLimits;
+use super::SaxElement;
+use super::SaxParser;
+
+/// The namespace bound to the `xml` prefix by definition.
+pub const XML_NS: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// The namespace of the `xmlns` namespace declaration attributes.
+pub const XMLNS_NS: &str = "http://www.w3.org/2000/xmlns/";
+
+/// A namespace qualified name.
+///
+/// The prefix is kept along with the resolved namespace so that
+/// applications can reproduce the original markup if they need to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct QName<'a> {
+    /// Namespace URI of the name, or None if the name is not in a namespace.
+    pub namespace_uri: Option<&'a str>,
+    /// Local part of the name without the prefix.
+    pub local_name: &'a str,
+    /// Prefix used in the markup, if any.
+    pub prefix: Option<&'a str>,
+}
+
+impl<'a> QName<'a> {
+    /// Splits a qualified name into its prefix and local parts.
+    ///
+    /// This does not resolve the prefix, the namespace_uri of
+    /// the returned name is always None.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use iks::QName;
+    ///
+    /// let name = QName::split("stream:features");
+    /// assert_eq!(name.prefix, Some("stream"));
+    /// assert_eq!(name.local_name, "features");
+    /// ```
+    pub fn split(name: &'a str) -> QName<'a> {
+        match name.split_once(':') {
+            Some((prefix, local_name)) => QName {
+                namespace_uri: None,
+                local_name,
+                prefix: Some(prefix),
+            },
+            None => QName {
+                namespace_uri: None,
+                local_name: name,
+                prefix: None,
+            },
+        }
+    }
+}
+
+// Returns the declared prefix of a namespace declaration attribute,
+// the empty prefix for the default namespace declarations.
+pub(crate) fn declared_prefix(name: &str) -> Option<&str> {
+    if name == "xmlns" {
+        Some("")
+    } else {
+        name.strip_prefix("xmlns:")
+    }
+}
+
+/// An XML element with resolved namespaces returned from the [NamespaceResolver].
+///
+/// These correspond one to one to the [SaxElement](crate::SaxElement) variants,
+/// except that the tag and attribute names are resolved into [QName]s.
+///
+/// Namespace declarations are reported as attributes in the [XMLNS_NS]
+/// namespace, with the declared prefix as the local name, or as
+/// `xmlns` for the default namespace declarations.
+#[derive(Debug, Eq, PartialEq)]
+pub enum NamespaceElement<'a> {
+    /// A start tag or empty element tag.
+    ///
+    /// Unlike the SaxParser, this element is sent after all the attributes
+    /// of the tag are parsed, since they might declare the namespace of
+    /// the tag itself.
+    StartTag(QName<'a>),
+
+    /// A tag attribute for the last StartTag.
+    ///
+    /// Unprefixed attributes are never in a namespace, even if there
+    /// is a default namespace declaration.
+    Attribute(QName<'a>, &'a str),
+
+    /// Indicates that the last StartTag was not an empty element tag.
+    StartTagContent,
+
+    /// Indicates that the last StartTag was an empty element tag and will have no content.
+    StartTagEmpty,
+
+    /// An end tag element.
+    EndTag(QName<'a>),
+
+    /// A character data element.
+    CData(&'a str),
+}
+
+/// An iterator over the namespace resolved elements of an XML text.
+pub struct NamespaceElements<'a> {
+    resolver: &'a mut NamespaceResolver,
+    bytes: &'a [u8],
+    bytes_parsed: usize,
+}
+
+impl<'a> NamespaceElements<'a> {
+    /// Constructs an iterator with a resolver over the elements of an XML text.
+    ///
+    /// While this method is publicly provided, it is more convenient
+    /// to use the [elements](NamespaceResolver::elements) method of the resolver.
+    pub fn new(resolver: &'a mut NamespaceResolver, bytes: &'a [u8]) -> Self {
+        NamespaceElements {
+            resolver,
+            bytes,
+            bytes_parsed: 0,
+        }
+    }
+
+    /// Returns the next element parsed from the XML text.
+    #[allow(
+        clippy::should_implement_trait,
+        reason = "Iterator trait does not support lending iterator pattern"
+    )]
+    pub fn next(&mut self) -> Option<Result<NamespaceElement<'_>, ParseError>> {
+        if self.bytes_parsed == self.bytes.len() && !self.resolver.has_pending() {
+            None
+        } else {
+            match self.resolver.parse_bytes(&self.bytes[self.bytes_parsed..]) {
+                Ok(Some((element, bytes))) => {
+                    self.bytes_parsed += bytes;
+                    Some(Ok(element))
+                }
+                Ok(None) => {
+                    self.bytes_parsed = self.bytes.len();
+                    None
+                }
+                Err(err) => Some(Err(err)),
+            }
+        }
+    }
+}
+
+struct Binding {
+    prefix: Range<usize>,
+    uri: Range<usize>,
+}
+
+struct PendingAttribute {
+    name: Range<usize>,
+    value: Range<usize>,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Emit {
+    Nothing,
+    StartTag,
+    Attribute(usize),
+    StartTagEnd,
+}
+
+/// Scoped prefix bindings and the start tag being collected.
+pub(crate) struct NamespaceStack {
+    declarations: String,
+    bindings: Vec<Binding>,
+    scopes: Vec<(usize, usize)>,
+    pending: String,
+    tag_size: usize,
+    attributes: Vec<PendingAttribute>,
+    is_empty: bool,
+    emit: Emit,
+    pop_scope: bool,
+}
+
+fn try_push_str(buffer: &mut String, s: &str) -> Result<Range<usize>, ParseError> {
+    if buffer.try_reserve(s.len()).is_err() {
+        return Err(ParseError::NoMemory);
+    }
+    let start = buffer.len();
+    buffer.push_str(s);
+    Ok(start..buffer.len())
+}
+
+fn check_qname(name: &str) -> Result<(), ParseError> {
+    if let Some((prefix, local_name)) = name.split_once(':')
+        && (prefix.is_empty() || local_name.is_empty() || local_name.contains(':'))
+    {
+        return Err(ParseErrorKind::NsBadQName.into());
+    }
+    Ok(())
+}
+
+impl NamespaceStack {
+    pub(crate) fn new() -> Self {
+        NamespaceStack {
+            declarations: String::new(),
+            bindings: Vec::new(),
+            scopes: Vec::new(),
+            pending: String::new(),
+            tag_size: 0,
+            attributes: Vec::new(),
+            is_empty: false,
+            emit: Emit::Nothing,
+            pop_scope: false,
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.declarations.clear();
+        self.bindings.clear();
+        self.scopes.clear();
+        self.pending.clear();
+        self.tag_size = 0;
+        self.attributes.clear();
+        self.is_empty = false;
+        self.emit = Emit::Nothing;
+        self.pop_scope = false;
+    }
+
+    pub(crate) fn lookup(&self, prefix: &str) -> Option<&str> {
+        match prefix {
+            "xml" => Some(XML_NS),
+            "xmlns" => Some(XMLNS_NS),
+            _ => {
+                for binding in self.bindings.iter().rev() {
+                    if &self.declarations[binding.prefix.clone()] == prefix {
+                        let uri = &self.declarations[binding.uri.clone()];
+                        if uri.is_empty() {
+                            return None;
+                        }
+                        return Some(uri);
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    fn resolve<'a>(&'a self, name: &'a str, is_attribute: bool) -> Result<QName<'a>, ParseError> {
+        let mut qname = QName::split(name);
+        match qname.prefix {
+            Some(prefix) => {
+                qname.namespace_uri = self.lookup(prefix);
+                if qname.namespace_uri.is_none() {
+                    return Err(ParseErrorKind::NsUnboundPrefix.into());
+                }
+            }
+            None => {
+                if is_attribute {
+                    if name == "xmlns" {
+                        qname.namespace_uri = Some(XMLNS_NS);
+                    }
+                } else {
+                    qname.namespace_uri = self.lookup("");
+                }
+            }
+        }
+        Ok(qname)
+    }
+
+    fn start_tag(&mut self, name: &str) -> Result<(), ParseError> {
+        check_qname(name)?;
+        self.pending.clear();
+        self.attributes.clear();
+        try_push_str(&mut self.pending, name)?;
+        self.tag_size = name.len();
+        Ok(())
+    }
+
+    fn attribute(&mut self, name: &str, value: &str) -> Result<(), ParseError> {
+        check_qname(name)?;
+        let name = try_push_str(&mut self.pending, name)?;
+        let value = try_push_str(&mut self.pending, value)?;
+        if self.attributes.try_reserve(1).is_err() {
+            return Err(ParseError::NoMemory);
+        }
+        self.attributes.push(PendingAttribute { name, value });
+        Ok(())
+    }
+
+    pub(crate) fn declare(&mut self, prefix: &str, uri: &str) -> Result<(), ParseError> {
+        match prefix {
+            "xml" => {
+                if uri != XML_NS {
+                    return Err(ParseErrorKind::NsReservedPrefix.into());
+                }
+                return Ok(());
+            }
+            "xmlns" => {
+                return Err(ParseErrorKind::NsReservedPrefix.into());
+            }
+            "" => (),
+            _ => {
+                if uri.is_empty() {
+                    return Err(ParseErrorKind::NsEmptyPrefixUri.into());
+                }
+            }
+        }
+        if uri == XML_NS || uri == XMLNS_NS {
+            return Err(ParseErrorKind::NsReservedPrefix.into());
+        }
+        let prefix = try_push_str(&mut self.declarations, prefix)?;
+        let uri = try_push_str(&mut self.declarations, uri)?;
+        if self.bindings.try_reserve(1).is_err() {
+            return Err(ParseError::NoMemory);
+        }
+        self.bindings.push(Binding { prefix, uri });
+        Ok(())
+    }
+
+    fn finish_tag(&mut self, is_empty: bool) -> Result<(), ParseError> {
+        if self.scopes.try_reserve(1).is_err() {
+            return Err(ParseError::NoMemory);
+        }
+        self.scopes
+            .push((self.bindings.len(), self.declarations.len()));
+
+        let pending = std::mem::take(&mut self.pending);
+        let result = self.declare_all(&pending);
+        self.pending = pending;
+        result?;
+
+        // Everything is declared now, check that all names can be resolved
+        self.resolve(&self.pending[0..self.tag_size], false)?;
+        for (i, attr) in self.attributes.iter().enumerate() {
+            let qname = self.resolve(&self.pending[attr.name.clone()], true)?;
+            for other in &self.attributes[..i] {
+                let other = self.resolve(&self.pending[other.name.clone()], true)?;
+                if other.namespace_uri == qname.namespace_uri
+                    && other.local_name == qname.local_name
+                {
+                    return Err(ParseErrorKind::NsDuplicateAttribute.into());
+                }
+            }
+        }
+
+        self.is_empty = is_empty;
+        self.emit = Emit::StartTag;
+        Ok(())
+    }
+
+    fn declare_all(&mut self, pending: &str) -> Result<(), ParseError> {
+        for i in 0..self.attributes.len() {
+            let name = &pending[self.attributes[i].name.clone()];
+            let value = &pending[self.attributes[i].value.clone()];
+            if let Some(prefix) = declared_prefix(name) {
+                self.declare(prefix, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self) {
+        if let Some((bindings, declarations)) = self.scopes.pop() {
+            self.bindings.truncate(bindings);
+            self.declarations.truncate(declarations);
+        }
+    }
+
+    fn resolve_checked<'a>(&'a self, name: &'a str, is_attribute: bool) -> QName<'a> {
+        // Names are already checked in finish_tag, errors cannot happen here
+        self.resolve(name, is_attribute)
+            .unwrap_or_else(|_| QName::split(name))
+    }
+
+    fn next_pending(&mut self) -> NamespaceElement<'_> {
+        match self.emit {
+            Emit::StartTag => {
+                self.emit = if self.attributes.is_empty() {
+                    Emit::StartTagEnd
+                } else {
+                    Emit::Attribute(0)
+                };
+                let tag = &self.pending[0..self.tag_size];
+                NamespaceElement::StartTag(self.resolve_checked(tag, false))
+            }
+            Emit::Attribute(i) => {
+                self.emit = if i + 1 < self.attributes.len() {
+                    Emit::Attribute(i + 1)
+                } else {
+                    Emit::StartTagEnd
+                };
+                let attr = &self.attributes[i];
+                let name = &self.pending[attr.name.clone()];
+                let value = &self.pending[attr.value.clone()];
+                NamespaceElement::Attribute(self.resolve_checked(name, true), value)
+            }
+            Emit::StartTagEnd | Emit::Nothing => {
+                self.emit = Emit::Nothing;
+                if self.is_empty {
+                    // Element has no content, its scope ends here
+                    self.pop_scope = true;
+                    NamespaceElement::StartTagEmpty
+                } else {
+                    NamespaceElement::StartTagContent
+                }
+            }
+        }
+    }
+}
+
+/// A namespace aware XML parser.
+///
+/// This struct wraps a [SaxParser](crate::SaxParser), keeps track of the
+/// namespace declarations in scope, and resolves the tag and attribute
+/// names into [QName]s with namespace URIs and local names. This allows
+/// the applications to recognize the elements regardless of the prefixes
+/// chosen by the document author.
+///
+/// Use of an undeclared prefix, declaring the reserved `xml` or `xmlns`
+/// prefixes incorrectly, malformed qualified names, and two attributes
+/// with the same namespace and local name are reported as errors.
+///
+/// # Examples
+///
+/// ```
+/// use iks::{NamespaceElement, NamespaceResolver, ParseError};
+/// # fn main() -> Result<(), ParseError> {
+///
+/// let mut resolver = NamespaceResolver::new();
+///
+/// let xml = b"<s:stream xmlns:s='http://etherx.jabber.org/streams'></s:stream>";
+/// let mut elements = resolver.elements(xml);
+/// while let Some(result) = elements.next() {
+///     if let NamespaceElement::StartTag(name) = result? {
+///         assert_eq!(name.namespace_uri, Some("http://etherx.jabber.org/streams"));
+///         assert_eq!(name.local_name, "stream");
+///     }
+/// }
+/// resolver.parse_finish()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct NamespaceResolver {
+    parser: SaxParser,
+    stack: NamespaceStack,
+}
+
+impl NamespaceResolver {
+    /// Creates a new namespace resolving parser.
+    pub fn new() -> NamespaceResolver {
+        NamespaceResolver::with_limits(ParserLimits::unlimited())
+    }
+
+    /// Creates a new namespace resolving parser with the given resource limits.
+    pub fn with_limits(limits: ParserLimits) -> NamespaceResolver {
+        NamespaceResolver {
+            parser: SaxParser::with_limits(limits),
+            stack: NamespaceStack::new(),
+        }
+    }
+
+    /// Resets the resolver and the parser into a clean state.
+    pub fn reset(&mut self) {
+        self.parser.reset();
+        self.stack.reset();
+    }
+
+    fn has_pending(&self) -> bool {
+        self.stack.emit != Emit::Nothing
+    }
+
+    /// Checks if the document is complete.
+    ///
+    /// See [SaxParser::parse_finish](crate::SaxParser::parse_finish).
+    pub fn parse_finish(&self) -> Result<(), ParseError> {
+        self.parser.parse_finish()
+    }
+
+    /// Returns an iterator over the elements parsed from the XML text.
+    pub fn elements<'a>(&'a mut self, bytes: &'a [u8]) -> NamespaceElements<'a> {
+        NamespaceElements::new(self, bytes)
+    }
+
+    /// Parses given XML bytes.
+    ///
+    /// Note that a start tag is returned as several elements after its
+    /// closing `>` is parsed, and the rest of these elements are returned
+    /// with zero bytes of input consumed. Keep calling this method until
+    /// all the input is consumed and it returns None.
+    pub fn parse_bytes<'a>(
+        &'a mut self,
+        bytes: &'a [u8],
+    ) -> Result<Option<(NamespaceElement<'a>, usize)>, ParseError> {
+        if self.stack.pop_scope {
+            self.stack.pop_scope = false;
+            self.stack.pop();
+        }
+        if self.has_pending() {
+            return Ok(Some((self.stack.next_pending(), 0)));
+        }
+
+        let parser: *mut SaxParser = &mut self.parser;
+        let mut pos: usize = 0;
+        while pos < bytes.len() {
+            // SAFETY:
+            // Invariant: Only one mutable reference to the parser must be alive.
+            // Guard: Elements returned from the previous iterations are either
+            // copied into the stack and dropped before the next iteration, or
+            // returned from the function. This is the well known limitation of
+            // the current borrow checker with the conditional returns in loops.
+            let element = unsafe { (*parser).parse_bytes(&bytes[pos..])? };
+            let Some((element, size)) = element else {
+                return Ok(None);
+            };
+            pos += size;
+            match element {
+                SaxElement::StartTag(name) => self
+                    .stack
+                    .start_tag(name)
+                    .map_err(|err| err.at(self.parser.location()))?,
+                SaxElement::Attribute(name, value) => self
+                    .stack
+                    .attribute(name, value)
+                    .map_err(|err| err.at(self.parser.location()))?,
+                SaxElement::StartTagContent => {
+                    self.stack
+                        .finish_tag(false)
+                        .map_err(|err| err.at(self.parser.location()))?;
+                    return Ok(Some((self.stack.next_pending(), pos)));
+                }
+                SaxElement::StartTagEmpty => {
+                    self.stack
+                        .finish_tag(true)
+                        .map_err(|err| err.at(self.parser.location()))?;
+                    return Ok(Some((self.stack.next_pending(), pos)));
+                }
+                SaxElement::EndTag(name) => {
+                    check_qname(name).map_err(|err| err.at(self.parser.location()))?;
+                    self.stack.pop_scope = true;
+                    let qname = self
+                        .stack
+                        .resolve(name, false)
+                        .map_err(|err| err.at(self.parser.location()))?;
+                    return Ok(Some((NamespaceElement::EndTag(qname), pos)));
+                }
+                SaxElement::CData(cdata)
+                | SaxElement::CDataSection(cdata)
+                | SaxElement::Reference(cdata) => {
+                    return Ok(Some((NamespaceElement::CData(cdata), pos)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the namespace URI bound to the given prefix in the current scope.
+    ///
+    /// An empty prefix looks up the default namespace.
+    pub fn lookup_namespace(&self, prefix: &str) -> Option<&str> {
+        self.stack.lookup(prefix)
+    }
+
+    /// Returns the current location of the parser.
+    pub fn location(&self) -> Location {
+        self.parser.location()
+    }
+}
+
+impl Default for NamespaceResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
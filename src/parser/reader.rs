@@ -0,0 +1,202 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use std::error::Error;
+use std::fmt::Display;
+use std::io::ErrorKind;
+use std::io::Read;
+
+use super::ParseError;
+use super::SaxElement;
+use super::SaxParser;
+
+/// Default size of the refill buffer used by the reader adapters.
+pub(crate) const DEFAULT_READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// The error type for parsing from a [Read] source.
+///
+/// Errors from the reader are kept separate from the parse errors,
+/// so applications can report them differently.
+#[derive(Debug)]
+pub enum ReadError {
+    /// Reading the input failed.
+    Io(std::io::Error),
+
+    /// Parsing the input failed.
+    Parse(ParseError),
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadError::Io(err) => err.fmt(f),
+            ReadError::Parse(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Io(err) => Some(err),
+            ReadError::Parse(err) => Some(err),
+        }
+    }
+}
+
+impl From<std::io::Error> for ReadError {
+    fn from(err: std::io::Error) -> Self {
+        ReadError::Io(err)
+    }
+}
+
+impl From<ParseError> for ReadError {
+    fn from(err: ParseError) -> Self {
+        ReadError::Parse(err)
+    }
+}
+
+/// Reads into the buffer until some bytes or the end of input is read.
+pub(crate) fn read_some<R: Read>(reader: &mut R, buffer: &mut [u8]) -> std::io::Result<usize> {
+    loop {
+        match reader.read(buffer) {
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            result => return result,
+        }
+    }
+}
+
+/// A SAX parser pulling its input from a [Read] source.
+///
+/// This is a convenience wrapper which runs the usual read loop around
+/// a [SaxParser] with an internal refill buffer. The parser itself does
+/// not do any I/O, so it can still be used directly with other kinds
+/// of input sources such as network protocols.
+///
+/// The completeness of the document is checked with the
+/// [parse_finish()](SaxParser::parse_finish) method when the end of
+/// the input is reached.
+///
+/// # Examples
+///
+/// ```
+/// use iks::{ReadError, SaxElement, SaxReader};
+/// # fn main() -> Result<(), ReadError> {
+///
+/// let input = "<doc><a>example</a></doc>".as_bytes();
+/// let mut reader = SaxReader::new(input);
+/// let mut tags = 0;
+/// while let Some(result) = reader.next() {
+///     if let SaxElement::StartTag(_) = result? {
+///         tags += 1;
+///     }
+/// }
+/// assert_eq!(tags, 2);
+/// # Ok(())
+/// # }
+/// ```
+pub struct SaxReader<R: Read> {
+    reader: R,
+    parser: SaxParser,
+    buffer: Vec<u8>,
+    pos: usize,
+    len: usize,
+    finished: bool,
+}
+
+impl<R: Read> SaxReader<R> {
+    /// Creates a new reader adapter with a default parser.
+    pub fn new(reader: R) -> Self {
+        SaxReader::with_parser(reader, SaxParser::new())
+    }
+
+    /// Creates a new reader adapter with the given parser.
+    ///
+    /// This can be used to parse with a parser which has some
+    /// [limits](crate::ParserLimits) set.
+    pub fn with_parser(reader: R, parser: SaxParser) -> Self {
+        SaxReader::with_buffer_size(reader, parser, DEFAULT_READ_BUFFER_SIZE)
+    }
+
+    /// Creates a new reader adapter with the given parser and refill buffer size.
+    pub fn with_buffer_size(reader: R, parser: SaxParser, buffer_size: usize) -> Self {
+        SaxReader {
+            reader,
+            parser,
+            buffer: vec![0u8; buffer_size.max(1)],
+            pos: 0,
+            len: 0,
+            finished: false,
+        }
+    }
+
+    /// Returns the parser used by the reader.
+    ///
+    /// This is useful for getting the [location](SaxParser::location) of
+    /// the last element or error.
+    pub fn parser(&self) -> &SaxParser {
+        &self.parser
+    }
+
+    /// Returns the underlying reader and parser.
+    pub fn into_inner(self) -> (R, SaxParser) {
+        (self.reader, self.parser)
+    }
+
+    fn fill(&mut self) -> Result<bool, ReadError> {
+        while self.pos == self.len {
+            if self.finished {
+                return Ok(false);
+            }
+            let len = read_some(&mut self.reader, &mut self.buffer)?;
+            if len == 0 {
+                self.finished = true;
+                self.parser.parse_finish()?;
+                return Ok(false);
+            }
+            self.pos = 0;
+            self.len = len;
+        }
+        Ok(true)
+    }
+
+    /// Returns the next element parsed from the input.
+    ///
+    /// An error is returned if the input cannot be read, or it is not
+    /// a well-formed document. `None` is returned after the end of the
+    /// input is reached.
+    #[allow(
+        clippy::should_implement_trait,
+        reason = "Iterator trait does not support lending iterator pattern"
+    )]
+    pub fn next(&mut self) -> Option<Result<SaxElement<'_>, ReadError>> {
+        loop {
+            match self.fill() {
+                Ok(true) => (),
+                Ok(false) => return None,
+                Err(err) => return Some(Err(err)),
+            }
+            let parser: *mut SaxParser = &mut self.parser;
+            let bytes: *const [u8] = &self.buffer[self.pos..self.len];
+            // SAFETY: Borrow checker cannot see that the borrows end when
+            // no element is returned and the loop refills the buffer. A
+            // returned element keeps self borrowed until it is dropped, so
+            // the parser and the buffer cannot be changed while it lives.
+            match unsafe { (*parser).parse_bytes(&*bytes) } {
+                Ok(Some((element, size))) => {
+                    self.pos += size;
+                    return Some(Ok(element));
+                }
+                Ok(None) => self.pos = self.len,
+                Err(err) => return Some(Err(err.into())),
+            }
+        }
+    }
+}
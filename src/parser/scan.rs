@@ -0,0 +1,72 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+// Bulk scanning of the plain bytes which do not change the parser state.
+//
+// A plain byte is a printable ASCII character which is not one of the
+// given stop characters. Non-ASCII bytes need UTF-8 validation, and
+// control characters need validation and line tracking, so they are
+// left to the byte by byte state machine too.
+//
+// Scanning is done eight bytes at a time with the SWAR (SIMD within
+// a register) technique. Bit tricks below flag the high bit of every
+// byte in a word which matches a condition. Flags can have false
+// positives only in the bytes after a true match due to the borrow
+// propagation, so the lowest flag always points to the first stop byte.
+
+const LOW_BITS: u64 = 0x0101_0101_0101_0101;
+const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+
+#[inline]
+fn zero_bytes(word: u64) -> u64 {
+    word.wrapping_sub(LOW_BITS) & !word & HIGH_BITS
+}
+
+#[inline]
+fn equal_bytes(word: u64, c: u8) -> u64 {
+    zero_bytes(word ^ (LOW_BITS * c as u64))
+}
+
+#[inline]
+fn stop_bytes(word: u64, stops: [u8; 3]) -> u64 {
+    let control = word.wrapping_sub(LOW_BITS * 0x20) & !word & HIGH_BITS;
+    (word & HIGH_BITS)
+        | control
+        | equal_bytes(word, stops[0])
+        | equal_bytes(word, stops[1])
+        | equal_bytes(word, stops[2])
+}
+
+#[inline]
+fn is_plain(c: u8, stops: [u8; 3]) -> bool {
+    (0x20..0x80).contains(&c) && c != stops[0] && c != stops[1] && c != stops[2]
+}
+
+/// Returns the number of plain bytes at the start of the given bytes.
+pub(super) fn plain_prefix(bytes: &[u8], stops: [u8; 3]) -> usize {
+    let mut pos = 0;
+    let mut words = bytes.chunks_exact(8);
+    for chunk in words.by_ref() {
+        let mut word = [0u8; 8];
+        word.copy_from_slice(chunk);
+        let flags = stop_bytes(u64::from_le_bytes(word), stops);
+        if flags != 0 {
+            return pos + (flags.trailing_zeros() / 8) as usize;
+        }
+        pos += 8;
+    }
+    for c in words.remainder() {
+        if !is_plain(*c, stops) {
+            break;
+        }
+        pos += 1;
+    }
+    pos
+}
@@ -0,0 +1,84 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use super::Location;
+
+/// A range of bytes in the parser input stream.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Span {
+    /// Location of the first byte in the range.
+    pub start: Location,
+    /// Location of the first byte after the range.
+    pub end: Location,
+}
+
+impl Span {
+    /// Creates a new span between the given locations.
+    pub fn new(start: Location, end: Location) -> Self {
+        Span { start, end }
+    }
+
+    /// Returns the number of bytes in the span.
+    pub fn len(&self) -> usize {
+        self.end.bytes - self.start.bytes
+    }
+
+    /// Returns true if the span does not contain any bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Span::new(Location::new(), Location::new())
+    }
+}
+
+/// Source ranges of a [SaxElement](crate::SaxElement).
+///
+/// This struct is returned along with the elements from the
+/// [parse_bytes_spanned()](crate::SaxParser::parse_bytes_spanned) and
+/// [next_spanned()](crate::SaxElements::next_spanned) methods.
+///
+/// The whole span of each element covers:
+///
+/// - [StartTag](crate::SaxElement::StartTag): the `<` character and the tag name.
+/// - [EndTag](crate::SaxElement::EndTag): the `</` characters and the tag name.
+/// - [Attribute](crate::SaxElement::Attribute): the attribute name up to and
+///   including the closing quote of the value.
+/// - [StartTagContent](crate::SaxElement::StartTagContent): the `>` character.
+/// - [StartTagEmpty](crate::SaxElement::StartTagEmpty): the `/` character.
+/// - [CData](crate::SaxElement::CData): the source text, which is the whole
+///   reference for a substituted entity or character reference.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ElementSpan {
+    /// Span of the whole element.
+    pub span: Span,
+    /// Span of the tag or attribute name.
+    ///
+    /// This is only set for the StartTag, EndTag, and Attribute elements.
+    pub name: Option<Span>,
+    /// Span of the attribute value between the quotes.
+    ///
+    /// This is only set for the Attribute elements. Note that the source
+    /// text can be longer than the returned value when it has references.
+    pub value: Option<Span>,
+}
+
+impl ElementSpan {
+    pub(super) fn new(span: Span) -> Self {
+        ElementSpan {
+            span,
+            name: None,
+            value: None,
+        }
+    }
+}
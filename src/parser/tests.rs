@@ -10,7 +10,6 @@
 
 use std::str::from_utf8;
 
-use super::error::description::*;
 use super::*;
 
 struct Tester<'a> {
@@ -54,9 +53,10 @@ impl<'a> Tester<'a> {
         let nr_column = s.lines().last().unwrap().len();
 
         let mut parser = SaxParser::new();
-        let mut elements = parser.elements(&s.as_bytes());
+        let mut elements = parser.elements(s.as_bytes());
         while let Some(element) = elements.next() {
-            let element = element.expect(&format!("Failed to parse {}", s));
+            assert!(element.is_ok(), "Failed to parse {s}");
+            let element = element.unwrap();
             self.check_element(&element);
         }
         assert_eq!(parser.parse_finish(), Ok(()));
@@ -73,7 +73,8 @@ impl<'a> Tester<'a> {
         for i in 0..s.len() {
             let mut elements = parser.elements(&s.as_bytes()[i..i + 1]);
             while let Some(element) = elements.next() {
-                let element = element.expect(&format!("Failed to parse {}", s));
+                assert!(element.is_ok(), "Failed to parse {s}");
+                let element = element.unwrap();
                 self.check_element(&element);
             }
         }
@@ -88,28 +89,34 @@ impl<'a> Tester<'a> {
 
 struct BadTester {
     bad_byte: usize,
-    err: ParseError,
+    kind: ParseErrorKind,
 }
 
 impl BadTester {
-    fn new(bad_byte: usize, msg: &'static str) -> BadTester {
-        BadTester {
-            bad_byte,
-            err: ParseError::BadXml(msg),
-        }
+    fn new(bad_byte: usize, kind: ParseErrorKind) -> BadTester {
+        BadTester { bad_byte, kind }
+    }
+
+    fn check_error(&self, err: &ParseError) {
+        assert_eq!(err.kind(), Some(&self.kind));
+        assert_eq!(
+            err.location().map(|location| location.bytes),
+            Some(self.bad_byte)
+        );
     }
 
     fn check(&mut self, s: &str) {
         let mut parser = SaxParser::new();
-        let mut elements = parser.elements(&s.as_bytes());
+        let mut elements = parser.elements(s.as_bytes());
         while let Some(element) = elements.next() {
             if let Err(err) = element {
-                assert_eq!(err, self.err);
+                self.check_error(&err);
                 assert_eq!(parser.location().bytes, self.bad_byte);
                 return;
             }
         }
-        assert_eq!(parser.parse_finish(), Err(self.err));
+        let err = parser.parse_finish().unwrap_err();
+        self.check_error(&err);
     }
 
     fn check_bytes(&mut self, bytes: &[u8]) {
@@ -117,12 +124,13 @@ impl BadTester {
         let mut elements = parser.elements(bytes);
         while let Some(element) = elements.next() {
             if let Err(err) = element {
-                assert_eq!(err, self.err);
+                self.check_error(&err);
                 assert_eq!(parser.location().bytes, self.bad_byte);
                 return;
             }
         }
-        assert_eq!(parser.parse_finish(), Err(self.err));
+        let err = parser.parse_finish().unwrap_err();
+        self.check_error(&err);
     }
 }
 
@@ -288,6 +296,292 @@ fn cdatas() {
     .check("<a>[[bg:Чингис хан]][[bn:চেঙ্গিজ খান]]</a>");
 }
 
+#[test]
+fn plain_scans() {
+    let stops = [b'<', b'&', b'<'];
+    for len in 0..40 {
+        let plain = vec![b'x'; len];
+        assert_eq!(scan::plain_prefix(&plain, stops), len);
+        for stop in [b'<', b'&', b'\n', b'\t', 0x7f, 0x80, 0xc4, 0xff] {
+            for at in 0..len {
+                let mut bytes = plain.clone();
+                bytes[at] = stop;
+                let expected = if stop == 0x7f { len } else { at };
+                assert_eq!(scan::plain_prefix(&bytes, stops), expected);
+                // Anything after the first stop byte must not matter
+                if at + 1 < len {
+                    bytes[at + 1] = 0;
+                    assert_eq!(scan::plain_prefix(&bytes, stops), expected.min(at + 1));
+                }
+            }
+        }
+    }
+    assert_eq!(scan::plain_prefix(b"abc--def", [b'-', b'-', b'-']), 3);
+    assert_eq!(scan::plain_prefix(b"it's \"so\"", [b'"', b'&', b'<']), 5);
+}
+
+#[test]
+fn long_texts() {
+    let text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit.";
+    let long_text = format!("{text}\n\t{text} \u{131}\u{11f} {text}\r\n{text}");
+    let xml = format!(
+        "<doc a='{text}' b=\"{text}&amp;'{text}'\"><!-- {text} - {text} -->{long_text}&lt;{text}</doc>"
+    );
+    let b_value = format!("{text}&'{text}'");
+    // Line endings are normalized
+    let cdata = format!("{}<{text}", long_text.replace("\r\n", "\n"));
+    Tester::new(&[
+        SaxElement::StartTag("doc"),
+        SaxElement::Attribute("a", text),
+        SaxElement::Attribute("b", &b_value),
+        SaxElement::StartTagContent,
+        SaxElement::CData(&cdata),
+        SaxElement::EndTag("doc"),
+    ])
+    .check(&xml);
+}
+
+#[test]
+fn normalization() {
+    Tester::new(&[
+        SaxElement::StartTag("a"),
+        SaxElement::StartTagContent,
+        SaxElement::CData("x\ny\nz\n\n<\n"),
+        SaxElement::EndTag("a"),
+    ])
+    .check("<a>x\r\ny\rz\r\r\n&lt;\r</a>");
+    Tester::new(&[
+        SaxElement::StartTag("a"),
+        SaxElement::StartTagContent,
+        SaxElement::CData("x\ny\n]\n"),
+        SaxElement::EndTag("a"),
+    ])
+    .check("<a><![CDATA[x\r\ny\r]\r]]></a>");
+    Tester::new(&[
+        SaxElement::StartTag("a"),
+        SaxElement::Attribute("b", "x y z w "),
+        SaxElement::Attribute("c", "\t\r\n"),
+        SaxElement::StartTagEmpty,
+    ])
+    .check("<a b='x\ty\r\nz\rw\n' c='&#9;&#13;&#10;'/>");
+
+    let xml = "<a b='x\r\ny'>1\r\n2\r</a>";
+    let mut parser = SaxParser::new();
+    parser.set_normalization(false);
+    let mut elements = parser.elements(xml.as_bytes());
+    let mut values = Vec::new();
+    while let Some(element) = elements.next() {
+        match element.unwrap() {
+            SaxElement::Attribute(_, value) => values.push(value.to_string()),
+            SaxElement::CData(cdata) => values.push(cdata.to_string()),
+            _ => (),
+        }
+    }
+    assert_eq!(values, ["x\r\ny", "1\r\n2\r"]);
+}
+
+fn is_borrowed(s: &str, input: &[u8]) -> bool {
+    input.as_ptr_range().contains(&s.as_ptr())
+}
+
+#[test]
+fn zero_copy() {
+    let input = b"<a x='1' y='2&amp;3'>text</a>";
+    let mut parser = SaxParser::new();
+    let mut elements = parser.elements(input);
+    let mut seen = 0;
+    while let Some(element) = elements.next() {
+        match element.unwrap() {
+            SaxElement::StartTag(name) | SaxElement::EndTag(name) => {
+                assert!(is_borrowed(name, input));
+            }
+            SaxElement::Attribute("x", value) => {
+                assert!(is_borrowed(value, input));
+            }
+            SaxElement::Attribute(name, value) => {
+                assert_eq!((name, value), ("y", "2&3"));
+                assert!(!is_borrowed(name, input));
+                assert!(!is_borrowed(value, input));
+            }
+            SaxElement::CData(cdata) => {
+                assert!(is_borrowed(cdata, input));
+            }
+            _ => (),
+        }
+        seen += 1;
+    }
+    assert_eq!(seen, 6);
+
+    // Tokens straddling the parts are collected in the parser
+    check_parts(&[b"<ab", b"c x", b"yz", b" =", b" 'va", b"lue'/>"]);
+    check_parts(&[b"<a b='", b"1'><c d", b"='2' e='3'/></a>"]);
+}
+
+fn location_at(s: &str, bytes: usize) -> Location {
+    let mut location = Location::new();
+    for c in &s.as_bytes()[0..bytes] {
+        location.advance(*c);
+    }
+    location
+}
+
+fn span_text<'a>(s: &'a str, span: &Span) -> &'a str {
+    assert_eq!(span.start, location_at(s, span.start.bytes));
+    assert_eq!(span.end, location_at(s, span.end.bytes));
+    &s[span.start.bytes..span.end.bytes]
+}
+
+#[test]
+fn spans() {
+    let s = "<doc>\n <a x='1&amp;2' y = \"z\"/>t&lt;\n<![CDATA[v]x]]]>\u{e9}</doc>";
+    let expected = [
+        ("<doc", Some("doc"), None),
+        (">", None, None),
+        ("\n ", None, None),
+        ("<a", Some("a"), None),
+        ("x='1&amp;2'", Some("x"), Some("1&amp;2")),
+        ("y = \"z\"", Some("y"), Some("z")),
+        ("/", None, None),
+        ("t", None, None),
+        ("&lt;", None, None),
+        ("\n", None, None),
+        ("v", None, None),
+        ("]", None, None),
+        ("x", None, None),
+        ("]", None, None),
+        ("\u{e9}", None, None),
+        ("</doc", Some("doc"), None),
+    ];
+    let mut parser = SaxParser::new();
+    let mut elements = parser.elements(s.as_bytes());
+    let mut current = 0;
+    while let Some(result) = elements.next_spanned() {
+        let (_, span) = result.unwrap();
+        let texts = (
+            span_text(s, &span.span),
+            span.name.map(|name| span_text(s, &name)),
+            span.value.map(|value| span_text(s, &value)),
+        );
+        assert_eq!(texts, expected[current]);
+        current += 1;
+    }
+    assert_eq!(current, expected.len());
+
+    // Spans of the elements sent in pieces should still cover the source
+    parser.reset();
+    let mut cdata_end = 0;
+    for i in 0..s.len() {
+        let mut elements = parser.elements(&s.as_bytes()[i..i + 1]);
+        while let Some(result) = elements.next_spanned() {
+            let (element, span) = result.unwrap();
+            let text = span_text(s, &span.span);
+            match element {
+                SaxElement::StartTag(name) | SaxElement::EndTag(name) => {
+                    assert!(text.ends_with(name));
+                    assert_eq!(span_text(s, &span.name.unwrap()), name);
+                }
+                SaxElement::Attribute(name, _) => {
+                    assert!(text.starts_with(name));
+                }
+                SaxElement::CData(cdata) => {
+                    if !text.starts_with('&') {
+                        assert_eq!(text, cdata);
+                    }
+                    assert!(span.span.start.bytes >= cdata_end);
+                    cdata_end = span.span.end.bytes;
+                }
+                _ => (),
+            }
+        }
+    }
+    assert_eq!(parser.parse_finish(), Ok(()));
+}
+
+// Reader which returns the input in small pieces with interruptions
+struct SlowReader<'a> {
+    bytes: &'a [u8],
+    interrupted: bool,
+}
+
+impl std::io::Read for SlowReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.interrupted = !self.interrupted;
+        if self.interrupted {
+            return Err(std::io::ErrorKind::Interrupted.into());
+        }
+        if self.bytes == b"!" {
+            return Err(std::io::Error::other("broken"));
+        }
+        let len = buf.len().min(self.bytes.len()).min(3);
+        buf[..len].copy_from_slice(&self.bytes[..len]);
+        self.bytes = &self.bytes[len..];
+        Ok(len)
+    }
+}
+
+#[test]
+fn readers() {
+    let s = "<doc a='x&amp;y'>text &lt; <b/>more</doc>";
+    let mut reader = SaxReader::with_buffer_size(s.as_bytes(), SaxParser::new(), 5);
+    let mut tester = Tester::new(&[
+        SaxElement::StartTag("doc"),
+        SaxElement::Attribute("a", "x&y"),
+        SaxElement::StartTagContent,
+        SaxElement::CData("text < "),
+        SaxElement::StartTag("b"),
+        SaxElement::StartTagEmpty,
+        SaxElement::CData("more"),
+        SaxElement::EndTag("doc"),
+    ]);
+    while let Some(result) = reader.next() {
+        tester.check_element(&result.unwrap());
+    }
+    assert_eq!(tester.current, tester.expected.len());
+    assert_eq!(reader.parser().location().bytes, s.len());
+
+    let slow = SlowReader {
+        bytes: s.as_bytes(),
+        interrupted: false,
+    };
+    let mut reader = SaxReader::new(slow);
+    let mut count = 0;
+    while let Some(result) = reader.next() {
+        result.unwrap();
+        count += 1;
+    }
+    assert!(count >= 8);
+
+    let mut reader = SaxReader::new("<doc>".as_bytes());
+    assert!(matches!(
+        reader.next(),
+        Some(Ok(SaxElement::StartTag("doc")))
+    ));
+    assert!(matches!(
+        reader.next(),
+        Some(Ok(SaxElement::StartTagContent))
+    ));
+    assert!(matches!(
+        reader.next(),
+        Some(Err(ReadError::Parse(ParseError::BadXml {
+            kind: ParseErrorKind::DocOpenTags,
+            ..
+        })))
+    ));
+    assert!(reader.next().is_none());
+
+    let broken = SlowReader {
+        bytes: b"<a>!",
+        interrupted: false,
+    };
+    let mut reader = SaxReader::new(broken);
+    assert!(matches!(reader.next(), Some(Ok(SaxElement::StartTag("a")))));
+    assert!(matches!(
+        reader.next(),
+        Some(Ok(SaxElement::StartTagContent))
+    ));
+    assert!(matches!(reader.next(), Some(Err(ReadError::Io(_)))));
+}
+
 fn check_parts(parts: &[&[u8]]) {
     let mut parser = SaxParser::new();
     for part in parts {
@@ -354,7 +648,7 @@ fn pi() {
         SaxElement::CData("bibi"),
         SaxElement::EndTag("a"),
     ])
-    .check("<a><?xml lala?>bibi</a>");
+    .check("<a><?app lala?>bibi</a>");
 }
 
 #[test]
@@ -420,7 +714,7 @@ fn attribute_entities() {
 #[test]
 fn long_tag() {
     let name = "abc".repeat(500);
-    let xml = format!("<{}></{}>", name, name);
+    let xml = format!("<{name}></{name}>");
 
     Tester::new(&[
         SaxElement::StartTag(&name),
@@ -443,98 +737,282 @@ fn location() {
 
 #[test]
 fn bad_tags() {
-    BadTester::new(4, TAG_WHITESPACE_START).check("<a>< b/></a>");
-    BadTester::new(6, TAG_EMPTY_TAG_MISSING_END).check("<a><b/ ></a>");
-    BadTester::new(8, TAG_DOUBLE_END).check("<a></ccc/></a>");
-    BadTester::new(13, TAG_DOUBLE_END).check("<a><b/><c></c/></a>");
-    BadTester::new(1, TAG_CLOSE_WITHOUT_OPEN).check("</a>");
-    BadTester::new(9, TAG_END_TAG_ATTRIBUTES).check("<a> </a  b>");
-    BadTester::new(8, TAG_OUTSIDE_ROOT).check("<a></a><b/>");
-    BadTester::new(10, TAG_ATTRIBUTE_BAD_NAME).check("<a a='1' b></a>");
-    BadTester::new(11, TAG_ATTRIBUTE_WITHOUT_QUOTE).check("<a a='1' b=></a>");
-    BadTester::new(12, TAG_ATTRIBUTE_WITHOUT_EQUAL).check("<a a='12' b '2'></a>");
-    BadTester::new(13, TAG_ATTRIBUTE_WITHOUT_EQUAL).check("<a a='123' b c='5'></a>");
-    BadTester::new(14, TAG_END_TAG_ATTRIBUTES).check("<a a='12'></a b='1'>");
-    BadTester::new(17, TAG_EMPTY_TAG_MISSING_END).check("<g><test a='123'/ b='lala'></g>");
-    BadTester::new(13, TAG_ATTRIBUTE_BAD_VALUE).check("<a a='1' b='></a>");
-    BadTester::new(13, TAG_ATTRIBUTE_BAD_VALUE).check("<a a='1' b=\"></a>");
-    BadTester::new(4, TAG_WHITESPACE_START).check("<a>< abc/></a>");
-    BadTester::new(5, TAG_EMPTY_NAME).check("<a> <> </a>");
-    BadTester::new(6, TAG_EMPTY_NAME).check("<a> </> </a>");
+    BadTester::new(4, ParseErrorKind::TagWhitespaceStart).check("<a>< b/></a>");
+    BadTester::new(6, ParseErrorKind::TagEmptyTagMissingEnd).check("<a><b/ ></a>");
+    BadTester::new(8, ParseErrorKind::TagDoubleEnd).check("<a></ccc/></a>");
+    BadTester::new(13, ParseErrorKind::TagDoubleEnd).check("<a><b/><c></c/></a>");
+    BadTester::new(1, ParseErrorKind::TagCloseWithoutOpen).check("</a>");
+    BadTester::new(9, ParseErrorKind::TagEndTagAttributes).check("<a> </a  b>");
+    BadTester::new(8, ParseErrorKind::TagOutsideRoot).check("<a></a><b/>");
+    BadTester::new(10, ParseErrorKind::TagAttributeBadName).check("<a a='1' b></a>");
+    BadTester::new(11, ParseErrorKind::TagAttributeWithoutQuote).check("<a a='1' b=></a>");
+    BadTester::new(12, ParseErrorKind::TagAttributeWithoutEqual).check("<a a='12' b '2'></a>");
+    BadTester::new(13, ParseErrorKind::TagAttributeWithoutEqual).check("<a a='123' b c='5'></a>");
+    BadTester::new(14, ParseErrorKind::TagEndTagAttributes).check("<a a='12'></a b='1'>");
+    BadTester::new(17, ParseErrorKind::TagEmptyTagMissingEnd)
+        .check("<g><test a='123'/ b='lala'></g>");
+    BadTester::new(13, ParseErrorKind::TagAttributeBadValue).check("<a a='1' b='></a>");
+    BadTester::new(13, ParseErrorKind::TagAttributeBadValue).check("<a a='1' b=\"></a>");
+    BadTester::new(4, ParseErrorKind::TagWhitespaceStart).check("<a>< abc/></a>");
+    BadTester::new(5, ParseErrorKind::TagEmptyName).check("<a> <> </a>");
+    BadTester::new(6, ParseErrorKind::TagEmptyName).check("<a> </> </a>");
 }
 
 #[test]
 fn bad_comments() {
-    BadTester::new(10, COMMENT_MISSING_END).check("<e><!-- -- --></e>");
-    BadTester::new(22, COMMENT_MISSING_DASH).check("<ha><!-- <lala> --><!- comment -></ha>");
-    BadTester::new(12, DOC_CDATA_WITHOUT_PARENT).check("<!-- c1 --> lala <ha/>");
-    BadTester::new(31, DOC_CDATA_WITHOUT_PARENT).check("<!-- c1 --> <ha/> <!-- pika -->c");
-    BadTester::new(9, COMMENT_MISSING_END).check("<!-- c ---> <ha/>");
+    BadTester::new(10, ParseErrorKind::CommentMissingEnd).check("<e><!-- -- --></e>");
+    BadTester::new(22, ParseErrorKind::CommentMissingDash)
+        .check("<ha><!-- <lala> --><!- comment -></ha>");
+    BadTester::new(12, ParseErrorKind::DocCDataWithoutParent).check("<!-- c1 --> lala <ha/>");
+    BadTester::new(31, ParseErrorKind::DocCDataWithoutParent)
+        .check("<!-- c1 --> <ha/> <!-- pika -->c");
+    BadTester::new(9, ParseErrorKind::CommentMissingEnd).check("<!-- c ---> <ha/>");
 }
 
 #[test]
 fn bad_pi() {
-    BadTester::new(12, PI_MISSING_END).check("<e/> <?xml ? >");
-    BadTester::new(12, DOC_OPEN_MARKUP).check("<e/> <?xml >");
-    BadTester::new(13, DOC_CDATA_WITHOUT_PARENT).check("<e/> <?xml ?>lala");
+    BadTester::new(12, ParseErrorKind::PiMissingEnd).check("<e/> <?app ? >");
+    BadTester::new(12, ParseErrorKind::DocOpenMarkup).check("<e/> <?app >");
+    BadTester::new(13, ParseErrorKind::DocCDataWithoutParent).check("<e/> <?app ?>lala");
 }
 
 #[test]
 fn bad_cdatas() {
-    BadTester::new(2, DOC_CDATA_WITHOUT_PARENT).check("  lala <a></a>");
-    BadTester::new(10, DOC_CDATA_WITHOUT_PARENT).check("  <a></a> lala");
-    BadTester::new(11, DOC_CDATA_WITHOUT_PARENT).check("  <a></a > lala");
-    BadTester::new(2, MARKUP_CDATA_SECTION_OUTSIDE_ROOT).check("<![CDATA[lala]> <a/>");
-    BadTester::new(8, MARKUP_CDATA_SECTION_OUTSIDE_ROOT).check(" <a/> <![CDATA[lala]>");
-    BadTester::new(7, MARKUP_CDATA_SECTION_BAD_START).check("<a> <![DATA[lala]> </a>");
-    BadTester::new(9, MARKUP_CDATA_SECTION_BAD_START).check("<a> <![CDaTA[lala]> </a>");
-    BadTester::new(12, MARKUP_CDATA_SECTION_BAD_START).check("<a> <![CDATAlala]> </a>");
+    BadTester::new(2, ParseErrorKind::DocCDataWithoutParent).check("  lala <a></a>");
+    BadTester::new(10, ParseErrorKind::DocCDataWithoutParent).check("  <a></a> lala");
+    BadTester::new(11, ParseErrorKind::DocCDataWithoutParent).check("  <a></a > lala");
+    BadTester::new(2, ParseErrorKind::MarkupCDataSectionOutsideRoot).check("<![CDATA[lala]> <a/>");
+    BadTester::new(8, ParseErrorKind::MarkupCDataSectionOutsideRoot).check(" <a/> <![CDATA[lala]>");
+    BadTester::new(7, ParseErrorKind::MarkupCDataSectionBadStart).check("<a> <![DATA[lala]> </a>");
+    BadTester::new(9, ParseErrorKind::MarkupCDataSectionBadStart).check("<a> <![CDaTA[lala]> </a>");
+    BadTester::new(12, ParseErrorKind::MarkupCDataSectionBadStart).check("<a> <![CDATAlala]> </a>");
 }
 
 #[test]
 fn bad_entities() {
-    BadTester::new(8, REFERENCE_CUSTOM_ENTITY).check("<a>&lala;</a>");
-    BadTester::new(12, REFERENCE_CUSTOM_ENTITY).check("<a>&lala           </a>");
-    BadTester::new(16, TAG_EMPTY_TAG_MISSING_END).check("<lol>&lt;<&gt;</lol>");
-    BadTester::new(6, REFERENCE_INVALID_DECIMAL).check("<a>&#1a;</a>");
-    BadTester::new(6, REFERENCE_INVALID_DECIMAL).check("<a>&#Xaa;</a>");
-    BadTester::new(8, REFERENCE_INVALID_HEX).check("<a>&#xa5g;</a>");
-    BadTester::new(6, CHAR_INVALID).check("<a>&#8;</a>");
-    BadTester::new(7, CHAR_INVALID).check("<a>&#11;</a>");
-    BadTester::new(7, CHAR_INVALID).check("<a>&#15;</a>");
-    BadTester::new(10, CHAR_INVALID).check("<a>&#xD800;</a>");
-    BadTester::new(10, CHAR_INVALID).check("<a>&#xDfFf;</a>");
-    BadTester::new(10, CHAR_INVALID).check("<a>&#xfFfE;</a>");
-    BadTester::new(10, CHAR_INVALID).check("<a>&#xFFff;</a>");
-    BadTester::new(12, CHAR_INVALID).check("<a>&#x110000;</a>");
+    BadTester::new(8, ParseErrorKind::ReferenceCustomEntity).check("<a>&lala;</a>");
+    BadTester::new(12, ParseErrorKind::ReferenceCustomEntity).check("<a>&lala           </a>");
+    BadTester::new(16, ParseErrorKind::TagEmptyTagMissingEnd).check("<lol>&lt;<&gt;</lol>");
+    BadTester::new(6, ParseErrorKind::ReferenceInvalidDecimal).check("<a>&#1a;</a>");
+    BadTester::new(6, ParseErrorKind::ReferenceInvalidDecimal).check("<a>&#Xaa;</a>");
+    BadTester::new(8, ParseErrorKind::ReferenceInvalidHex).check("<a>&#xa5g;</a>");
+    BadTester::new(6, ParseErrorKind::CharInvalid).check("<a>&#8;</a>");
+    BadTester::new(7, ParseErrorKind::CharInvalid).check("<a>&#11;</a>");
+    BadTester::new(7, ParseErrorKind::CharInvalid).check("<a>&#15;</a>");
+    BadTester::new(10, ParseErrorKind::CharInvalid).check("<a>&#xD800;</a>");
+    BadTester::new(10, ParseErrorKind::CharInvalid).check("<a>&#xDfFf;</a>");
+    BadTester::new(10, ParseErrorKind::CharInvalid).check("<a>&#xfFfE;</a>");
+    BadTester::new(10, ParseErrorKind::CharInvalid).check("<a>&#xFFff;</a>");
+    BadTester::new(12, ParseErrorKind::CharInvalid).check("<a>&#x110000;</a>");
 }
 
 #[test]
 fn bad_chars() {
-    BadTester::new(6, UTF8_INVALID_PREFIX_BYTE).check_bytes(b"<test>\xFF</test>");
-    BadTester::new(6, UTF8_INVALID_PREFIX_BYTE).check_bytes(b"<test>\xFE</test>");
-    BadTester::new(2, CHAR_INVALID).check_bytes(b"<t\x00></t>");
-    BadTester::new(2, CHAR_INVALID).check_bytes(b"<t\x19></t>");
-    BadTester::new(8, UTF8_INVALID_CONT_BYTE).check_bytes(b"<test>\xe3\x8fa</test>");
-    BadTester::new(7, UTF8_OVERLONG_SEQUENCE).check_bytes(b"<test>\xC0\x80</test>");
-    BadTester::new(7, UTF8_OVERLONG_SEQUENCE).check_bytes(b"<test>\xC0\xaf</test>");
-    BadTester::new(8, UTF8_OVERLONG_SEQUENCE).check_bytes(b"<test>\xe0\x80\xaf</test>");
-    BadTester::new(9, UTF8_OVERLONG_SEQUENCE).check_bytes(b"<test>\xf0\x80\x80\xaf</test>");
-    BadTester::new(7, UTF8_OVERLONG_SEQUENCE).check_bytes(b"<test>\xc1\xbf</test>");
-    BadTester::new(8, UTF8_OVERLONG_SEQUENCE).check_bytes(b"<test>\xe0\x9f\xbf</test>");
-    BadTester::new(9, UTF8_OVERLONG_SEQUENCE).check_bytes(b"<test>\xf0\x8f\xbf\xbf</test>");
-    BadTester::new(1, UTF8_INVALID_PREFIX_BYTE).check_bytes(b"<\x8f\x85></\x8f\x85>");
-    BadTester::new(7, UTF8_OVERLONG_SEQUENCE).check_bytes(
+    BadTester::new(6, ParseErrorKind::Utf8InvalidPrefixByte).check_bytes(b"<test>\xFF</test>");
+    BadTester::new(6, ParseErrorKind::Utf8InvalidPrefixByte).check_bytes(b"<test>\xFE</test>");
+    BadTester::new(2, ParseErrorKind::CharInvalid).check_bytes(b"<t\x00></t>");
+    BadTester::new(2, ParseErrorKind::CharInvalid).check_bytes(b"<t\x19></t>");
+    BadTester::new(8, ParseErrorKind::Utf8InvalidContByte).check_bytes(b"<test>\xe3\x8fa</test>");
+    BadTester::new(7, ParseErrorKind::Utf8OverlongSequence).check_bytes(b"<test>\xC0\x80</test>");
+    BadTester::new(7, ParseErrorKind::Utf8OverlongSequence).check_bytes(b"<test>\xC0\xaf</test>");
+    BadTester::new(8, ParseErrorKind::Utf8OverlongSequence)
+        .check_bytes(b"<test>\xe0\x80\xaf</test>");
+    BadTester::new(9, ParseErrorKind::Utf8OverlongSequence)
+        .check_bytes(b"<test>\xf0\x80\x80\xaf</test>");
+    BadTester::new(7, ParseErrorKind::Utf8OverlongSequence).check_bytes(b"<test>\xc1\xbf</test>");
+    BadTester::new(8, ParseErrorKind::Utf8OverlongSequence)
+        .check_bytes(b"<test>\xe0\x9f\xbf</test>");
+    BadTester::new(9, ParseErrorKind::Utf8OverlongSequence)
+        .check_bytes(b"<test>\xf0\x8f\xbf\xbf</test>");
+    BadTester::new(1, ParseErrorKind::Utf8InvalidPrefixByte).check_bytes(b"<\x8f\x85></\x8f\x85>");
+    BadTester::new(7, ParseErrorKind::Utf8OverlongSequence).check_bytes(
         b"<utf8>\xC1\x80<br/>\xED\x95\x9C\xEA\xB5\xAD\xEC\x96\xB4<err>\xC1\x65</err></utf8>",
     );
 }
 
 #[test]
 fn bad_unfinished() {
-    BadTester::new(5, DOC_OPEN_TAGS).check(" <a> ");
-    BadTester::new(20, DOC_NO_CONTENT).check("  <!-- lala -->     ");
-    BadTester::new(27, DOC_OPEN_MARKUP).check(" <a></a> <!-- open comment ");
-    BadTester::new(23, DOC_OPEN_MARKUP).check(" <a></a> <?app open pi ");
+    BadTester::new(5, ParseErrorKind::DocOpenTags).check(" <a> ");
+    BadTester::new(20, ParseErrorKind::DocNoContent).check("  <!-- lala -->     ");
+    BadTester::new(27, ParseErrorKind::DocOpenMarkup).check(" <a></a> <!-- open comment ");
+    BadTester::new(23, ParseErrorKind::DocOpenMarkup).check(" <a></a> <?app open pi ");
+}
+
+fn check_recovery(bytes: &[u8], expected: &[SaxElement], errors: &[(usize, ParseErrorKind)]) {
+    // Whole input and byte by byte
+    for chunk_size in [bytes.len(), 1] {
+        let mut tester = Tester::new(expected);
+        let mut parser = SaxParser::new();
+        parser.set_recovery(true);
+        for chunk in bytes.chunks(chunk_size) {
+            let mut elements = parser.elements(chunk);
+            while let Some(element) = elements.next() {
+                tester.check_element(&element.unwrap());
+            }
+        }
+        assert_eq!(tester.current, expected.len());
+        assert_eq!(parser.parse_finish(), Ok(()));
+        let diagnostics: Vec<(usize, ParseErrorKind)> = parser
+            .take_diagnostics()
+            .into_iter()
+            .map(|err| (err.location().unwrap().bytes, err.kind().unwrap().clone()))
+            .collect();
+        assert_eq!(diagnostics, errors);
+        assert!(parser.diagnostics().is_empty());
+    }
+}
+
+#[test]
+fn recovery() {
+    check_recovery(
+        b"<a><b c=1>x</b><c>&bad;y</c></a>",
+        &[
+            SaxElement::StartTag("a"),
+            SaxElement::StartTagContent,
+            SaxElement::StartTag("b"),
+            SaxElement::StartTagContent,
+            SaxElement::CData("x"),
+            SaxElement::EndTag("b"),
+            SaxElement::StartTag("c"),
+            SaxElement::StartTagContent,
+            SaxElement::EndTag("c"),
+            SaxElement::EndTag("a"),
+        ],
+        &[
+            (8, ParseErrorKind::TagAttributeWithoutQuote),
+            (22, ParseErrorKind::ReferenceCustomEntity),
+        ],
+    );
+    check_recovery(
+        b"<a><b c<d/></b></a>",
+        &[
+            SaxElement::StartTag("a"),
+            SaxElement::StartTagContent,
+            SaxElement::StartTag("b"),
+            SaxElement::StartTagContent,
+            SaxElement::StartTag("d"),
+            SaxElement::StartTagEmpty,
+            SaxElement::EndTag("b"),
+            SaxElement::EndTag("a"),
+        ],
+        &[(7, ParseErrorKind::TagAttributeBadName)],
+    );
+    check_recovery(
+        b"junk<a>\xFF</a> tail",
+        &[
+            SaxElement::StartTag("a"),
+            SaxElement::StartTagContent,
+            SaxElement::EndTag("a"),
+        ],
+        &[
+            (0, ParseErrorKind::DocCDataWithoutParent),
+            (7, ParseErrorKind::Utf8InvalidPrefixByte),
+            (13, ParseErrorKind::DocCDataWithoutParent),
+        ],
+    );
+    check_recovery(
+        b"<a><b></b x><c/></a>",
+        &[
+            SaxElement::StartTag("a"),
+            SaxElement::StartTagContent,
+            SaxElement::StartTag("b"),
+            SaxElement::StartTagContent,
+            SaxElement::EndTag("b"),
+            SaxElement::StartTag("c"),
+            SaxElement::StartTagEmpty,
+            SaxElement::EndTag("a"),
+        ],
+        &[(10, ParseErrorKind::TagEndTagAttributes)],
+    );
+
+    let mut parser = SaxParser::new();
+    parser.set_recovery(true);
+    let mut elements = parser.elements(b"<a></a");
+    while let Some(element) = elements.next() {
+        element.unwrap();
+    }
+    parser.reset();
+    assert!(parser.recovery());
+    assert!(parser.diagnostics().is_empty());
+}
+
+fn check_strict(s: &str) -> Result<(), ParseError> {
+    // Whole input and byte by byte must give the same result
+    let mut results = Vec::new();
+    for chunk_size in [s.len(), 1] {
+        let mut parser = SaxParser::new();
+        parser.set_strict(true);
+        let result = (|| {
+            for chunk in s.as_bytes().chunks(chunk_size) {
+                let mut elements = parser.elements(chunk);
+                while let Some(element) = elements.next() {
+                    element?;
+                }
+            }
+            parser.parse_finish()
+        })();
+        results.push(result);
+    }
+    assert_eq!(results[0], results[1]);
+    results.pop().unwrap()
+}
+
+#[test]
+fn strict() {
+    assert_eq!(
+        check_strict("<a x='1' y='1'><b/><c><b x='2'/></c></a>"),
+        Ok(())
+    );
+    assert_eq!(check_strict("<a><a><a/></a></a>"), Ok(()));
+
+    let err = check_strict("<a><b x='1'></a></b>").unwrap_err();
+    assert_eq!(
+        err.kind(),
+        Some(&ParseErrorKind::TagMismatch(Box::new(TagMismatch {
+            open_name: "b".to_string(),
+            open_location: Some(location_at("<a><b x='1'></a></b>", 5)),
+            close_name: "a".to_string(),
+            close_location: Some(location_at("<a><b x='1'></a></b>", 15)),
+        })))
+    );
+    assert_eq!(err.location().map(|location| location.bytes), Some(15));
+
+    let err = check_strict("<a x='1' y='2' x='3'/>").unwrap_err();
+    assert_eq!(err.kind(), Some(&ParseErrorKind::DuplicateAttribute));
+    assert_eq!(err.location().map(|location| location.bytes), Some(19));
+    let err = check_strict("<a x='&amp;' x='2'/>").unwrap_err();
+    assert_eq!(err.kind(), Some(&ParseErrorKind::DuplicateAttribute));
+
+    // Same names in different tags are fine
+    assert_eq!(check_strict("<a x='1'><b x='1'/></a>"), Ok(()));
+
+    let mut parser = SaxParser::new();
+    parser.set_strict(true);
+    parser.set_recovery(true);
+    let mut names = Vec::new();
+    let mut elements = parser.elements(b"<a><b><c></b><d x='1' x='2' y='3'/></x></a>");
+    while let Some(element) = elements.next() {
+        match element.unwrap() {
+            SaxElement::EndTag(name) => names.push(format!("/{name}")),
+            SaxElement::Attribute(name, _) => names.push(name.to_string()),
+            _ => (),
+        }
+    }
+    assert_eq!(parser.parse_finish(), Ok(()));
+    assert_eq!(names, ["/b", "x", "y", "/a"]);
+    let descriptions: Vec<_> = parser
+        .diagnostics()
+        .iter()
+        .map(|err| err.kind().unwrap().description())
+        .collect();
+    assert_eq!(
+        descriptions,
+        [
+            "start and end tags have different names",
+            "attribute name already used in this tag",
+            "start and end tags have different names",
+        ]
+    );
 }
 
 #[test]
@@ -543,25 +1021,434 @@ fn prints() {
 
     {
         let location = parser.location();
-        let s1 = format!("{:?}", location);
-        assert!(s1.len() > 0);
-        let s2 = format!("{}", location);
-        assert!(s2.len() > 0);
+        let s1 = format!("{location:?}");
+        assert!(!s1.is_empty());
+        let s2 = format!("{location}");
+        assert!(!s2.is_empty());
     }
 
     {
         let e = ParseError::NoMemory;
-        let s1 = format!("{:?}", e);
-        assert!(s1.len() > 0);
-        let s2 = format!("{}", e);
-        assert!(s2.len() > 0);
+        let s1 = format!("{e:?}");
+        assert!(!s1.is_empty());
+        let s2 = format!("{e}");
+        assert!(!s2.is_empty());
     }
 
     {
-        let e = ParseError::BadXml("test");
-        let s1 = format!("{:?}", e);
-        assert!(s1.len() > 0);
-        let s2 = format!("{}", e);
-        assert!(s2.len() > 0);
+        let e = ParseError::from(ParseErrorKind::TagEmptyName);
+        let s1 = format!("{e:?}");
+        assert!(!s1.is_empty());
+        let s2 = format!("{e}");
+        assert!(!s2.is_empty());
+    }
+}
+
+fn qname<'a>(
+    namespace_uri: Option<&'a str>,
+    prefix: Option<&'a str>,
+    local_name: &'a str,
+) -> QName<'a> {
+    QName {
+        namespace_uri,
+        local_name,
+        prefix,
+    }
+}
+
+fn check_namespaces(s: &str, expected: &[NamespaceElement]) {
+    let mut resolver = NamespaceResolver::new();
+    let mut current = 0;
+    let mut elements = resolver.elements(s.as_bytes());
+    while let Some(element) = elements.next() {
+        assert_eq!(element.unwrap(), expected[current]);
+        current += 1;
+    }
+    assert_eq!(resolver.parse_finish(), Ok(()));
+    assert_eq!(current, expected.len());
+
+    // now try byte by byte
+    resolver.reset();
+    current = 0;
+    for i in 0..s.len() {
+        let mut elements = resolver.elements(&s.as_bytes()[i..i + 1]);
+        while let Some(element) = elements.next() {
+            assert_eq!(element.unwrap(), expected[current]);
+            current += 1;
+        }
+    }
+    assert_eq!(resolver.parse_finish(), Ok(()));
+    assert_eq!(current, expected.len());
+}
+
+fn check_bad_namespaces(s: &str, kind: ParseErrorKind) {
+    let mut resolver = NamespaceResolver::new();
+    let mut elements = resolver.elements(s.as_bytes());
+    let mut error = None;
+    while let Some(element) = elements.next() {
+        if let Err(err) = element {
+            error = Some(err);
+            break;
+        }
+    }
+    assert_eq!(
+        error.as_ref().and_then(ParseError::kind),
+        Some(&kind),
+        "Wrong result for {s}"
+    );
+    assert!(error.unwrap().location().is_some());
+}
+
+#[test]
+fn namespaces() {
+    check_namespaces(
+        "<a xmlns='urn:a' xmlns:b='urn:b'><b:c b:x='1' y='2'/><d xmlns=''>t</d></a>",
+        &[
+            NamespaceElement::StartTag(qname(Some("urn:a"), None, "a")),
+            NamespaceElement::Attribute(qname(Some(XMLNS_NS), None, "xmlns"), "urn:a"),
+            NamespaceElement::Attribute(qname(Some(XMLNS_NS), Some("xmlns"), "b"), "urn:b"),
+            NamespaceElement::StartTagContent,
+            NamespaceElement::StartTag(qname(Some("urn:b"), Some("b"), "c")),
+            NamespaceElement::Attribute(qname(Some("urn:b"), Some("b"), "x"), "1"),
+            NamespaceElement::Attribute(qname(None, None, "y"), "2"),
+            NamespaceElement::StartTagEmpty,
+            NamespaceElement::StartTag(qname(None, None, "d")),
+            NamespaceElement::Attribute(qname(Some(XMLNS_NS), None, "xmlns"), ""),
+            NamespaceElement::StartTagContent,
+            NamespaceElement::CData("t"),
+            NamespaceElement::EndTag(qname(None, None, "d")),
+            NamespaceElement::EndTag(qname(Some("urn:a"), None, "a")),
+        ],
+    );
+
+    check_namespaces(
+        "<p:a xmlns:p='u1'><p:b xmlns:p='u2'/><p:c xml:lang='en'></p:c></p:a>",
+        &[
+            NamespaceElement::StartTag(qname(Some("u1"), Some("p"), "a")),
+            NamespaceElement::Attribute(qname(Some(XMLNS_NS), Some("xmlns"), "p"), "u1"),
+            NamespaceElement::StartTagContent,
+            NamespaceElement::StartTag(qname(Some("u2"), Some("p"), "b")),
+            NamespaceElement::Attribute(qname(Some(XMLNS_NS), Some("xmlns"), "p"), "u2"),
+            NamespaceElement::StartTagEmpty,
+            NamespaceElement::StartTag(qname(Some("u1"), Some("p"), "c")),
+            NamespaceElement::Attribute(qname(Some(XML_NS), Some("xml"), "lang"), "en"),
+            NamespaceElement::StartTagContent,
+            NamespaceElement::EndTag(qname(Some("u1"), Some("p"), "c")),
+            NamespaceElement::EndTag(qname(Some("u1"), Some("p"), "a")),
+        ],
+    );
+
+    check_namespaces(
+        "<s:stream xmlns:s='http://etherx.jabber.org/streams'><s:features/></s:stream>",
+        &[
+            NamespaceElement::StartTag(qname(
+                Some("http://etherx.jabber.org/streams"),
+                Some("s"),
+                "stream",
+            )),
+            NamespaceElement::Attribute(
+                qname(Some(XMLNS_NS), Some("xmlns"), "s"),
+                "http://etherx.jabber.org/streams",
+            ),
+            NamespaceElement::StartTagContent,
+            NamespaceElement::StartTag(qname(
+                Some("http://etherx.jabber.org/streams"),
+                Some("s"),
+                "features",
+            )),
+            NamespaceElement::StartTagEmpty,
+            NamespaceElement::EndTag(qname(
+                Some("http://etherx.jabber.org/streams"),
+                Some("s"),
+                "stream",
+            )),
+        ],
+    );
+}
+
+#[test]
+fn namespace_lookups() {
+    let mut resolver = NamespaceResolver::new();
+    let mut elements = resolver.elements(b"<a xmlns='urn:x' xmlns:y='urn:y'>");
+    while let Some(element) = elements.next() {
+        element.unwrap();
+    }
+    assert_eq!(resolver.lookup_namespace(""), Some("urn:x"));
+    assert_eq!(resolver.lookup_namespace("y"), Some("urn:y"));
+    assert_eq!(resolver.lookup_namespace("xml"), Some(XML_NS));
+    assert_eq!(resolver.lookup_namespace("z"), None);
+
+    let name = QName::split("stream:features");
+    assert_eq!(name.prefix, Some("stream"));
+    assert_eq!(name.local_name, "features");
+    assert_eq!(name.namespace_uri, None);
+    assert_eq!(QName::split("message").prefix, None);
+}
+
+#[test]
+fn bad_namespaces() {
+    check_bad_namespaces("<a:b/>", ParseErrorKind::NsUnboundPrefix);
+    check_bad_namespaces("<a><b x:y='1'/></a>", ParseErrorKind::NsUnboundPrefix);
+    check_bad_namespaces("<a xmlns:p='u'></a><p:b/>", ParseErrorKind::TagOutsideRoot);
+    check_bad_namespaces(
+        "<a><b xmlns:p='u'/><p:c/></a>",
+        ParseErrorKind::NsUnboundPrefix,
+    );
+    check_bad_namespaces("<a></p:a>", ParseErrorKind::NsUnboundPrefix);
+    check_bad_namespaces("<:a/>", ParseErrorKind::NsBadQName);
+    check_bad_namespaces("<a:/>", ParseErrorKind::NsBadQName);
+    check_bad_namespaces("<a b:c:d='1'/>", ParseErrorKind::NsBadQName);
+    check_bad_namespaces("<a xmlns:xmlns='u'/>", ParseErrorKind::NsReservedPrefix);
+    check_bad_namespaces("<a xmlns:xml='u'/>", ParseErrorKind::NsReservedPrefix);
+    check_bad_namespaces(
+        "<a xmlns:p='http://www.w3.org/2000/xmlns/'/>",
+        ParseErrorKind::NsReservedPrefix,
+    );
+    check_bad_namespaces("<a xmlns:p=''/>", ParseErrorKind::NsEmptyPrefixUri);
+    check_bad_namespaces(
+        "<a xmlns:p='u' xmlns:q='u' p:x='1' q:x='2'/>",
+        ParseErrorKind::NsDuplicateAttribute,
+    );
+}
+
+fn parse_with_limits(limits: ParserLimits, s: &str, byte_by_byte: bool) -> Result<(), ParseError> {
+    let mut parser = SaxParser::with_limits(limits);
+    let chunk_size = if byte_by_byte { 1 } else { s.len() };
+    for chunk in s.as_bytes().chunks(chunk_size) {
+        let mut elements = parser.elements(chunk);
+        while let Some(element) = elements.next() {
+            element?;
+        }
+    }
+    parser.parse_finish()
+}
+
+fn check_limit(limits: ParserLimits, good: &str, bad: &str, err: ParseError) {
+    assert_eq!(parse_with_limits(limits, good, false), Ok(()));
+    assert_eq!(parse_with_limits(limits, good, true), Ok(()));
+    assert_eq!(parse_with_limits(limits, bad, false), Err(err.clone()));
+    assert_eq!(parse_with_limits(limits, bad, true), Err(err));
+}
+
+#[test]
+fn limits() {
+    let unlimited = ParserLimits::default();
+
+    check_limit(
+        ParserLimits {
+            max_depth: 3,
+            ..unlimited
+        },
+        "<a><b><c/></b><b><c></c></b></a>",
+        "<a><b><c><d/></c></b></a>",
+        ParseError::TooDeep,
+    );
+    check_limit(
+        ParserLimits {
+            max_name_length: 4,
+            ..unlimited
+        },
+        "<abcd abcd='12345'><abc/></abcd>",
+        "<a><abcde/></a>",
+        ParseError::NameTooLong,
+    );
+    check_limit(
+        ParserLimits {
+            max_name_length: 4,
+            ..unlimited
+        },
+        "<abcd abcd='12345'><abc/></abcd>",
+        "<a abcde='1'/>",
+        ParseError::NameTooLong,
+    );
+    check_limit(
+        ParserLimits {
+            max_attributes: 2,
+            ..unlimited
+        },
+        "<a x='1' y='2'><b x='1' y='2'/></a>",
+        "<a x='1' y='2' z='3'/>",
+        ParseError::TooManyAttributes,
+    );
+    check_limit(
+        ParserLimits {
+            max_attribute_value_length: 4,
+            ..unlimited
+        },
+        "<a x='1234' y='&amp;&#x41;&lt;4'/>",
+        "<a x='12&amp;45'/>",
+        ParseError::AttributeValueTooLong,
+    );
+    check_limit(
+        ParserLimits {
+            max_cdata_length: 6,
+            ..unlimited
+        },
+        "<a>abcdef<b>&amp;bcdef</b><![CDATA[]]]]>abc</a>",
+        "<a>abc<!-- comment -->abcd</a>",
+        ParseError::CDataTooLong,
+    );
+    check_limit(
+        ParserLimits {
+            max_cdata_length: 6,
+            ..unlimited
+        },
+        "<a>abcdef</a>",
+        "<a>abc&#xC7;&#x131;</a>",
+        ParseError::CDataTooLong,
+    );
+    check_limit(
+        ParserLimits {
+            max_total_bytes: 8,
+            ..unlimited
+        },
+        "<a>b</a>",
+        "<a>bc</a>",
+        ParseError::InputTooLong,
+    );
+}
+
+fn check_declaration(bytes: &[u8]) -> Result<Option<XmlDeclaration>, ParseError> {
+    // Whole input and byte by byte must give the same result
+    let mut results = Vec::new();
+    for chunk_size in [bytes.len(), 1] {
+        let mut parser = SaxParser::new();
+        let result = (|| {
+            for chunk in bytes.chunks(chunk_size) {
+                let mut elements = parser.elements(chunk);
+                while let Some(element) = elements.next() {
+                    element?;
+                }
+            }
+            parser.parse_finish()?;
+            Ok(parser.xml_declaration().cloned())
+        })();
+        results.push(result);
     }
+    assert_eq!(results[0], results[1]);
+    results.pop().unwrap()
+}
+
+#[test]
+fn xml_declaration() {
+    assert_eq!(check_declaration(b"<a/>"), Ok(None));
+    assert_eq!(
+        check_declaration(b"<?xml version='1.0'?><a/>"),
+        Ok(Some(XmlDeclaration {
+            version: "1.0".to_string(),
+            encoding: None,
+            standalone: None,
+        }))
+    );
+    assert_eq!(
+        check_declaration(
+            b"<?xml version = \"1.1\"\n encoding='utf-8' standalone=\"yes\" ?>\n<a/>"
+        ),
+        Ok(Some(XmlDeclaration {
+            version: "1.1".to_string(),
+            encoding: Some("utf-8".to_string()),
+            standalone: Some(true),
+        }))
+    );
+    assert_eq!(
+        check_declaration(b"\xef\xbb\xbf<?xml version='1.0' standalone='no'?><a/>"),
+        Ok(Some(XmlDeclaration {
+            version: "1.0".to_string(),
+            encoding: None,
+            standalone: Some(false),
+        }))
+    );
+    assert_eq!(check_declaration(b"\xef\xbb\xbf<a>lala</a>"), Ok(None));
+    // Other processing instructions are skipped
+    assert_eq!(
+        check_declaration(b"<?xml-stylesheet href='a.xsl'?><a/>"),
+        Ok(None)
+    );
+    assert_eq!(
+        check_declaration(b"<a/><?xml-stylesheet href='a.xsl'?>"),
+        Ok(None)
+    );
+
+    BadTester::new(
+        42,
+        ParseErrorKind::XmlDeclEncoding("ISO-8859-1".to_string()),
+    )
+    .check("<?xml version='1.0' encoding='ISO-8859-1'?><a/>");
+    BadTester::new(6, ParseErrorKind::XmlDeclBad).check("<?xml?><a/>");
+    BadTester::new(23, ParseErrorKind::XmlDeclBad).check("<?xml encoding='UTF-8'?><a/>");
+    BadTester::new(20, ParseErrorKind::XmlDeclBad).check("<?xml version='2.0'?><a/>");
+    BadTester::new(36, ParseErrorKind::XmlDeclBad)
+        .check("<?xml version='1.0'standalone='yes'?><a/>");
+    BadTester::new(39, ParseErrorKind::XmlDeclBad)
+        .check("<?xml version='1.0' standalone='maybe'?><a/>");
+    // Declaration is only allowed at the start of the document
+    BadTester::new(6, ParseErrorKind::XmlDeclBad)
+        .check(" <?xml version='1.0' encoding='latin1'?><a/>");
+    BadTester::new(9, ParseErrorKind::XmlDeclBad).check("<a/><?xml version='1.0'?>");
+    BadTester::new(9, ParseErrorKind::XmlDeclBad).check("<a><?xml?></a>");
+    BadTester::new(2, ParseErrorKind::DocCDataWithoutParent).check_bytes(b"\xef\xbf\xbd<a/>");
+    BadTester::new(4, ParseErrorKind::DocCDataWithoutParent).check_bytes(b"<a/>\xef\xbb\xbf");
+}
+
+fn check_text_origins(s: &str) -> Vec<(&'static str, String)> {
+    // Whole input and byte by byte must give the same result
+    let mut results = Vec::new();
+    for chunk_size in [s.len(), 1] {
+        let mut parser = SaxParser::new();
+        parser.set_text_origins(true);
+        let mut texts: Vec<(&'static str, String)> = Vec::new();
+        for chunk in s.as_bytes().chunks(chunk_size) {
+            let mut elements = parser.elements(chunk);
+            while let Some(element) = elements.next() {
+                let (origin, text) = match element.unwrap() {
+                    SaxElement::CData(text) => ("text", text),
+                    SaxElement::CDataSection(text) => ("section", text),
+                    SaxElement::Reference(text) => ("reference", text),
+                    _ => continue,
+                };
+                match texts.last_mut() {
+                    Some((last, last_text)) if *last == origin && origin != "reference" => {
+                        last_text.push_str(text)
+                    }
+                    _ => texts.push((origin, text.to_string())),
+                }
+            }
+        }
+        assert_eq!(parser.parse_finish(), Ok(()));
+        results.push(texts);
+    }
+    assert_eq!(results[0], results[1]);
+    results.pop().unwrap()
+}
+
+#[test]
+fn text_origins() {
+    assert_eq!(
+        check_text_origins("<a>x&lt;y<![CDATA[<b>&lt;]]]]>z&#x131;&#65;</a>"),
+        vec![
+            ("text", "x".to_string()),
+            ("reference", "<".to_string()),
+            ("text", "y".to_string()),
+            ("section", "<b>&lt;]]".to_string()),
+            ("text", "z".to_string()),
+            ("reference", "ı".to_string()),
+            ("reference", "A".to_string()),
+        ]
+    );
+    assert_eq!(
+        check_text_origins("<a><![CDATA[çü\r\nğ]]>ş\rı</a>"),
+        vec![
+            ("section", "çü\nğ".to_string()),
+            ("text", "ş\nı".to_string()),
+        ]
+    );
+
+    // Default mode sends all as CData
+    Tester::new(&[
+        SaxElement::StartTag("a"),
+        SaxElement::StartTagContent,
+        SaxElement::CData("x<y<b>&lt;]]zA"),
+        SaxElement::EndTag("a"),
+    ])
+    .check("<a>x&lt;y<![CDATA[<b>&lt;]]]]>z&#65;</a>");
 }
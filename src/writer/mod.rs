@@ -0,0 +1,446 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use std::error::Error;
+use std::fmt::Display;
+use std::io::Write;
+
+use crate::ParseError;
+use crate::ParseErrorKind;
+use crate::SaxElement;
+use crate::TagMismatch;
+use crate::entities::escape;
+use crate::parser::is_valid_xml_char;
+
+/// The error type for writing to a [Write] sink.
+///
+/// Errors from the writer are kept separate from the well-formedness
+/// errors, so applications can report them differently.
+#[derive(Debug)]
+pub enum WriteError {
+    /// Writing the output failed.
+    Io(std::io::Error),
+
+    /// Written elements do not make a well-formed document.
+    Xml(ParseError),
+}
+
+impl Display for WriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::Io(err) => err.fmt(f),
+            WriteError::Xml(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for WriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WriteError::Io(err) => Some(err),
+            WriteError::Xml(err) => Some(err),
+        }
+    }
+}
+
+impl From<std::io::Error> for WriteError {
+    fn from(err: std::io::Error) -> Self {
+        WriteError::Io(err)
+    }
+}
+
+impl From<ParseError> for WriteError {
+    fn from(err: ParseError) -> Self {
+        WriteError::Xml(err)
+    }
+}
+
+impl From<ParseErrorKind> for WriteError {
+    fn from(kind: ParseErrorKind) -> Self {
+        WriteError::Xml(kind.into())
+    }
+}
+
+fn is_name_start_char(c: char) -> bool {
+    matches!(c,
+        ':' | 'A'..='Z' | '_' | 'a'..='z'
+        | '\u{c0}'..='\u{d6}'
+        | '\u{d8}'..='\u{f6}'
+        | '\u{f8}'..='\u{2ff}'
+        | '\u{370}'..='\u{37d}'
+        | '\u{37f}'..='\u{1fff}'
+        | '\u{200c}'..='\u{200d}'
+        | '\u{2070}'..='\u{218f}'
+        | '\u{2c00}'..='\u{2fef}'
+        | '\u{3001}'..='\u{d7ff}'
+        | '\u{f900}'..='\u{fdcf}'
+        | '\u{fdf0}'..='\u{fffd}'
+        | '\u{10000}'..='\u{effff}'
+    )
+}
+
+fn is_name_char(c: char) -> bool {
+    is_name_start_char(c)
+        || matches!(c,
+            '-' | '.' | '0'..='9' | '\u{b7}'
+            | '\u{300}'..='\u{36f}'
+            | '\u{203f}'..='\u{2040}'
+        )
+}
+
+fn check_name(name: &str) -> Result<(), ParseErrorKind> {
+    let mut chars = name.chars();
+    match chars.next() {
+        None => Err(ParseErrorKind::NameInvalid),
+        Some(c) if !is_name_start_char(c) => Err(ParseErrorKind::NameInvalid),
+        Some(_) if !chars.all(is_name_char) => Err(ParseErrorKind::NameInvalid),
+        Some(_) => Ok(()),
+    }
+}
+
+fn check_chars(s: &str) -> Result<(), ParseErrorKind> {
+    if s.chars().all(|c| is_valid_xml_char(c as u32)) {
+        Ok(())
+    } else {
+        Err(ParseErrorKind::CharInvalid)
+    }
+}
+
+/// A streaming XML writer.
+///
+/// This is the inverse of the [SaxParser](crate::SaxParser). Elements
+/// are written to the sink as they are given, so large documents can
+/// be produced without building a [Document](crate::Document) first.
+/// Text and attribute values are escaped, and the well-formedness of
+/// the output is enforced: tags must be balanced, attributes cannot be
+/// written after the content of a tag or repeated, and the names must
+/// be valid.
+///
+/// Elements from a parser can be passed with the
+/// [write_element](XmlWriter::write_element) method, so a parse, filter,
+/// and write pipeline can run without keeping the whole document.
+///
+/// Each call writes to the sink directly, so wrapping it with a
+/// [BufWriter](std::io::BufWriter) is recommended.
+///
+/// # Examples
+///
+/// ```
+/// use iks::{WriteError, XmlWriter};
+/// # fn main() -> Result<(), WriteError> {
+///
+/// let mut writer = XmlWriter::new(Vec::new());
+/// writer.start_tag("doc")?;
+/// writer.attribute("lang", "en")?;
+/// writer.empty_tag("br")?;
+/// writer.start_tag("p")?;
+/// writer.text("1 < 2")?;
+/// writer.end_tag("p")?;
+/// writer.end_tag("doc")?;
+/// let output = writer.finish()?;
+///
+/// assert_eq!(output, b"<doc lang=\"en\"><br/><p>1 &lt; 2</p></doc>");
+/// # Ok(())
+/// # }
+/// ```
+pub struct XmlWriter<W: Write> {
+    writer: W,
+    open_names: String,
+    open_tags: Vec<usize>,
+    attribute_names: String,
+    attribute_starts: Vec<usize>,
+    in_start_tag: bool,
+    is_empty_tag: bool,
+    in_cdata_section: bool,
+    section_brackets: usize,
+    seen_root: bool,
+    buffer: String,
+}
+
+impl<W: Write> XmlWriter<W> {
+    /// Creates a new writer for the given sink.
+    pub fn new(writer: W) -> Self {
+        XmlWriter {
+            writer,
+            open_names: String::new(),
+            open_tags: Vec::new(),
+            attribute_names: String::new(),
+            attribute_starts: Vec::new(),
+            in_start_tag: false,
+            is_empty_tag: false,
+            in_cdata_section: false,
+            section_brackets: 0,
+            seen_root: false,
+            buffer: String::new(),
+        }
+    }
+
+    /// Returns the underlying sink.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns the number of the open tags.
+    pub fn depth(&self) -> usize {
+        self.open_tags.len()
+    }
+
+    fn write_str(&mut self, s: &str) -> Result<(), WriteError> {
+        self.writer.write_all(s.as_bytes())?;
+        Ok(())
+    }
+
+    fn write_escaped(&mut self, s: &str) -> Result<(), WriteError> {
+        self.buffer.clear();
+        escape(s, &mut self.buffer);
+        self.writer.write_all(self.buffer.as_bytes())?;
+        Ok(())
+    }
+
+    fn pop_tag(&mut self) {
+        if let Some(start) = self.open_tags.pop() {
+            self.open_names.truncate(start);
+        }
+    }
+
+    // Finishes the pending start tag or character data section
+    // before the next element.
+    fn close_markup(&mut self) -> Result<(), WriteError> {
+        if self.in_cdata_section {
+            self.in_cdata_section = false;
+            self.write_str("]]>")?;
+        }
+        if self.in_start_tag {
+            self.in_start_tag = false;
+            if self.is_empty_tag {
+                self.write_str("/>")?;
+                self.pop_tag();
+            } else {
+                self.write_str(">")?;
+            }
+        }
+        Ok(())
+    }
+
+    // Records the attribute name, returns false if it is already used
+    // in the current start tag.
+    fn push_attribute(&mut self, name: &str) -> Result<bool, WriteError> {
+        for (index, start) in self.attribute_starts.iter().enumerate() {
+            let end = match self.attribute_starts.get(index + 1) {
+                Some(end) => *end,
+                None => self.attribute_names.len(),
+            };
+            if &self.attribute_names[*start..end] == name {
+                return Ok(false);
+            }
+        }
+        if self.attribute_names.try_reserve(name.len()).is_err()
+            || self.attribute_starts.try_reserve(1).is_err()
+        {
+            return Err(ParseError::NoMemory.into());
+        }
+        self.attribute_starts.push(self.attribute_names.len());
+        self.attribute_names.push_str(name);
+        Ok(true)
+    }
+
+    fn check_content(&self) -> Result<(), WriteError> {
+        if self.open_tags.is_empty() {
+            return Err(ParseErrorKind::DocCDataWithoutParent.into());
+        }
+        Ok(())
+    }
+
+    /// Writes a start tag.
+    ///
+    /// Attributes of the tag can be written with the
+    /// [attribute](XmlWriter::attribute) method until some content
+    /// or another tag is written. If the tag is closed without any
+    /// content, it is written as an empty element tag.
+    pub fn start_tag(&mut self, name: &str) -> Result<(), WriteError> {
+        check_name(name)?;
+        self.close_markup()?;
+        if self.open_tags.is_empty() && self.seen_root {
+            return Err(ParseErrorKind::TagOutsideRoot.into());
+        }
+        if self.open_tags.try_reserve(1).is_err()
+            || self.open_names.try_reserve(name.len()).is_err()
+        {
+            return Err(ParseError::NoMemory.into());
+        }
+        self.write_str("<")?;
+        self.write_str(name)?;
+        self.open_tags.push(self.open_names.len());
+        self.open_names.push_str(name);
+        self.attribute_names.clear();
+        self.attribute_starts.clear();
+        self.in_start_tag = true;
+        self.is_empty_tag = false;
+        self.seen_root = true;
+        Ok(())
+    }
+
+    /// Writes an empty element tag.
+    ///
+    /// Attributes of the tag can be written with the
+    /// [attribute](XmlWriter::attribute) method, and the tag is
+    /// closed automatically with the next element.
+    pub fn empty_tag(&mut self, name: &str) -> Result<(), WriteError> {
+        self.start_tag(name)?;
+        self.is_empty_tag = true;
+        Ok(())
+    }
+
+    /// Writes an attribute of the last start tag.
+    ///
+    /// The value is escaped as needed. A [StartTagClosed](ParseErrorKind::StartTagClosed)
+    /// error is returned if the content of the tag is already written,
+    /// and a [DuplicateAttribute](ParseErrorKind::DuplicateAttribute)
+    /// error if the tag already has an attribute with the same name.
+    pub fn attribute(&mut self, name: &str, value: &str) -> Result<(), WriteError> {
+        if !self.in_start_tag {
+            return Err(ParseErrorKind::StartTagClosed.into());
+        }
+        check_name(name)?;
+        check_chars(value)?;
+        if !self.push_attribute(name)? {
+            return Err(ParseErrorKind::DuplicateAttribute.into());
+        }
+        self.write_str(" ")?;
+        self.write_str(name)?;
+        self.write_str("=\"")?;
+        self.write_escaped(value)?;
+        self.write_str("\"")
+    }
+
+    /// Writes character data.
+    ///
+    /// The text is escaped as needed. Whitespace can be written
+    /// outside of the root tag, other text must be inside it.
+    pub fn text(&mut self, text: &str) -> Result<(), WriteError> {
+        check_chars(text)?;
+        self.close_markup()?;
+        if !text.bytes().all(|c| c.is_ascii_whitespace()) {
+            self.check_content()?;
+        }
+        self.write_escaped(text)
+    }
+
+    /// Writes character data inside a `<![CDATA[...]]>` section.
+    ///
+    /// Consecutive calls are written into the same section. Any `]]>`
+    /// sequence in the text is split into two sections, so the text
+    /// is kept as it is.
+    pub fn cdata_section(&mut self, text: &str) -> Result<(), WriteError> {
+        check_chars(text)?;
+        if !self.in_cdata_section {
+            self.close_markup()?;
+            self.check_content()?;
+            self.write_str("<![CDATA[")?;
+            self.in_cdata_section = true;
+            self.section_brackets = 0;
+        }
+        let mut back = 0;
+        for (i, c) in text.bytes().enumerate() {
+            match c {
+                b']' => self.section_brackets += 1,
+                b'>' if self.section_brackets >= 2 => {
+                    self.write_str(&text[back..i])?;
+                    self.write_str("]]><![CDATA[")?;
+                    back = i;
+                    self.section_brackets = 0;
+                }
+                _ => self.section_brackets = 0,
+            }
+        }
+        self.write_str(&text[back..])
+    }
+
+    /// Writes an end tag.
+    ///
+    /// The name must match the last open tag. If the tag has no content,
+    /// it is written as an empty element tag.
+    pub fn end_tag(&mut self, name: &str) -> Result<(), WriteError> {
+        if self.in_start_tag && self.is_empty_tag {
+            self.close_markup()?;
+        }
+        let Some(start) = self.open_tags.last() else {
+            return Err(ParseErrorKind::TagCloseWithoutOpen.into());
+        };
+        let open_name = &self.open_names[*start..];
+        if open_name != name {
+            return Err(ParseErrorKind::TagMismatch(Box::new(TagMismatch {
+                open_name: open_name.to_string(),
+                open_location: None,
+                close_name: name.to_string(),
+                close_location: None,
+            }))
+            .into());
+        }
+        if self.in_start_tag {
+            self.in_start_tag = false;
+            self.write_str("/>")?;
+        } else {
+            self.close_markup()?;
+            self.write_str("</")?;
+            self.write_str(name)?;
+            self.write_str(">")?;
+        }
+        self.pop_tag();
+        Ok(())
+    }
+
+    /// Writes an element returned from a [SaxParser](crate::SaxParser).
+    ///
+    /// Start and empty element tags are written as they are in the
+    /// parsed document. Character data sections are kept when the
+    /// parser is in the [text origins](crate::SaxParser::set_text_origins)
+    /// mode.
+    pub fn write_element(&mut self, element: &SaxElement) -> Result<(), WriteError> {
+        match element {
+            SaxElement::StartTag(name) => self.start_tag(name),
+            SaxElement::Attribute(name, value) => self.attribute(name, value),
+            SaxElement::StartTagContent => {
+                if !self.in_start_tag {
+                    return Err(ParseErrorKind::StartTagClosed.into());
+                }
+                self.close_markup()
+            }
+            SaxElement::StartTagEmpty => {
+                if !self.in_start_tag {
+                    return Err(ParseErrorKind::StartTagClosed.into());
+                }
+                self.is_empty_tag = true;
+                self.close_markup()
+            }
+            SaxElement::EndTag(name) => self.end_tag(name),
+            SaxElement::CData(text) | SaxElement::Reference(text) => self.text(text),
+            SaxElement::CDataSection(text) => self.cdata_section(text),
+        }
+    }
+
+    /// Checks that the document is complete and returns the underlying sink.
+    ///
+    /// The sink is flushed before it is returned.
+    pub fn finish(mut self) -> Result<W, WriteError> {
+        self.close_markup()?;
+        if !self.seen_root {
+            return Err(ParseErrorKind::DocNoContent.into());
+        }
+        if !self.open_tags.is_empty() {
+            return Err(ParseErrorKind::DocOpenTags.into());
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests;
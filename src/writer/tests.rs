@@ -0,0 +1,145 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use super::*;
+use crate::SaxParser;
+
+fn output(writer: XmlWriter<Vec<u8>>) -> String {
+    String::from_utf8(writer.finish().unwrap()).unwrap()
+}
+
+fn kind(result: Result<(), WriteError>) -> ParseErrorKind {
+    let Err(WriteError::Xml(err)) = result else {
+        unreachable!("expected a well-formedness error");
+    };
+    err.kind().unwrap().clone()
+}
+
+#[test]
+fn writes() {
+    let mut writer = XmlWriter::new(Vec::new());
+    writer.text("\n").unwrap();
+    writer.start_tag("a").unwrap();
+    writer.attribute("x", "'1' & \"2\"").unwrap();
+    writer.start_tag("b").unwrap();
+    writer.end_tag("b").unwrap();
+    writer.empty_tag("c").unwrap();
+    writer.attribute("y", "<").unwrap();
+    writer.text("1 < 2 > 0").unwrap();
+    writer.cdata_section("<raw>").unwrap();
+    writer.cdata_section("]]").unwrap();
+    writer.cdata_section(">]]>").unwrap();
+    writer.end_tag("a").unwrap();
+    assert_eq!(
+        output(writer),
+        "\n<a x=\"&apos;1&apos; &amp; &quot;2&quot;\"><b/><c y=\"&lt;\"/>1 &lt; 2 &gt; 0\
+         <![CDATA[<raw>]]]]><![CDATA[>]]]]><![CDATA[>]]></a>"
+    );
+}
+
+#[test]
+fn well_formedness() {
+    let mut writer = XmlWriter::new(Vec::new());
+    assert_eq!(
+        kind(writer.end_tag("a")),
+        ParseErrorKind::TagCloseWithoutOpen
+    );
+    assert_eq!(
+        kind(writer.text("abc")),
+        ParseErrorKind::DocCDataWithoutParent
+    );
+    assert_eq!(kind(writer.start_tag("1a")), ParseErrorKind::NameInvalid);
+    assert_eq!(kind(writer.start_tag("")), ParseErrorKind::NameInvalid);
+    writer.start_tag("a").unwrap();
+    assert_eq!(
+        kind(writer.attribute("x y", "1")),
+        ParseErrorKind::NameInvalid
+    );
+    assert_eq!(
+        kind(writer.attribute("x", "\u{1}")),
+        ParseErrorKind::CharInvalid
+    );
+    writer.attribute("x", "1").unwrap();
+    assert_eq!(
+        kind(writer.attribute("x", "2")),
+        ParseErrorKind::DuplicateAttribute
+    );
+    writer.text("abc").unwrap();
+    assert_eq!(
+        kind(writer.attribute("x", "1")),
+        ParseErrorKind::StartTagClosed
+    );
+    writer.start_tag("b").unwrap();
+    writer.attribute("x", "3").unwrap();
+    assert!(matches!(
+        kind(writer.end_tag("a")),
+        ParseErrorKind::TagMismatch(mismatch) if mismatch.open_name == "b"
+    ));
+    writer.end_tag("b").unwrap();
+    writer.end_tag("a").unwrap();
+    assert_eq!(kind(writer.start_tag("c")), ParseErrorKind::TagOutsideRoot);
+    assert_eq!(output(writer), "<a x=\"1\">abc<b x=\"3\"/></a>");
+
+    let mut writer = XmlWriter::new(Vec::new());
+    writer.start_tag("a").unwrap();
+    assert!(matches!(
+        writer.finish(),
+        Err(WriteError::Xml(ParseError::BadXml {
+            kind: ParseErrorKind::DocOpenTags,
+            ..
+        }))
+    ));
+    let writer = XmlWriter::new(Vec::new());
+    assert!(matches!(
+        writer.finish(),
+        Err(WriteError::Xml(ParseError::BadXml {
+            kind: ParseErrorKind::DocNoContent,
+            ..
+        }))
+    ));
+}
+
+#[test]
+fn sax_elements() {
+    let xml = "<a x='1'><b></b><c/>text &amp; <![CDATA[<raw>]]>&#65;</a>";
+
+    let mut parser = SaxParser::new();
+    parser.set_text_origins(true);
+    let mut writer = XmlWriter::new(Vec::new());
+    let mut elements = parser.elements(xml.as_bytes());
+    while let Some(element) = elements.next() {
+        writer.write_element(&element.unwrap()).unwrap();
+    }
+    assert_eq!(
+        output(writer),
+        "<a x=\"1\"><b></b><c/>text &amp; <![CDATA[<raw>]]>A</a>"
+    );
+
+    // Filter out a tag while streaming
+    let mut parser = SaxParser::new();
+    let mut writer = XmlWriter::new(Vec::new());
+    let mut skip = 0;
+    let mut elements = parser.elements(xml.as_bytes());
+    while let Some(element) = elements.next() {
+        let element = element.unwrap();
+        match element {
+            SaxElement::StartTag("b") => skip += 1,
+            SaxElement::EndTag("b") => {
+                skip -= 1;
+                continue;
+            }
+            _ => (),
+        }
+        if skip == 0 {
+            writer.write_element(&element).unwrap();
+        }
+    }
+    assert_eq!(output(writer), "<a x=\"1\"><c/>text &amp; &lt;raw&gt;A</a>");
+}
@@ -0,0 +1,256 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::net::lookup_host;
+use tokio::time::timeout;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::client::TlsStream;
+
+use crate::Document;
+use crate::Jid;
+use crate::XMPP_CLIENT_PORT;
+use crate::XmppClientBuilder;
+use crate::XmppClientError;
+use crate::XmppClientProtocol;
+use crate::xmpp::protocol::XmppClientProtocolEvent;
+
+use super::client::need_port;
+use super::client::tls_config;
+
+impl XmppClientBuilder {
+    /// Connects to the server and returns an [AsyncXmppClient].
+    ///
+    /// This is the async version of the [connect](XmppClientBuilder::connect)
+    /// method and it must be called within a tokio runtime.
+    pub async fn connect_async(self) -> Result<AsyncXmppClient, XmppClientError> {
+        let host = self.host();
+        let result: Vec<_> = if need_port(host) {
+            lookup_host((host, XMPP_CLIENT_PORT)).await?.collect()
+        } else {
+            lookup_host(host).await?.collect()
+        };
+        for addr in result {
+            if self.debug {
+                println!("Connecting to: {addr:?}");
+            }
+            match timeout(self.connection_timeout, TcpStream::connect(addr)).await {
+                Ok(Ok(tcp_stream)) => {
+                    return Ok(AsyncXmppClient::with_stream(
+                        XmppClientProtocol::new(self.jid, self.password),
+                        Box::new(tcp_stream),
+                        self.debug,
+                    ));
+                }
+                Ok(Err(err)) => {
+                    if self.debug {
+                        println!("Failed to connect to {addr:?}: {err}");
+                    }
+                }
+                Err(_) => {
+                    if self.debug {
+                        println!("Timed out while connecting to {addr:?}");
+                    }
+                }
+            }
+        }
+        Err(XmppClientError::BadStream("cannot connect"))
+    }
+}
+
+// Byte stream under the client, a TcpStream except in the tests
+pub(super) trait AsyncIo: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> AsyncIo for T {}
+
+struct AsyncXmppStream {
+    tcp_stream: Option<Box<dyn AsyncIo>>,
+    tls_stream: Option<Box<TlsStream<Box<dyn AsyncIo>>>>,
+}
+
+impl AsyncXmppStream {
+    fn new(tcp_stream: Box<dyn AsyncIo>) -> Self {
+        AsyncXmppStream {
+            tcp_stream: Some(tcp_stream),
+            tls_stream: None,
+        }
+    }
+
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        let result = if let Some(tcp) = &mut self.tcp_stream {
+            tcp.read(buf).await
+        } else if let Some(tls) = &mut self.tls_stream {
+            tls.read(buf).await
+        } else {
+            Err(std::io::Error::other("No stream"))
+        };
+        match result {
+            Ok(0) => Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "peer closed connection",
+            )),
+            result => result,
+        }
+    }
+
+    async fn write_all(&mut self, buf: &[u8]) -> Result<(), std::io::Error> {
+        if let Some(tcp) = &mut self.tcp_stream {
+            tcp.write_all(buf).await
+        } else if let Some(tls) = &mut self.tls_stream {
+            tls.write_all(buf).await?;
+            // TLS stream buffers the records until flushed
+            tls.flush().await
+        } else {
+            Err(std::io::Error::other("No stream"))
+        }
+    }
+
+    async fn upgrade(&mut self, jid: &Jid) -> Result<(), XmppClientError> {
+        let connector = TlsConnector::from(Arc::new(tls_config()?));
+        let server_name = jid.domainpart().to_owned().try_into()?;
+        match self.tcp_stream.take() {
+            Some(tcp_stream) => {
+                let tls = connector.connect(server_name, tcp_stream).await?;
+                self.tls_stream = Some(Box::new(tls));
+                Ok(())
+            }
+            None => Err(XmppClientError::BadStream("not possible")),
+        }
+    }
+}
+
+/// An XMPP client using the tokio async I/O.
+///
+/// This client drives the same [XmppClientProtocol] as the blocking
+/// [XmppClient](crate::XmppClient), so many connections can be served
+/// from a single tokio runtime without a thread per connection.
+///
+/// Only available with the `async` feature.
+pub struct AsyncXmppClient {
+    protocol: XmppClientProtocol,
+    stream: AsyncXmppStream,
+    read_buffer: [u8; 4096],
+    consumed: usize,
+    read: usize,
+    debug: bool,
+}
+
+impl AsyncXmppClient {
+    pub(super) fn with_stream(
+        protocol: XmppClientProtocol,
+        stream: Box<dyn AsyncIo>,
+        debug: bool,
+    ) -> Self {
+        AsyncXmppClient {
+            protocol,
+            stream: AsyncXmppStream::new(stream),
+            read_buffer: [0; 4096],
+            consumed: 0,
+            read: 0,
+            debug,
+        }
+    }
+
+    pub fn build(jid: Jid, password: String) -> XmppClientBuilder {
+        XmppClientBuilder::new(jid, password)
+    }
+
+    pub async fn send_bytes(&mut self, bytes: Vec<u8>) -> Result<(), XmppClientError> {
+        if self.debug {
+            println!("Sending bytes: {}", String::from_utf8_lossy(&bytes));
+        }
+        self.stream.write_all(bytes.as_slice()).await?;
+        Ok(())
+    }
+
+    pub async fn send_stanza(&mut self, stanza: Document) -> Result<(), XmppClientError> {
+        self.send_bytes(stanza.to_string().into_bytes()).await
+    }
+
+    pub async fn send_message(&mut self, jid: Jid, body: &str) -> Result<(), XmppClientError> {
+        let stanza = self.protocol.message_stanza(&jid, body)?;
+        self.send_stanza(stanza).await
+    }
+
+    pub async fn request_roster(&mut self) -> Result<(), XmppClientError> {
+        let stanza = self.protocol.roster_request_stanza()?;
+        self.send_stanza(stanza).await
+    }
+
+    pub async fn wait_for_stanza(&mut self) -> Result<Document, XmppClientError> {
+        loop {
+            match self.wait_for_stanza_timeout(None).await? {
+                Some(doc) => return Ok(doc),
+                None => continue,
+            }
+        }
+    }
+
+    /// Waits for the next stanza from the server.
+    ///
+    /// None is returned if the timeout expires before a stanza is
+    /// received. Partially received data is kept for the next call.
+    pub async fn wait_for_stanza_timeout(
+        &mut self,
+        timeout_duration: Option<Duration>,
+    ) -> Result<Option<Document>, XmppClientError> {
+        loop {
+            if let Some(bytes) = self.protocol.send_bytes() {
+                self.send_bytes(bytes).await?;
+            }
+            let bytes = if self.read > self.consumed {
+                &self.read_buffer[self.consumed..self.read]
+            } else {
+                let read = self.stream.read(&mut self.read_buffer);
+                let nr_read = match timeout_duration {
+                    None => read.await?,
+                    Some(duration) => match timeout(duration, read).await {
+                        Ok(result) => result?,
+                        Err(_) => return Ok(None),
+                    },
+                };
+                if self.debug {
+                    println!(
+                        "Received bytes: {}",
+                        String::from_utf8_lossy(&self.read_buffer[..nr_read])
+                    );
+                }
+                self.read = nr_read;
+                self.consumed = 0;
+                &self.read_buffer[..self.read]
+            };
+            match self.protocol.receive_bytes(bytes) {
+                Ok(Some((event, processed))) => {
+                    self.consumed += processed;
+                    match event {
+                        XmppClientProtocolEvent::Send(bytes) => self.send_bytes(bytes).await?,
+                        XmppClientProtocolEvent::StartTls => {
+                            self.stream.upgrade(self.protocol.jid()).await?;
+                        }
+                        XmppClientProtocolEvent::Continue => {}
+                        XmppClientProtocolEvent::Stanza(doc) => {
+                            return Ok(Some(doc));
+                        }
+                        XmppClientProtocolEvent::End => {}
+                    }
+                }
+                Ok(None) => self.consumed = self.read,
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
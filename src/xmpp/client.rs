@@ -21,6 +21,7 @@ use webpki_roots::TLS_SERVER_ROOTS;
 
 use crate::Document;
 use crate::Jid;
+use crate::SerializeOptions;
 use crate::XMPP_CLIENT_PORT;
 use crate::XmppClientError;
 use crate::XmppClientProtocol;
@@ -38,6 +39,18 @@ pub(super) fn need_port(host: &str) -> bool {
     }
 }
 
+pub(super) fn tls_config() -> Result<rustls::ClientConfig, XmppClientError> {
+    let root_store = RootCertStore {
+        roots: TLS_SERVER_ROOTS.into(),
+    };
+    Ok(rustls::ClientConfig::builder_with_provider(
+        rustls::crypto::aws_lc_rs::default_provider().into(),
+    )
+    .with_safe_default_protocol_versions()?
+    .with_root_certificates(root_store)
+    .with_no_client_auth())
+}
+
 fn resolve_host_with_default_port(
     host: &str,
     default_port: u16,
@@ -50,11 +63,11 @@ fn resolve_host_with_default_port(
 }
 
 pub struct XmppClientBuilder {
-    jid: Jid,
-    server: Option<String>,
-    password: String,
-    connection_timeout: Duration,
-    debug: bool,
+    pub(super) jid: Jid,
+    pub(super) server: Option<String>,
+    pub(super) password: String,
+    pub(super) connection_timeout: Duration,
+    pub(super) debug: bool,
 }
 
 impl XmppClientBuilder {
@@ -83,11 +96,15 @@ impl XmppClientBuilder {
         self
     }
 
-    pub fn connect(self) -> Result<XmppClient, XmppClientError> {
-        let host = match &self.server {
+    pub(super) fn host(&self) -> &str {
+        match &self.server {
             Some(server) => server,
             None => self.jid.domainpart(),
-        };
+        }
+    }
+
+    pub fn connect(self) -> Result<XmppClient, XmppClientError> {
+        let host = self.host();
         let result = resolve_host_with_default_port(host, XMPP_CLIENT_PORT)?;
         for addr in result {
             if self.debug {
@@ -120,6 +137,28 @@ struct XmppStream {
     tls_stream: Option<rustls::StreamOwned<rustls::ClientConnection, TcpStream>>,
 }
 
+impl Write for XmppStream {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        if let Some(tcp) = &mut self.tcp_stream {
+            tcp.write(buf)
+        } else if let Some(tls) = &mut self.tls_stream {
+            tls.write(buf)
+        } else {
+            Err(std::io::Error::other("No stream"))
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        if let Some(tcp) = &mut self.tcp_stream {
+            tcp.flush()
+        } else if let Some(tls) = &mut self.tls_stream {
+            tls.flush()
+        } else {
+            Err(std::io::Error::other("No stream"))
+        }
+    }
+}
+
 impl XmppStream {
     fn new(tcp_stream: TcpStream) -> Self {
         XmppStream {
@@ -172,27 +211,8 @@ impl XmppStream {
         }
     }
 
-    fn write_all(&mut self, buf: &[u8]) -> Result<(), std::io::Error> {
-        if let Some(tcp) = &mut self.tcp_stream {
-            Ok(tcp.write_all(buf)?)
-        } else if let Some(tls) = &mut self.tls_stream {
-            Ok(tls.write_all(buf)?)
-        } else {
-            Err(std::io::Error::other("No stream"))
-        }
-    }
-
     fn upgrade(&mut self, jid: &Jid) -> Result<(), XmppClientError> {
-        let root_store = RootCertStore {
-            roots: TLS_SERVER_ROOTS.into(),
-        };
-        let config = rustls::ClientConfig::builder_with_provider(
-            rustls::crypto::aws_lc_rs::default_provider().into(),
-        )
-        .with_safe_default_protocol_versions()?
-        .with_root_certificates(root_store)
-        .with_no_client_auth();
-
+        let config = tls_config()?;
         let server_name = jid.domainpart().to_owned().try_into()?;
         let conn = rustls::ClientConnection::new(Arc::new(config), server_name)?;
         match self.tcp_stream.take() {
@@ -229,28 +249,21 @@ impl XmppClient {
     }
 
     pub fn send_stanza(&mut self, stanza: Document) -> Result<(), XmppClientError> {
-        self.send_bytes(stanza.to_string().into_bytes())
+        if self.debug {
+            println!("Sending stanza: {stanza}");
+        }
+        // Streamed into the connection without an intermediate string
+        stanza.write_to(&mut self.stream, &SerializeOptions::compact())?;
+        Ok(())
     }
 
     pub fn send_message(&mut self, jid: Jid, body: &str) -> Result<(), XmppClientError> {
-        let stanza = Document::new("message")?;
-        stanza
-            .root()
-            .set_attribute("to", Some(jid.full()))?
-            .insert_tag("body")?
-            .insert_cdata(body)?;
+        let stanza = self.protocol.message_stanza(&jid, body)?;
         self.send_stanza(stanza)
     }
 
     pub fn request_roster(&mut self) -> Result<(), XmppClientError> {
-        let stanza = Document::new("iq")?;
-        stanza
-            .root()
-            .set_attribute("type", Some("get"))?
-            .set_attribute("from", Some(self.protocol.jid().full()))?
-            .set_attribute("id", Some("roster"))?
-            .insert_tag("query")?
-            .set_attribute("xmlns", Some("jabber:iq:roster"))?;
+        let stanza = self.protocol.roster_request_stanza()?;
         self.send_stanza(stanza)
     }
 
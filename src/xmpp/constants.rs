@@ -12,9 +12,9 @@ pub const CLIENT_PORT: u16 = 5222;
 
 pub const SERVER_PORT: u16 = 5269;
 
-pub const STREAM_TAG: &str = "stream:stream";
+pub const STREAM_TAG: &str = "stream";
 
-pub const FEATURES_TAG: &str = "stream:features";
+pub const FEATURES_TAG: &str = "features";
 
 pub const PROCEED_TAG: &str = "proceed";
 
@@ -28,6 +28,6 @@ pub const IQ_TAG: &str = "iq";
 
 //pub const SASL_NS: &str = "urn:ietf:params:xml:ns:xmpp-sasl";
 
-//pub const STREAM_NS: &str = "http://etherx.jabber.org/streams";
+pub const STREAM_NS: &str = "http://etherx.jabber.org/streams";
 
 //pub const CLIENT_NS: &str = "jabber:client";
@@ -20,6 +20,7 @@ pub enum XmppClientError {
     NoMemory,
     BadXml(&'static str),
     BadStream(&'static str),
+    LimitExceeded(ParseError),
     IOError(std::io::Error),
     TlsError(Box<dyn std::error::Error + Send + Sync>),
 }
@@ -30,6 +31,7 @@ impl Display for XmppClientError {
             XmppClientError::NoMemory => write!(f, "not enough memory"),
             XmppClientError::BadXml(msg) => write!(f, "invalid XML syntax: {msg}"),
             XmppClientError::BadStream(msg) => write!(f, "invalid stream protocol: {msg}"),
+            XmppClientError::LimitExceeded(err) => err.fmt(f),
             XmppClientError::IOError(err) => err.fmt(f),
             XmppClientError::TlsError(err) => err.fmt(f),
         }
@@ -52,6 +54,7 @@ impl From<StreamError> for XmppClientError {
             StreamError::NoMemory => XmppClientError::NoMemory,
             StreamError::BadXml(msg) => XmppClientError::BadXml(msg),
             StreamError::BadStream(msg) => XmppClientError::BadStream(msg),
+            StreamError::LimitExceeded(err) => XmppClientError::LimitExceeded(err),
         }
     }
 }
@@ -60,7 +63,8 @@ impl From<ParseError> for XmppClientError {
     fn from(err: ParseError) -> Self {
         match err {
             ParseError::NoMemory => XmppClientError::NoMemory,
-            ParseError::BadXml(msg) => XmppClientError::BadXml(msg),
+            ParseError::BadXml { kind, .. } => XmppClientError::BadXml(kind.description()),
+            _ => XmppClientError::LimitExceeded(err),
         }
     }
 }
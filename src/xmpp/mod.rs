@@ -8,6 +8,8 @@
 ** the License, or (at your option) any later version.
 */
 
+#[cfg(feature = "async")]
+mod async_client;
 mod client;
 pub(crate) mod constants;
 mod error;
@@ -15,12 +17,16 @@ mod jid;
 mod protocol;
 mod stream;
 
+#[cfg(feature = "async")]
+pub use async_client::AsyncXmppClient;
 pub use client::XmppClient;
 pub use client::XmppClientBuilder;
 pub use error::XmppClientError;
 pub use jid::BadJid;
 pub use jid::Jid;
 pub use protocol::XmppClientProtocol;
+#[cfg(feature = "async")]
+pub use stream::AsyncStreamReader;
 pub use stream::StreamElement;
 pub use stream::StreamError;
 pub use stream::StreamParser;
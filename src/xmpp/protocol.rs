@@ -13,6 +13,7 @@ use base64::engine::general_purpose::STANDARD;
 
 use crate::Document;
 use crate::Jid;
+use crate::ParseError;
 use crate::StreamElement;
 use crate::StreamError;
 use crate::StreamParser;
@@ -103,6 +104,30 @@ impl XmppClientProtocol {
         &self.jid
     }
 
+    /// Builds a chat message stanza to the given address.
+    pub fn message_stanza(&self, jid: &Jid, body: &str) -> Result<Document, ParseError> {
+        let stanza = Document::new(MESSAGE_TAG)?;
+        stanza
+            .root()
+            .set_attribute("to", Some(jid.full()))?
+            .insert_tag("body")?
+            .insert_cdata(body)?;
+        Ok(stanza)
+    }
+
+    /// Builds a roster request stanza from the client.
+    pub fn roster_request_stanza(&self) -> Result<Document, ParseError> {
+        let stanza = Document::new(IQ_TAG)?;
+        stanza
+            .root()
+            .set_attribute("type", Some("get"))?
+            .set_attribute("from", Some(self.jid.full()))?
+            .set_attribute("id", Some("roster"))?
+            .insert_tag("query")?
+            .set_attribute("xmlns", Some("jabber:iq:roster"))?;
+        Ok(stanza)
+    }
+
     pub fn events<'a>(&'a mut self, bytes: &'a [u8]) -> XmppClientProtocolEvents<'a> {
         XmppClientProtocolEvents::new(self, bytes)
     }
@@ -119,8 +144,9 @@ impl XmppClientProtocol {
         &mut self,
         element: Document,
     ) -> Result<(XmppClientProtocolEvent, bool), StreamError> {
-        match element.root().name() {
-            STREAM_TAG => match self.state {
+        let root = element.root();
+        match self.stream_parser.stream_local_name(&root) {
+            Some(STREAM_TAG) => match self.state {
                 StreamState::StartSent => {
                     self.state = StreamState::StartReceived;
                     Ok((XmppClientProtocolEvent::Continue, false))
@@ -138,7 +164,7 @@ impl XmppClientProtocol {
                     Err(StreamError::BadStream("Unexpected stream tag"))
                 }
             },
-            FEATURES_TAG => match self.state {
+            Some(FEATURES_TAG) => match self.state {
                 StreamState::StartReceived => {
                     self.state = StreamState::FeaturesReceived;
                     let mut bytes = Vec::new();
@@ -183,6 +209,19 @@ impl XmppClientProtocol {
                     Err(StreamError::BadStream("Unexpected features tag"))
                 }
             },
+            Some(_) => {
+                self.state = StreamState::Error;
+                Err(StreamError::BadStream("Unknown stream tag"))
+            }
+            None => self.receive_stanza(element),
+        }
+    }
+
+    fn receive_stanza(
+        &mut self,
+        element: Document,
+    ) -> Result<(XmppClientProtocolEvent, bool), StreamError> {
+        match element.root().name() {
             PROCEED_TAG => {
                 self.state = StreamState::Handshake;
                 self.stream_parser.reset();
@@ -0,0 +1,105 @@
+/*
+** This file is a part of Iksemel (XML parser for Jabber/XMPP)
+** Copyright (C) 2000-2026 Gurer Ozen
+**
+** Iksemel is free software: you can redistribute it and/or modify it
+** under the terms of the GNU Lesser General Public License as
+** published by the Free Software Foundation, either version 3 of
+** the License, or (at your option) any later version.
+*/
+
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+
+use crate::ReadError;
+
+use super::StreamElement;
+use super::StreamParser;
+
+const READ_BUFFER_SIZE: usize = 4096;
+
+/// A stream parser pulling its input from a tokio [AsyncRead] source.
+///
+/// Only available with the `async` feature.
+///
+/// # Examples
+///
+/// ```
+/// use iks::{AsyncStreamReader, StreamElement};
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), iks::ReadError> {
+///
+/// let input = "<stream:stream xmlns:stream='http://etherx.jabber.org/streams'><a/>".as_bytes();
+/// let mut reader = AsyncStreamReader::new(input);
+/// let mut count = 0;
+/// while let Some(result) = reader.next().await {
+///     if let StreamElement::Element(_) = result? {
+///         count += 1;
+///     }
+/// }
+/// assert_eq!(count, 2);
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncStreamReader<R: AsyncRead + Unpin> {
+    reader: R,
+    parser: StreamParser,
+    buffer: Vec<u8>,
+    pos: usize,
+    len: usize,
+}
+
+impl<R: AsyncRead + Unpin> AsyncStreamReader<R> {
+    /// Creates a new reader adapter with a default stream parser.
+    pub fn new(reader: R) -> Self {
+        AsyncStreamReader::with_parser(reader, StreamParser::new())
+    }
+
+    /// Creates a new reader adapter with the given stream parser.
+    pub fn with_parser(reader: R, parser: StreamParser) -> Self {
+        AsyncStreamReader {
+            reader,
+            parser,
+            buffer: vec![0u8; READ_BUFFER_SIZE],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns the stream parser used by the reader.
+    pub fn parser(&self) -> &StreamParser {
+        &self.parser
+    }
+
+    /// Returns the underlying reader and stream parser.
+    pub fn into_inner(self) -> (R, StreamParser) {
+        (self.reader, self.parser)
+    }
+
+    /// Returns the next top level element of the stream.
+    ///
+    /// `None` is returned when the end of the input is reached. Errors
+    /// from the reader are returned separately from the parse errors.
+    pub async fn next(&mut self) -> Option<Result<StreamElement, ReadError>> {
+        loop {
+            if self.pos == self.len {
+                match self.reader.read(&mut self.buffer).await {
+                    Ok(0) => return None,
+                    Ok(len) => {
+                        self.pos = 0;
+                        self.len = len;
+                    }
+                    Err(err) => return Some(Err(err.into())),
+                }
+            }
+            match self.parser.parse_bytes(&self.buffer[self.pos..self.len]) {
+                Ok(Some((element, size))) => {
+                    self.pos += size;
+                    return Some(Ok(element));
+                }
+                Ok(None) => self.pos = self.len,
+                Err(err) => return Some(Err(err.into())),
+            }
+        }
+    }
+}
@@ -13,11 +13,12 @@ use std::fmt::Display;
 
 use crate::ParseError;
 
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Clone)]
 pub enum StreamError {
     NoMemory,
     BadXml(&'static str),
     BadStream(&'static str),
+    LimitExceeded(ParseError),
 }
 
 impl Display for StreamError {
@@ -26,6 +27,7 @@ impl Display for StreamError {
             StreamError::NoMemory => write!(f, "not enough memory"),
             StreamError::BadXml(msg) => write!(f, "invalid XML syntax: {msg}"),
             StreamError::BadStream(msg) => write!(f, "invalid stream protocol: {msg}"),
+            StreamError::LimitExceeded(err) => err.fmt(f),
         }
     }
 }
@@ -36,7 +38,8 @@ impl From<ParseError> for StreamError {
     fn from(err: ParseError) -> Self {
         match err {
             ParseError::NoMemory => StreamError::NoMemory,
-            ParseError::BadXml(msg) => StreamError::BadXml(msg),
+            ParseError::BadXml { kind, .. } => StreamError::BadXml(kind.description()),
+            _ => StreamError::LimitExceeded(err),
         }
     }
 }
@@ -8,15 +8,24 @@
 ** the License, or (at your option) any later version.
 */
 
+#[cfg(feature = "async")]
+mod async_reader;
 mod error;
 
+use crate::Cursor;
 use crate::Document;
 use crate::DocumentBuilder;
 use crate::ParseError;
+use crate::ParserLimits;
+use crate::QName;
 use crate::SaxElement;
 use crate::SaxParser;
+use crate::parser::NamespaceStack;
+use crate::parser::declared_prefix;
 
 use super::constants::*;
+#[cfg(feature = "async")]
+pub use async_reader::AsyncStreamReader;
 pub use error::StreamError;
 
 pub enum StreamElement {
@@ -61,14 +70,32 @@ pub struct StreamParser {
     sax_parser: SaxParser,
     builder: DocumentBuilder,
     level: usize,
+    stream_tag: Option<String>,
+    // Namespace declarations of the stream tag, in scope for all stanzas
+    stream_namespaces: NamespaceStack,
+}
+
+fn is_stream_tag(root: Cursor) -> bool {
+    // Servers are free to choose any prefix for the stream namespace
+    root.local_name() == STREAM_TAG && root.namespace_uri() == Some(STREAM_NS)
 }
 
 impl StreamParser {
     pub fn new() -> Self {
+        Self::with_limits(ParserLimits::unlimited())
+    }
+
+    /// Creates a new stream parser which enforces the given resource limits.
+    ///
+    /// Stream start tag counts as a nesting level, and the total bytes
+    /// limit applies to the whole stream, not to the individual stanzas.
+    pub fn with_limits(limits: ParserLimits) -> Self {
         Self {
-            sax_parser: SaxParser::new(),
+            sax_parser: SaxParser::with_limits(limits),
             builder: DocumentBuilder::new(),
             level: 0,
+            stream_tag: None,
+            stream_namespaces: NamespaceStack::new(),
         }
     }
 
@@ -76,6 +103,33 @@ impl StreamParser {
         self.sax_parser.reset();
         self.builder.take();
         self.level = 0;
+        self.stream_tag = None;
+        self.stream_namespaces.reset();
+    }
+
+    /// Returns the local name if the given element is in the stream namespace.
+    ///
+    /// The prefix of the element name is resolved with the namespace
+    /// declarations of the element itself, and then with the ones of
+    /// the stream start tag which are in scope for all stanzas. This
+    /// always returns None for the stanzas before the stream start tag
+    /// is parsed.
+    pub fn stream_local_name<'b>(&self, element: &'b Cursor) -> Option<&'b str> {
+        let prefix = QName::split(element.name()).prefix.unwrap_or("");
+        let is_declared = element
+            .clone()
+            .attributes()
+            .any(|(name, _)| declared_prefix(name) == Some(prefix));
+        let namespace = if is_declared {
+            element.namespace_uri()
+        } else {
+            self.stream_namespaces.lookup(prefix)
+        };
+        if namespace == Some(STREAM_NS) {
+            Some(element.local_name())
+        } else {
+            None
+        }
     }
 
     pub fn elements<'a>(&'a mut self, bytes: &'a [u8]) -> StreamElements<'a> {
@@ -106,7 +160,7 @@ impl StreamParser {
                     self.level -= 1;
                 }
                 SaxElement::EndTag(name) => {
-                    if self.level == 0 && name == STREAM_TAG {
+                    if self.level == 0 && self.stream_tag.as_deref() == Some(name) {
                         return Ok(Some((StreamElement::End, bytes_parsed)));
                     }
                     self.level -= 1;
@@ -120,10 +174,17 @@ impl StreamParser {
                         && self
                             .builder
                             .peek()
-                            .is_some_and(|doc| doc.root().name() == STREAM_TAG)
+                            .is_some_and(|doc| is_stream_tag(doc.root()))
                         && let Some(doc) = self.builder.take()
                     {
                         self.level = 0;
+                        self.stream_tag = Some(doc.root().name().to_string());
+                        self.stream_namespaces.reset();
+                        for (name, uri) in doc.root().attributes() {
+                            if let Some(prefix) = declared_prefix(name) {
+                                self.stream_namespaces.declare(prefix, uri)?;
+                            }
+                        }
                         return Ok(Some((StreamElement::Element(doc), bytes_parsed)));
                     }
                 }
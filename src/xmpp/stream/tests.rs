@@ -8,6 +8,9 @@
 ** the License, or (at your option) any later version.
 */
 
+use std::str::FromStr;
+
+use crate::ParserLimits;
 use crate::StreamElement;
 
 use super::*;
@@ -17,7 +20,7 @@ fn check_stream(stream_text: &str, expected: &[&str]) {
     let mut current: usize = 0;
     let mut ended: bool = false;
 
-    let mut elements = parser.elements(&stream_text.as_bytes());
+    let mut elements = parser.elements(stream_text.as_bytes());
     while let Some(element) = elements.next() {
         assert!(!ended);
         match element.unwrap() {
@@ -52,3 +55,113 @@ fn stream_elements() {
         ],
     );
 }
+
+#[test]
+fn stream_prefixes() {
+    check_stream(
+        "<s:stream xmlns:s='http://etherx.jabber.org/streams' version='1.0'>\
+        <s:features/>\
+        <message><body>Hi</body></message>\
+        </s:stream>",
+        &[
+            "<s:stream xmlns:s=\"http://etherx.jabber.org/streams\" version=\"1.0\"/>",
+            "<s:features/>",
+            "<message><body>Hi</body></message>",
+        ],
+    );
+
+    let mut parser = StreamParser::new();
+    assert_eq!(local_name(&parser, "<stream:features/>"), None);
+    let mut elements = parser.elements(b"<x:stream xmlns:x='http://etherx.jabber.org/streams'>");
+    assert!(matches!(
+        elements.next(),
+        Some(Ok(StreamElement::Element(_)))
+    ));
+    assert_eq!(
+        local_name(&parser, "<x:features/>"),
+        Some("features".to_string())
+    );
+    assert_eq!(local_name(&parser, "<stream:features/>"), None);
+    assert_eq!(local_name(&parser, "<message/>"), None);
+    assert_eq!(
+        local_name(
+            &parser,
+            "<y:error xmlns:y='http://etherx.jabber.org/streams'/>"
+        ),
+        Some("error".to_string())
+    );
+    assert_eq!(local_name(&parser, "<x:error xmlns:x='urn:other'/>"), None);
+}
+
+fn local_name(parser: &StreamParser, xml: &str) -> Option<String> {
+    let doc = Document::from_str(xml).unwrap();
+    parser.stream_local_name(&doc.root()).map(str::to_string)
+}
+
+#[test]
+fn stream_default_namespace() {
+    let stream_text = "<stream xmlns='http://etherx.jabber.org/streams' version='1.0'>\
+        <features/>\
+        <iq xmlns='jabber:client' type='result'/>\
+        <message xmlns='jabber:client'><body>Hi</body></message>\
+        </stream>";
+    check_stream(
+        stream_text,
+        &[
+            "<stream xmlns=\"http://etherx.jabber.org/streams\" version=\"1.0\"/>",
+            "<features/>",
+            "<iq xmlns=\"jabber:client\" type=\"result\"/>",
+            "<message xmlns=\"jabber:client\"><body>Hi</body></message>",
+        ],
+    );
+
+    let mut parser = StreamParser::new();
+    let mut documents = Vec::new();
+    let mut elements = parser.elements(stream_text.as_bytes());
+    while let Some(Ok(StreamElement::Element(document))) = elements.next() {
+        documents.push(document);
+    }
+    let names: Vec<Option<String>> = documents
+        .iter()
+        .map(|document| {
+            parser
+                .stream_local_name(&document.root())
+                .map(str::to_string)
+        })
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            Some("stream".to_string()),
+            Some("features".to_string()),
+            None,
+            None
+        ]
+    );
+}
+
+#[test]
+fn stream_limits() {
+    let limits = ParserLimits {
+        max_cdata_length: 8,
+        ..ParserLimits::default()
+    };
+    let mut parser = StreamParser::with_limits(limits);
+    let mut elements = parser.elements(
+        b"<stream:stream xmlns:stream='http://etherx.jabber.org/streams'>\
+        <message><body>12345678</body></message>\
+        <message><body>123456789</body></message>",
+    );
+    assert!(matches!(
+        elements.next(),
+        Some(Ok(StreamElement::Element(_)))
+    ));
+    assert!(matches!(
+        elements.next(),
+        Some(Ok(StreamElement::Element(_)))
+    ));
+    assert!(matches!(
+        elements.next(),
+        Some(Err(StreamError::LimitExceeded(ParseError::CDataTooLong)))
+    ));
+}
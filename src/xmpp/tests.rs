@@ -9,6 +9,9 @@
 */
 
 use super::client::need_port;
+use super::protocol::XmppClientProtocolEvent;
+use crate::Jid;
+use crate::XmppClientProtocol;
 
 #[test]
 fn host_port_checking() {
@@ -17,3 +20,120 @@ fn host_port_checking() {
     assert!(need_port("[::1]"));
     assert!(!need_port("[::1]:5222"));
 }
+
+#[test]
+fn unprefixed_stream() {
+    let jid = Jid::new("user@example.com/res").unwrap();
+    let mut protocol = XmppClientProtocol::new(jid, "pass".to_string());
+    assert!(protocol.send_bytes().is_some());
+
+    // Stanzas declare their own namespace under a default stream namespace
+    let bytes = "<stream xmlns='http://etherx.jabber.org/streams' version='1.0'>\
+        <features/>\
+        <message xmlns='jabber:client'><body>Hi</body></message>\
+        <proceed xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>";
+    let mut events = protocol.events(bytes.as_bytes());
+    assert!(matches!(
+        events.next(),
+        Some(Ok(XmppClientProtocolEvent::Continue))
+    ));
+    assert!(matches!(
+        events.next(),
+        Some(Ok(XmppClientProtocolEvent::Send(_)))
+    ));
+    let Some(Ok(XmppClientProtocolEvent::Stanza(stanza))) = events.next() else {
+        unreachable!("expected a stanza");
+    };
+    assert_eq!(stanza.root().name(), "message");
+    assert!(matches!(
+        events.next(),
+        Some(Ok(XmppClientProtocolEvent::StartTls))
+    ));
+}
+
+#[cfg(feature = "async")]
+mod async_tests {
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+
+    use crate::AsyncStreamReader;
+    use crate::AsyncXmppClient;
+    use crate::Jid;
+    use crate::ReadError;
+    use crate::StreamElement;
+    use crate::XmppClientProtocol;
+
+    fn assert_send<T: Send>(_: T) {}
+
+    #[allow(
+        dead_code,
+        reason = "only checks that the futures can move across threads"
+    )]
+    fn client_futures_are_send(client: &mut AsyncXmppClient) {
+        assert_send(client.wait_for_stanza());
+    }
+
+    #[tokio::test]
+    async fn stream_reader() {
+        let (mut writer, reader) = tokio::io::duplex(8);
+        let input = concat!(
+            "<stream:stream xmlns:stream='http://etherx.jabber.org/streams'>",
+            "<message><body>hello</body></message>",
+            "<presence/>",
+            "</stream:stream>"
+        );
+        tokio::spawn(async move {
+            writer.write_all(input.as_bytes()).await.unwrap();
+        });
+        let mut reader = AsyncStreamReader::new(reader);
+        let mut names = Vec::new();
+        while let Some(result) = reader.next().await {
+            match result.unwrap() {
+                StreamElement::Element(doc) => names.push(doc.root().name().to_string()),
+                StreamElement::End => names.push("end".to_string()),
+            }
+        }
+        assert_eq!(names, ["stream:stream", "message", "presence", "end"]);
+
+        let mut reader = AsyncStreamReader::new("<a></b>".as_bytes());
+        assert!(matches!(
+            reader.next().await,
+            Some(Err(ReadError::Parse(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn client_over_duplex() {
+        let (client_io, mut server) = tokio::io::duplex(4096);
+        let jid = Jid::new("user@example.com/res").unwrap();
+        let protocol = XmppClientProtocol::new(jid, "pass".to_string());
+        let mut client = AsyncXmppClient::with_stream(protocol, Box::new(client_io), false);
+
+        server
+            .write_all(
+                b"<stream:stream xmlns:stream='http://etherx.jabber.org/streams'>\
+                <message from='friend@example.com'><body>Hi</body></message>",
+            )
+            .await
+            .unwrap();
+        let stanza = client.wait_for_stanza().await.unwrap();
+        assert_eq!(stanza.root().name(), "message");
+        assert_eq!(stanza.root().attribute("from"), Some("friend@example.com"));
+
+        client
+            .send_message(Jid::new("friend@example.com").unwrap(), "Hello")
+            .await
+            .unwrap();
+        client.request_roster().await.unwrap();
+        drop(client);
+
+        let mut sent = String::new();
+        server.read_to_string(&mut sent).await.unwrap();
+        assert!(sent.starts_with("<?xml version='1.0'?><stream:stream "));
+        assert!(sent.ends_with(concat!(
+            "<message to=\"friend@example.com\"><body>Hello</body></message>",
+            "<iq type=\"get\" from=\"user@example.com/res\" id=\"roster\">",
+            "<query xmlns=\"jabber:iq:roster\"/></iq>"
+        )));
+    }
+}